@@ -6,51 +6,532 @@ use cosmic::{
 };
 use tracing::{error, info, warn};
 
+static SEARCH_ID: std::sync::LazyLock<cosmic::widget::Id> =
+    std::sync::LazyLock::new(|| cosmic::widget::Id::new("search"));
+static LIST_ID: std::sync::LazyLock<cosmic::widget::Id> =
+    std::sync::LazyLock::new(|| cosmic::widget::Id::new("list"));
+
+/// The height of the headings between favorites and other entries in the main list.
+const HEADING_HEIGHT: f32 = 28.0;
+/// The tallest the main list grows before it scrolls.
+const LIST_MAX_HEIGHT: f32 = 480.0;
+/// How far beyond the visible part of the main list rows are still built.
+const LIST_OVERSCAN: f32 = 100.0;
+
+mod brands;
+#[cfg(feature = "camera")]
+mod camera;
+mod clipboard;
+mod clock;
+mod config;
 mod entry;
 mod errors;
+mod formats;
+mod icon_packs;
+mod icons;
+#[cfg(feature = "keepass")]
+mod keepass;
+mod lock;
+mod presets;
+mod qr;
 mod secrets;
+mod selftest;
+mod session;
+mod shortcuts;
+#[cfg(feature = "tpm")]
+mod tpm;
+
+/// A row of the main list, which have heights known ahead of layout so only the visible ones
+/// need to be built.
+enum ListRow {
+    Heading(&'static str),
+    /// The entry at `idx` in the vault, shown at `pos` in [`App::display_order`].
+    Entry {
+        pos: usize,
+        idx: usize,
+    },
+}
+impl ListRow {
+    fn height(&self, entries: &[entry::Entry], display: entry::DisplayOptions) -> f32 {
+        match self {
+            Self::Heading(_) => HEADING_HEIGHT,
+            Self::Entry { idx, .. } => entries[*idx].row_height(display),
+        }
+    }
+}
 
+#[derive(Default)]
 pub struct Editing {
     entry: Option<usize>,
+    /// The changes to `entry`, which only replace it once editing is finished.
+    draft: Option<entry::Entry>,
+    /// Whether cancelling is waiting for changes to the draft to be confirmed as discarded.
+    confirm_discard: bool,
+    /// Earlier versions of the draft, most recent last.
+    undo: Vec<entry::Entry>,
+    /// Versions of the draft that were undone, most recently undone last.
+    redo: Vec<entry::Entry>,
+    /// Entries ticked for bulk actions.
+    selected: Vec<bool>,
+    group: String,
+    confirm_delete: bool,
+}
+impl Editing {
+    fn entry(idx: usize, entry: &entry::Entry) -> Self {
+        let mut draft = entry.clone();
+        // Every edit starts with the secret masked again
+        draft.show_secret = false;
+        Self {
+            entry: Some(idx),
+            draft: Some(draft),
+            ..Self::default()
+        }
+    }
+
+    fn selection(&self) -> impl Iterator<Item = usize> {
+        self.selected
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, selected)| selected.then_some(idx))
+    }
+}
+
+/// The most changes to an entry that can be undone.
+const EDIT_HISTORY: usize = 100;
+
+/// How long to wait for further changes before writing the vault to the keyring.
+const SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+/// How long deleted entries can be restored before the deletion is saved.
+const UNDO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Entries removed from the vault that can still be restored.
+pub struct Deleted {
+    /// The entries with their former indices, in ascending order.
+    entries: Vec<(usize, entry::Entry)>,
+    id: u64,
+}
+
+/// An encrypted import waiting for its password.
+pub struct PendingImport {
+    data: std::sync::Arc<[u8]>,
+    password: String,
+    /// Why the last password didn't work.
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    Skip,
+    Overwrite,
+    /// Keeps the existing entry, filling in its missing details.
+    Merge,
+    AddCopy,
+}
+impl DuplicateAction {
+    const ALL: [Self; 4] = [Self::Skip, Self::Overwrite, Self::Merge, Self::AddCopy];
+    const LABELS: [&str; 4] = ["Skip", "Overwrite", "Merge", "Add copy"];
+}
+
+pub struct ImportCandidate {
+    entry: entry::Entry,
+    selected: bool,
+    /// The id of an identical entry already in the vault, as indices change while the preview
+    /// is open.
+    existing: Option<String>,
+    /// Whether an earlier candidate in the preview is the same entry.
+    repeated: bool,
+    action: DuplicateAction,
+}
+
+/// Parsed entries waiting to be confirmed before they're added to the vault.
+#[derive(Default)]
+pub struct ImportPreview {
+    candidates: Vec<ImportCandidate>,
+    /// Whether a multi-code Google Authenticator transfer is still missing codes.
+    incomplete_batch: bool,
+}
+
+/// The order entries are shown in the popup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SortMode {
+    /// The order set in the edit view.
+    #[default]
+    Manual,
+    /// Most recently copied first.
+    RecentlyUsed,
+}
+
+/// Reading or writing the vault, which is retried when it fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageOp {
+    Load,
+    Save,
+}
+
+/// A vault read or write that failed.
+pub struct StorageFailure {
+    op: StorageOp,
+    /// Failures in a row.
+    attempts: u32,
+    error: String,
+    /// Whether another attempt is scheduled, rather than waiting for the user.
+    retrying: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EncryptedExport {
+    Aegis,
+    Backup,
+}
+
+/// Exporting entries to files or other authenticators.
+pub struct Export {
+    selected: Vec<bool>,
+    codes: Vec<cosmic::iced::widget::qr_code::Data>,
+    page: usize,
+    /// The format, password and its confirmation, when exporting an encrypted file.
+    encrypted: Option<(EncryptedExport, String, String)>,
+    /// Whether the unencrypted export warning is shown.
+    confirm_uri_list: bool,
+}
+
+/// Checking a code from elsewhere against an entry.
+pub struct Verify {
+    /// The names of the entries, in vault order.
+    names: Vec<String>,
+    entry: Option<usize>,
+    code: String,
+    result: Option<String>,
+}
+
+/// A random secret for setting up TOTP on a server, shown with its QR code for enrolling devices.
+pub struct Generator {
+    /// The length of the secret in bytes.
+    bytes: usize,
+    /// Holds the secret, and the names shown in authenticators.
+    entry: entry::Entry,
+    qr: Option<cosmic::iced::widget::qr_code::Data>,
+}
+impl Generator {
+    fn new(mut entry: entry::Entry, bytes: usize) -> Self {
+        entry.totp.secret = entry::random_secret(bytes);
+        entry.secret = entry.totp.get_secret_base32();
+        let mut generator = Self {
+            bytes,
+            entry,
+            qr: None,
+        };
+        generator.refresh_qr();
+        generator
+    }
+
+    fn refresh_qr(&mut self) {
+        self.qr = cosmic::iced::widget::qr_code::Data::new(self.entry.uri())
+            .inspect_err(|e| warn!("Couldn't create QR code: {e}"))
+            .ok();
+    }
 }
 
+/// The secret length generated by default, the 160 bits RFC 4226 recommends.
+const GENERATOR_BYTES: usize = 20;
+
+/// How many steps either side of the current one a code is looked for when verifying, to tell
+/// how far off a clock is when it's outside the skew.
+const VERIFY_SEARCH_STEPS: i64 = 20;
+
 pub struct App {
     core: cosmic::app::Core,
-    config: cosmic::cosmic_config::Config,
+    config_handler: cosmic::cosmic_config::Config,
+    config: config::Config,
+    /// Whether to copy the quick copy entry's code once the vault has been unlocked.
+    copy_on_unlock: bool,
+    /// The unlock prompt, while the app lock is set and hasn't been unlocked.
+    lock: Option<lock::Prompt>,
+    /// The new app lock passphrase or PIN, while one is being set.
+    lock_setup: Option<lock::Setup>,
     popup: Option<cosmic::iced::window::Id>,
+    search: String,
+    /// Codes are masked because the popup hasn't been used for a while.
+    idle: bool,
+    last_activity: std::time::Instant,
+    /// Whether the desktop has animations turned off.
+    system_reduce_motion: bool,
+    show_settings: bool,
+    /// The keyboard selection, as a position in [`App::display_order`].
+    selected: Option<usize>,
+    /// The visible part of the main list, unknown until it's first scrolled.
+    list_viewport: Option<cosmic::iced::widget::scrollable::Viewport>,
 
     secret: secrets::State,
+    /// The key of the vault's extra encryption layer, once its passphrase has been entered.
+    vault_key: Option<secrets::VaultKey>,
+    vault_passphrase: String,
+    vault_error: Option<String>,
+    /// Whether the keyring is showing its unlock prompt.
+    unlocking_keyring: bool,
+    storage_failure: Option<StorageFailure>,
+    /// The users with a vault in the store, offered at login.
+    found_users: Vec<String>,
+    login_error: Option<String>,
+    /// Bumped when the codes' timers can't be trusted any more, such as after sleeping, to start
+    /// them again.
+    timers: u64,
+    /// How many seconds the clock was behind the NTP server when last checked.
+    clock_offset: Option<f64>,
+    /// The NTP server being typed in the settings, only checked against once it's submitted.
+    ntp_server_input: Option<String>,
+    /// The failures of the last code generation self-test, once it's been run.
+    self_test: Option<Vec<String>>,
+    /// The new vault passphrase, while the extra encryption layer is being set up.
+    vault_setup: Option<lock::Setup>,
     new_entry: Option<entry::Entry>,
+    /// The entry of the quick generator, which is never saved.
+    scratch: Option<entry::Entry>,
     entry_error: Option<String>,
+    status: Option<String>,
+    imported_batches: Vec<(i32, i32)>,
     editing_entry: Option<Editing>,
     pending_delete: Option<usize>,
+    recently_deleted: Option<Deleted>,
+    undo_id: u64,
+    /// Whether the vault has changes that haven't been written to the keyring yet.
+    dirty: bool,
+    save_id: u64,
+    /// The existing entry the new entry duplicates.
+    pending_duplicate: Option<usize>,
 
     user: Option<String>,
     migrating: bool,
+    exporting: Option<Export>,
+    generator: Option<Generator>,
+    verifying: Option<Verify>,
+    pending_import: Option<PendingImport>,
+    import_preview: Option<ImportPreview>,
+
+    /// Whether a QR scan is running whose result is still wanted.
+    scanning: bool,
+    #[cfg(feature = "camera")]
+    camera_scan: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+
+    #[cfg(feature = "keepass")]
+    keepass: Vec<entry::Entry>,
+    #[cfg(feature = "keepass")]
+    keepass_path: Option<std::path::PathBuf>,
+    /// Whether the KeePassXC database was opened, as it may have no entries with codes.
+    #[cfg(feature = "keepass")]
+    keepass_unlocked: bool,
+    /// The password being typed to unlock the KeePassXC database.
+    #[cfg(feature = "keepass")]
+    keepass_password: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     TogglePopup,
-    RetrievedKey(Result<secrets::State, String>),
+    GlobalShortcut(Result<String, String>),
+    RetrievedKey(Result<secrets::Loaded, String>),
+    /// Keeps the vault in a file, as the keyring can't be reached.
+    UseFileStorage,
     UsernameInput(String),
     UsernameSubmit(String),
+    SetStorage(secrets::Storage),
+    SetKeyringService(String),
+    SetKeyringCollection(String),
     Logout,
+    FoundUsers(Result<Vec<String>, String>),
+    /// Opens the vault of another of the known users.
+    SwitchUser(usize),
+    UnlockInput(String),
+    /// A digit pressed on the PIN keypad.
+    UnlockDigit(char),
+    UnlockBackspace,
+    UnlockSubmit,
+    Unlocked(bool),
+    /// The wait after too many wrong passphrases is over.
+    UnlockRetry,
+    LockNow,
+    AutoLockCheck,
+    SessionLocked,
+    /// The system woke from sleep.
+    Resumed,
+    /// The wall clock was changed.
+    ClockJumped,
+    SetAutoLockSeconds(u64),
+    LockSetup(lock::LockKind),
+    LockSetupInput(String),
+    LockSetupConfirm(String),
+    LockSetupSave,
+    LockSetupCancel,
+    LockHashed(lock::LockKind, Result<String, String>),
+    RemoveLock,
+    VaultPassphraseInput(String),
+    VaultUnlock,
+    VaultUnlocked(Result<(secrets::State, secrets::VaultKey), String>),
+    KeyringUnlock,
+    KeyringUnlocked(Result<(), String>),
+    StorageRetry(StorageOp),
+    /// Replaces an unreadable vault with the entries recovered from it.
+    KeepRecovered,
+    VaultSetup,
+    VaultSetupInput(String),
+    VaultSetupConfirm(String),
+    VaultSetupSave,
+    VaultSetupCancel,
+    VaultKeyDerived(Result<secrets::VaultKey, String>),
+    /// Removes the extra encryption layer, leaving only the keyring's.
+    VaultDecrypt,
+    #[cfg(feature = "tpm")]
+    VaultSeal,
     Save,
     SetKey(Result<(), String>),
     NewEntry,
+    /// Opens the quick generator.
+    QuickGenerate,
+    /// Moves the quick generator's secret and settings to a new entry.
+    ScratchAdd,
+    ScratchClose,
     Entry(entry::EntryR, entry::EntryMessage),
     EntryClearError,
     NewEntryCancel,
     NewEntryAccept,
+    ResolveDuplicate(DuplicateAction),
+    ScanQrImage,
+    ScanQrScreen,
+    #[cfg(feature = "camera")]
+    ScanQrCamera,
+    #[cfg(feature = "camera")]
+    CancelCameraScan,
+    ScannedQr(Result<Vec<String>, String>),
+    SetSortMode(SortMode),
+    ToggleSettings,
+    SetAutoCopySearch(bool),
+    SetCloseOnCopy(bool),
+    SetDownloadLogos(bool),
+    SetShowNextCode(bool),
+    SetGroupDigits(bool),
+    SetProgressBar(bool),
+    SetReduceMotion(bool),
+    SystemReduceMotion(bool),
+    SetWarningBelow(u64),
+    SetCriticalBelow(u64),
+    SetHideCodes(bool),
+    SetRevealSeconds(u64),
+    SetIdleHideSeconds(u64),
+    SetScreenShare(bool),
+    /// Input in the popup, `deliberate` for clicks and key presses rather than pointer movement.
+    PopupActivity {
+        deliberate: bool,
+    },
+    IdleCheck,
+    ConfigChanged(config::Config),
+    SetClearClipboardAfter(u64),
+    SetDefaultAlgorithm(totp_rs::Algorithm),
+    SetDefaultDigits(usize),
+    SetDefaultStep(u64),
+    ImportIconPack,
+    IconPackImported(Result<Option<String>, String>),
+    /// Checks code generation against the RFC 6238 test vectors.
+    SelfTest,
+    ClockChecked(Result<f64, String>),
+    NtpServerInput(String),
+    SetNtpServer(String),
+    SetClockWarningSeconds(u64),
+    /// Sets the seconds added to the clock when generating codes.
+    SetTimeOffset(i64),
+    SearchInput(String),
+    SearchSubmit,
+    FocusSearch,
+    MoveSelection {
+        up: bool,
+    },
+    ListScrolled(cosmic::iced::widget::scrollable::Viewport),
+    CopySelected,
+    ClosePopup,
     EditEntries,
-    MoveEntry { entry: usize, up: bool },
+    EditSelect(usize, bool),
+    BulkGroupInput(String),
+    BulkMoveToGroup,
+    BulkArchive,
+    BulkExport,
+    BulkDelete,
+    BulkDeleteConfirm,
+    BulkDeleteCancel,
+    MoveEntry {
+        entry: usize,
+        up: bool,
+    },
     EditEntry(usize),
-    FinishEdit { only_current: bool },
+    /// Leaves the entry editor, asking first if there are changes.
+    CancelEdit,
+    KeepEditing,
+    DiscardEdit,
+    EditUndo,
+    EditRedo,
+    FinishEdit {
+        only_current: bool,
+    },
     DeleteEntry(usize),
+    DuplicateEntry(usize),
     ClearPendingDelete,
     AcceptPendingDelete,
+    Undo,
+    UndoExpired(u64),
+    SaveDebounced(u64),
     StartMigration,
+    StartExport,
+    ExportSelect(usize, bool),
+    Import,
+    Imported(Result<formats::Imported, String>),
+    ImportPassword(String),
+    ImportUnlock,
+    ImportUnlocked(Result<formats::Imported, String>),
+    ImportCancel,
+    ImportPreviewSelect(usize, bool),
+    ImportPreviewAction(usize, DuplicateAction),
+    ImportPreviewAccept,
+    ImportPreviewCancel,
+    ExportGenerate,
+    ExportPage(usize),
+    ExportEncrypted(EncryptedExport),
+    ExportPassword(String),
+    ExportPasswordConfirm(String),
+    ExportEncryptedSave,
+    ExportUriList,
+    ExportUriListConfirm,
+    ExportUriListCancel,
+    Exported(Result<Option<std::path::PathBuf>, String>),
+    #[cfg(feature = "keepass")]
+    KeePassPick,
+    #[cfg(feature = "keepass")]
+    KeePassPicked(Option<std::path::PathBuf>),
+    #[cfg(feature = "keepass")]
+    KeePassPrompt,
+    #[cfg(feature = "keepass")]
+    KeePassPassword(String),
+    #[cfg(feature = "keepass")]
+    KeePassUnlock,
+    #[cfg(feature = "keepass")]
+    KeePassCancel,
+    #[cfg(feature = "keepass")]
+    KeePassOpened(Result<Vec<entry::Entry>, String>),
+    #[cfg(feature = "keepass")]
+    KeePassClose,
+    FinishExport,
+    StartVerify,
+    VerifyEntry(usize),
+    VerifyInput(String),
+    VerifyCheck,
+    FinishVerify,
+    StartGenerator,
+    GeneratorBytes(usize),
+    GeneratorName(String),
+    GeneratorIssuer(String),
+    GeneratorCopy,
+    /// Replaces the generated secret with a new one.
+    GeneratorRegenerate,
+    /// Moves the generated secret to a new entry.
+    GeneratorAdd,
+    FinishGenerator,
 }
 
 impl cosmic::Application for App {
@@ -74,23 +555,104 @@ impl cosmic::Application for App {
         core: cosmic::app::Core,
         _flags: Self::Flags,
     ) -> (Self, cosmic::app::Task<Self::Message>) {
-        let config = cosmic::cosmic_config::Config::new(crate::APP_ID, crate::CONFIG_VER)
+        let config_handler = cosmic::cosmic_config::Config::new(crate::APP_ID, crate::CONFIG_VER)
             .expect("there should be a config path available");
-        let user = config.get::<Option<String>>("last-user").ok().flatten();
+        let config = config::Config::load(&config_handler);
+        let user = config_handler
+            .get::<Option<String>>("last-user")
+            .ok()
+            .flatten();
+        #[cfg(feature = "keepass")]
+        let keepass_path = config_handler
+            .get::<Option<std::path::PathBuf>>("keepass-database")
+            .ok()
+            .flatten();
+        clock::set_offset(config.time_offset);
+        // Debug builds check code generation on every start, so regressions show in the log
+        let self_test = cfg!(debug_assertions).then(selftest::run);
+        let lock = config
+            .lock_hash
+            .clone()
+            .map(|hash| lock::Prompt::new(hash, config.lock_failures, config.lock_retry_at));
+        // A wait from too many wrong attempts carries on where the last run left it
+        let retry = lock
+            .as_ref()
+            .and_then(|prompt| prompt.retry_at)
+            .map_or_else(Task::none, |at| {
+                let wait = at.saturating_duration_since(std::time::Instant::now());
+                Task::perform(tokio::time::sleep(wait), |()| {
+                    cosmic::Action::App(Message::UnlockRetry)
+                })
+            });
+        for failure in self_test.iter().flatten() {
+            error!("Self-test failed: {failure}");
+        }
         (
             Self {
                 core,
+                config_handler,
                 config,
+                copy_on_unlock: false,
+                lock,
+                lock_setup: None,
                 popup: None,
+                search: String::new(),
+                idle: false,
+                last_activity: std::time::Instant::now(),
+                system_reduce_motion: false,
+                show_settings: false,
+                selected: None,
+                list_viewport: None,
                 secret: secrets::State::PendingUser,
+                vault_key: None,
+                vault_passphrase: String::new(),
+                vault_error: None,
+                unlocking_keyring: false,
+                storage_failure: None,
+                found_users: Vec::new(),
+                login_error: None,
+                timers: 0,
+                clock_offset: None,
+                ntp_server_input: None,
+                self_test,
+                vault_setup: None,
                 user,
                 new_entry: None,
+                scratch: None,
                 entry_error: None,
+                status: None,
+                imported_batches: Vec::new(),
                 editing_entry: None,
                 pending_delete: None,
+                recently_deleted: None,
+                undo_id: 0,
+                dirty: false,
+                save_id: 0,
+                pending_duplicate: None,
                 migrating: false,
+                exporting: None,
+                generator: None,
+                verifying: None,
+                pending_import: None,
+                import_preview: None,
+                scanning: false,
+                #[cfg(feature = "camera")]
+                camera_scan: None,
+                #[cfg(feature = "keepass")]
+                keepass: Vec::new(),
+                #[cfg(feature = "keepass")]
+                keepass_path,
+                #[cfg(feature = "keepass")]
+                keepass_unlocked: false,
+                #[cfg(feature = "keepass")]
+                keepass_password: None,
             },
-            cosmic::app::Task::none(),
+            Task::batch([
+                Task::perform(system_reduce_motion(), |r| {
+                    cosmic::Action::App(Message::SystemReduceMotion(r))
+                }),
+                retry,
+            ]),
         )
     }
 
@@ -107,51 +669,378 @@ impl cosmic::Application for App {
         use cosmic::widget::{button, column, horizontal_space, icon, row, text_input, warning};
 
         let mut content = column().padding(10).spacing(5);
-        if matches!(&self.secret, secrets::State::PendingUser) {
-            content = content.push(
-                text_input("username", self.user.as_deref().unwrap_or(""))
-                    .password()
-                    .on_input(Message::UsernameInput)
-                    .on_submit(Message::UsernameSubmit),
+        if let Some(prompt) = &self.lock {
+            content = content.push(self.view_lock(prompt));
+        } else if matches!(&self.secret, secrets::State::PendingUser) {
+            content = content.push(self.view_login());
+        } else if matches!(&self.secret, secrets::State::Encrypted) {
+            content = content
+                .push(cosmic::widget::text::title3("Vault Passphrase"))
+                .push(
+                    text_input("Passphrase", &self.vault_passphrase)
+                        .password()
+                        .on_input(Message::VaultPassphraseInput)
+                        .on_submit(|_| Message::VaultUnlock),
+                )
+                .push_maybe(
+                    self.vault_error
+                        .as_deref()
+                        .map(cosmic::widget::text::caption),
+                )
+                .push(
+                    row()
+                        .push(button::standard("Log Out").on_press(Message::Logout))
+                        .push(horizontal_space())
+                        .push(button::suggested("Unlock").on_press(Message::VaultUnlock))
+                        .spacing(5),
+                );
+        } else if matches!(&self.secret, secrets::State::KeyringLocked) {
+            let unlock = (!self.unlocking_keyring).then_some(Message::KeyringUnlock);
+            content = content
+                .push(cosmic::widget::text::title3("Vault locked"))
+                .push(cosmic::widget::text::caption(
+                    "The keyring holding the vault is locked.",
+                ))
+                .push_maybe(
+                    self.vault_error
+                        .as_deref()
+                        .map(cosmic::widget::text::caption),
+                )
+                .push(
+                    row()
+                        .push(button::standard("Log Out").on_press(Message::Logout))
+                        .push(horizontal_space())
+                        .push(button::suggested("Unlock").on_press_maybe(unlock))
+                        .spacing(5),
+                );
+        } else if let secrets::State::Corrupt(recovery) = &self.secret {
+            content = content
+                .push(cosmic::widget::text::title3("Vault Damaged"))
+                .push(cosmic::widget::text::caption(&recovery.error))
+                .push(cosmic::widget::text(format!(
+                    "A copy of the damaged vault was saved to {}. {} entries could be recovered, \
+                     keeping them replaces the damaged vault.",
+                    recovery.backup.display(),
+                    recovery.entries.len()
+                )))
+                .push(
+                    row()
+                        .push(button::standard("Log Out").on_press(Message::Logout))
+                        .push(horizontal_space())
+                        .push(button::suggested("Keep Recovered").on_press(Message::KeepRecovered))
+                        .spacing(5),
+                );
+        } else if let Some(setup) = self.vault_setup.as_ref().filter(|_| self.needs_vault_key()) {
+            content = content
+                .push(cosmic::widget::text::title3("Vault Passphrase"))
+                .push(cosmic::widget::text::caption(
+                    "Secrets are stored in a file, choose a passphrase to encrypt it with.",
+                ))
+                .push(view_setup(
+                    setup,
+                    Message::VaultSetupInput,
+                    Message::VaultSetupConfirm,
+                    Message::VaultSetupSave,
+                    Message::Logout,
+                ));
+        } else if let Some(preview) = &self.import_preview {
+            content = content.push(cosmic::widget::text::title1("Import Entries"));
+            if preview.incomplete_batch {
+                content = content.push(
+                    row()
+                        .push(button::standard("Scan QR from image").on_press(Message::ScanQrImage))
+                        .push(button::standard("Scan QR on screen").on_press(Message::ScanQrScreen))
+                        .spacing(5),
+                );
+            }
+            let mut column = cosmic::widget::column();
+            for (idx, candidate) in preview.candidates.iter().enumerate() {
+                let child = candidate
+                    .entry
+                    .view::<false>(entry::DisplayOptions::default())
+                    .map(move |m| {
+                        Message::Entry(entry::EntryR::Import(idx.try_into().unwrap()), m)
+                    });
+                let duplicate = candidate.existing.as_ref().map(|_| {
+                    cosmic::widget::column()
+                        .push(cosmic::widget::text::caption("Already in vault"))
+                        .push(cosmic::widget::dropdown(
+                            &DuplicateAction::LABELS,
+                            DuplicateAction::ALL
+                                .iter()
+                                .position(|a| *a == candidate.action),
+                            move |a| Message::ImportPreviewAction(idx, DuplicateAction::ALL[a]),
+                        ))
+                });
+                column = column.push(
+                    row()
+                        .push(
+                            cosmic::widget::checkbox("", candidate.selected)
+                                .on_toggle(move |b| Message::ImportPreviewSelect(idx, b)),
+                        )
+                        .push(child)
+                        .push(horizontal_space())
+                        .push_maybe(
+                            (candidate.repeated && candidate.existing.is_none())
+                                .then(|| cosmic::widget::text::caption("Repeated in this import")),
+                        )
+                        .push_maybe(duplicate)
+                        .spacing(5)
+                        .align_y(cosmic::iced::Alignment::Center),
+                );
+            }
+            let count = preview
+                .candidates
+                .iter()
+                .filter(|c| {
+                    c.selected && !(c.existing.is_some() && c.action == DuplicateAction::Skip)
+                })
+                .count();
+            content = content.push(column.spacing(5)).push(
+                row()
+                    .push(button::destructive("Cancel").on_press(Message::ImportPreviewCancel))
+                    .push(horizontal_space())
+                    .push(
+                        button::suggested(format!("Import {count}"))
+                            .on_press_maybe((count > 0).then_some(Message::ImportPreviewAccept)),
+                    ),
             );
+        } else if let Some(entry) = &self.scratch {
+            content = content
+                .push(
+                    entry
+                        .view_scratch(self.display())
+                        .map(|m| Message::Entry(entry::EntryR::Scratch, m)),
+                )
+                .push(
+                    row()
+                        .push(horizontal_space())
+                        .push(button::standard("Add as Entry").on_press(Message::ScratchAdd))
+                        .push(button::suggested("Close").on_press(Message::ScratchClose))
+                        .spacing(5),
+                );
         } else if let Some(entry) = &self.new_entry {
+            let scan = row()
+                .push(button::standard("Scan QR from image").on_press(Message::ScanQrImage))
+                .push(button::standard("Scan QR on screen").on_press(Message::ScanQrScreen));
+            #[cfg(feature = "camera")]
+            let scan = if self.camera_scan.is_some() {
+                row()
+                    .push(cosmic::widget::text("Point your camera at a QR code..."))
+                    .push(horizontal_space())
+                    .push(button::destructive("Cancel").on_press(Message::CancelCameraScan))
+                    .align_y(cosmic::iced::Alignment::Center)
+            } else {
+                scan.push(button::standard("Scan QR with camera").on_press(Message::ScanQrCamera))
+            };
+            let savable = entry.check_savable();
             content = content
+                .push(scan.spacing(5))
                 .push(
                     entry
-                        .view_settings(true)
+                        .view_settings(true, self.display())
                         .map(|m| Message::Entry(entry::EntryR::NewEntry, m)),
                 )
                 .push(
                     row()
                         .push(button::destructive("Cancel").on_press(Message::NewEntryCancel))
                         .push(horizontal_space())
-                        .push(button::suggested("Create").on_press(Message::NewEntryAccept)),
-                )
-                .push_maybe(
-                    self.entry_error
-                        .as_deref()
-                        .map(|s| warning(s).on_close(Message::EntryClearError)),
+                        .push_maybe(savable_caption(entry, &savable))
+                        .push(
+                            button::suggested("Create")
+                                .on_press_maybe(savable.is_ok().then_some(Message::NewEntryAccept)),
+                        )
+                        .spacing(5)
+                        .align_y(cosmic::iced::Alignment::Center),
+                );
+        } else if let Some(generator) = &self.generator {
+            content = content.push(view_generator(generator));
+        } else if let Some(verify) = &self.verifying {
+            content = content.push(view_verify(verify));
+        } else if let Some(export) = &self.exporting {
+            content = content.push(cosmic::widget::text::title1("Export Entries"));
+            if let Some(code) = export.codes.get(export.page) {
+                let pages = export.codes.len();
+                content = content
+                    .push(container(cosmic::iced::widget::qr_code(code)).center_x(Length::Fill))
+                    .push(
+                        row()
+                            .push(
+                                button::icon(icon::from_name("go-previous-symbolic"))
+                                    .on_press_maybe(
+                                        export.page.checked_sub(1).map(Message::ExportPage),
+                                    ),
+                            )
+                            .push(horizontal_space())
+                            .push(cosmic::widget::text(format!(
+                                "Code {} of {pages}",
+                                export.page + 1
+                            )))
+                            .push(horizontal_space())
+                            .push(
+                                button::icon(icon::from_name("go-next-symbolic")).on_press_maybe(
+                                    (export.page + 1 < pages)
+                                        .then_some(Message::ExportPage(export.page + 1)),
+                                ),
+                            )
+                            .align_y(cosmic::iced::Alignment::Center),
+                    )
+                    .push(
+                        row()
+                            .push(horizontal_space())
+                            .push(button::suggested("Done").on_press(Message::FinishExport)),
+                    );
+            } else if let Some((format, password, confirm)) = &export.encrypted {
+                let matching = !password.is_empty() && password == confirm;
+                content = content
+                    .push(cosmic::widget::text(match format {
+                        EncryptedExport::Aegis => {
+                            "Choose a password for the Aegis vault, it will be needed to restore it."
+                        }
+                        EncryptedExport::Backup => {
+                            "Choose a password for the backup, it will be needed to restore it."
+                        }
+                    }))
+                    .push(
+                        text_input("Password", password)
+                            .password()
+                            .on_input(Message::ExportPassword),
+                    )
+                    .push(
+                        text_input("Confirm password", confirm)
+                            .password()
+                            .on_input(Message::ExportPasswordConfirm),
+                    )
+                    .push(
+                        row()
+                            .push(button::destructive("Cancel").on_press(Message::FinishExport))
+                            .push(horizontal_space())
+                            .push(
+                                button::suggested("Save").on_press_maybe(
+                                    matching.then_some(Message::ExportEncryptedSave),
+                                ),
+                            ),
+                    );
+            } else {
+                let mut column = cosmic::widget::column();
+                for (idx, entry) in self.secret.as_array().iter().enumerate() {
+                    let child =
+                        entry
+                            .view::<false>(entry::DisplayOptions::default())
+                            .map(move |m| {
+                                Message::Entry(entry::EntryR::Index(idx.try_into().unwrap()), m)
+                            });
+                    column = column.push(
+                        row()
+                            .push(
+                                cosmic::widget::checkbox(
+                                    "",
+                                    export.selected.get(idx).copied().unwrap_or_default(),
+                                )
+                                .on_toggle(move |b| Message::ExportSelect(idx, b)),
+                            )
+                            .push(child)
+                            .spacing(5)
+                            .align_y(cosmic::iced::Alignment::Center),
+                    );
+                }
+                let any_selected = export.selected.contains(&true);
+                let google = self
+                    .secret
+                    .as_array()
+                    .iter()
+                    .zip(&export.selected)
+                    .any(|(entry, selected)| *selected && formats::google::is_exportable(entry));
+                content = content.push(column.spacing(5)).push(
+                    row()
+                        .push(button::destructive("Cancel").on_press(Message::FinishExport))
+                        .push(horizontal_space())
+                        .push(
+                            button::standard("URI List")
+                                .on_press_maybe(any_selected.then_some(Message::ExportUriList)),
+                        )
+                        .push(
+                            button::standard("Backup").on_press_maybe(
+                                any_selected
+                                    .then_some(Message::ExportEncrypted(EncryptedExport::Backup)),
+                            ),
+                        )
+                        .push(
+                            button::standard("Aegis Vault").on_press_maybe(
+                                any_selected
+                                    .then_some(Message::ExportEncrypted(EncryptedExport::Aegis)),
+                            ),
+                        )
+                        .push(
+                            button::suggested("Google Authenticator")
+                                .on_press_maybe(google.then_some(Message::ExportGenerate)),
+                        )
+                        .spacing(5),
                 );
+            }
         } else if let Some(editing) = &self.editing_entry {
-            let delete = if let Some(e) = editing.entry
-                && let Some(entry) = self.secret.as_array().get(e)
+            let savable = editing
+                .draft
+                .as_ref()
+                .map_or(Ok(()), entry::Entry::check_savable);
+            let actions: Option<cosmic::Element<_>> = if let Some(e) = editing.entry
+                && let Some(entry) = &editing.draft
             {
-                content =
-                    content.push(entry.view_settings(false).map(move |m| {
-                        Message::Entry(entry::EntryR::Index(e.try_into().unwrap()), m)
-                    }));
+                content = content.push(
+                    entry
+                        .view_settings(false, self.display())
+                        .map(|m| Message::Entry(entry::EntryR::Draft, m)),
+                );
 
-                Some(button::destructive("Delete").on_press(Message::DeleteEntry(e)))
+                Some(
+                    row()
+                        .push(button::destructive("Delete").on_press(Message::DeleteEntry(e)))
+                        .push(button::standard("Duplicate").on_press(Message::DuplicateEntry(e)))
+                        .push(
+                            button::standard(if entry.archived { "Restore" } else { "Archive" })
+                                .on_press(Message::Entry(
+                                    entry::EntryR::Draft,
+                                    entry::EntryMessage::Archive(!entry.archived),
+                                )),
+                        )
+                        .push(button::standard("Cancel").on_press(Message::CancelEdit))
+                        .push(
+                            button::icon(icon::from_name("edit-undo-symbolic")).on_press_maybe(
+                                (!editing.undo.is_empty()).then_some(Message::EditUndo),
+                            ),
+                        )
+                        .push(
+                            button::icon(icon::from_name("edit-redo-symbolic")).on_press_maybe(
+                                (!editing.redo.is_empty()).then_some(Message::EditRedo),
+                            ),
+                        )
+                        .spacing(5)
+                        .into(),
+                )
             } else {
                 let mut column = cosmic::widget::column();
                 for (idx, entry) in self.secret.as_array().iter().enumerate() {
-                    let child = entry.view::<false>().map(move |m| {
-                        Message::Entry(entry::EntryR::Index(idx.try_into().unwrap()), m)
-                    });
+                    if entry.archived {
+                        continue;
+                    }
+                    let child =
+                        entry
+                            .view::<false>(entry::DisplayOptions::default())
+                            .map(move |m| {
+                                Message::Entry(entry::EntryR::Index(idx.try_into().unwrap()), m)
+                            });
                     column = column.push(
                         row()
+                            .push(
+                                cosmic::widget::checkbox(
+                                    "",
+                                    editing.selected.get(idx).copied().unwrap_or_default(),
+                                )
+                                .on_toggle(move |b| Message::EditSelect(idx, b)),
+                            )
                             .push(child)
                             .push(horizontal_space())
+                            .push(cosmic::widget::text::caption(entry.usage_summary()))
                             .push(button::icon(icon::from_name("go-up-symbolic")).on_press(
                                 Message::MoveEntry {
                                     entry: idx,
@@ -173,44 +1062,277 @@ impl cosmic::Application for App {
                 }
                 content = content.push(column.spacing(5));
 
-                Some(button::standard("Migrate").on_press(Message::StartMigration))
+                let mut archived = self
+                    .secret
+                    .as_array()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, entry)| entry.archived)
+                    .peekable();
+                if archived.peek().is_some() {
+                    let mut column =
+                        cosmic::widget::column().push(cosmic::widget::text::heading("Archived"));
+                    for (idx, entry) in archived {
+                        let r = entry::EntryR::Index(idx.try_into().unwrap());
+                        column = column.push(
+                            row()
+                                .push(
+                                    entry
+                                        .view::<false>(entry::DisplayOptions::default())
+                                        .map(move |m| Message::Entry(r, m)),
+                                )
+                                .push(horizontal_space())
+                                .push(button::standard("Restore").on_press(Message::Entry(
+                                    r,
+                                    entry::EntryMessage::Archive(false),
+                                )))
+                                .push(
+                                    button::icon(icon::from_name("edit-symbolic"))
+                                        .class(cosmic::theme::Button::Standard)
+                                        .on_press(Message::EditEntry(idx)),
+                                )
+                                .spacing(5)
+                                .align_y(cosmic::iced::Alignment::Center),
+                        );
+                    }
+                    content = content.push(column.spacing(5));
+                }
+
+                let count = editing.selection().count();
+                if count > 0 {
+                    content = content.push(
+                        cosmic::widget::column()
+                            .push(cosmic::widget::text::heading(format!("{count} selected")))
+                            .push(
+                                row()
+                                    .push(
+                                        text_input("Group", &editing.group)
+                                            .on_input(Message::BulkGroupInput)
+                                            .on_submit(|_| Message::BulkMoveToGroup),
+                                    )
+                                    .push(
+                                        button::standard("Move to Group")
+                                            .on_press(Message::BulkMoveToGroup),
+                                    )
+                                    .spacing(5),
+                            )
+                            .push(
+                                row()
+                                    .push(
+                                        button::destructive("Delete").on_press(Message::BulkDelete),
+                                    )
+                                    .push(
+                                        button::standard("Archive").on_press(Message::BulkArchive),
+                                    )
+                                    .push(button::standard("Export").on_press(Message::BulkExport))
+                                    .spacing(5),
+                            )
+                            .spacing(5),
+                    );
+                }
+
+                content = content.push(
+                    row()
+                        .push(cosmic::widget::text::heading("Tools"))
+                        .push(horizontal_space())
+                        .push(button::standard("Verify Code").on_press(Message::StartVerify))
+                        .push(button::standard("Quick Generate").on_press(Message::QuickGenerate))
+                        .push(button::standard("New Secret").on_press(Message::StartGenerator))
+                        .spacing(5)
+                        .align_y(cosmic::iced::Alignment::Center),
+                );
+
+                let actions = row()
+                    .push(button::standard("Import").on_press(Message::Import))
+                    .push(button::standard("Export").on_press(Message::StartExport))
+                    .push(button::standard("Migrate").on_press(Message::StartMigration));
+                #[cfg(feature = "keepass")]
+                let actions =
+                    actions.push(button::standard("KeePassXC").on_press(Message::KeePassPick));
+
+                Some(actions.spacing(5).into())
             };
-            content = content.push(row().push_maybe(delete).push(horizontal_space()).push(
-                button::suggested("Close").on_press(Message::FinishEdit {
-                    only_current: editing.entry.is_some(),
-                }),
-            ));
+            content = content.push(
+                row()
+                    .push_maybe(actions)
+                    .push(horizontal_space())
+                    .push_maybe(
+                        editing
+                            .draft
+                            .as_ref()
+                            .and_then(|draft| savable_caption(draft, &savable)),
+                    )
+                    .push(
+                        button::suggested("Close").on_press_maybe(savable.is_ok().then_some(
+                            Message::FinishEdit {
+                                only_current: editing.entry.is_some(),
+                            },
+                        )),
+                    )
+                    .spacing(5)
+                    .align_y(cosmic::iced::Alignment::Center),
+            );
+        } else if self.show_settings {
+            content = content.push(self.view_settings());
         } else {
             let logout = button::icon(icon::from_name("system-log-out-symbolic"))
                 .class(cosmic::theme::Button::Destructive)
                 .on_press(Message::Logout);
+            let lock = self.config.lock_hash.is_some().then(|| {
+                button::icon(icon::from_name("system-lock-screen-symbolic"))
+                    .class(cosmic::theme::Button::Standard)
+                    .on_press(Message::LockNow)
+            });
             let edit_entries = button::icon(icon::from_name("edit-symbolic"))
                 .class(cosmic::theme::Button::Standard)
                 .on_press(Message::EditEntries);
+            let settings = button::icon(icon::from_name("emblem-system-symbolic"))
+                .class(cosmic::theme::Button::Standard)
+                .on_press(Message::ToggleSettings);
             let new_entry = button::icon(icon::from_name("list-add-symbolic"))
                 .class(cosmic::theme::Button::Suggested)
                 .on_press(Message::NewEntry);
+            let screen_share = self.config.display.screen_share;
+            let privacy = cosmic::widget::tooltip(
+                button::icon(icon::from_name(if screen_share {
+                    "view-conceal-symbolic"
+                } else {
+                    "view-reveal-symbolic"
+                }))
+                .class(if screen_share {
+                    cosmic::theme::Button::Suggested
+                } else {
+                    cosmic::theme::Button::Standard
+                })
+                .on_press(Message::SetScreenShare(!screen_share)),
+                cosmic::widget::text("Hide codes and names for screen sharing"),
+                cosmic::widget::tooltip::Position::Bottom,
+            );
+            let recent = self.config.sort_mode == SortMode::RecentlyUsed;
+            let sort = button::icon(icon::from_name("view-sort-descending-symbolic"))
+                .class(if recent {
+                    cosmic::theme::Button::Suggested
+                } else {
+                    cosmic::theme::Button::Standard
+                })
+                .on_press(Message::SetSortMode(if recent {
+                    SortMode::Manual
+                } else {
+                    SortMode::RecentlyUsed
+                }));
             let system_bar = container(
                 row()
                     .push(logout)
-                    .push(self.user.as_deref().unwrap())
+                    .push_maybe(lock)
+                    .push(self.view_user_switcher())
                     .push_maybe((!self.secret.as_array().is_empty()).then_some(horizontal_space()))
+                    .push(privacy)
+                    .push(sort)
+                    .push(settings)
                     .push(edit_entries)
                     .push(new_entry)
                     .spacing(5)
                     .align_y(cosmic::iced::Alignment::Center),
             );
-            content = content.push(system_bar);
-            let mut column = cosmic::widget::column();
-            for (idx, entry) in self.secret.as_array().iter().enumerate() {
-                column = column.push(entry.view::<true>().map(move |m| {
-                    Message::Entry(entry::EntryR::Index(idx.try_into().unwrap()), m)
-                }));
+            content = content
+                .push(system_bar)
+                .push_maybe(self.clock_warning().map(warning));
+            if self.secret.as_array().is_empty() {
+                content = content.push(view_empty());
+            } else {
+                let search = cosmic::widget::search_input("Search", &self.search)
+                    .id(SEARCH_ID.clone())
+                    .on_input(Message::SearchInput)
+                    .on_submit(|_| Message::SearchSubmit)
+                    .on_clear(Message::SearchInput(String::new()));
+                content = content.push(search);
+                let entries = self.secret.as_array();
+                let display = self.display();
+                let rows = self.list_rows();
+                let total: f32 = rows.iter().map(|row| row.height(entries, display)).sum();
+                let (top, height) = self.list_viewport.map_or((0.0, LIST_MAX_HEIGHT), |v| {
+                    (v.absolute_offset().y, v.bounds().height)
+                });
+                // The last scroll position may be past the end of a list that has since shrunk
+                let top = top.min((total - height).max(0.0));
+                let mut column = cosmic::widget::column();
+                let mut y = 0.0;
+                // Rows out of view are replaced by empty space of the same height
+                let mut skipped = 0.0;
+                for row in rows {
+                    let row_height = row.height(entries, display);
+                    let visible =
+                        y + row_height >= top - LIST_OVERSCAN && y <= top + height + LIST_OVERSCAN;
+                    y += row_height;
+                    if !visible {
+                        skipped += row_height;
+                        continue;
+                    }
+                    if skipped > 0.0 {
+                        column = column.push(cosmic::iced::widget::Space::with_height(skipped));
+                        skipped = 0.0;
+                    }
+                    let element = match row {
+                        ListRow::Heading(heading) => cosmic::widget::text::heading(heading).into(),
+                        ListRow::Entry { pos, idx } => {
+                            let view = entries[idx].view::<true>(display).map(move |m| {
+                                Message::Entry(entry::EntryR::Index(idx.try_into().unwrap()), m)
+                            });
+                            if self.selected == Some(pos) {
+                                container(view)
+                                    .class(cosmic::theme::Container::Primary)
+                                    .into()
+                            } else {
+                                view
+                            }
+                        }
+                    };
+                    column = column.push(
+                        container(element)
+                            .height(row_height)
+                            .align_y(cosmic::iced::Alignment::Center),
+                    );
+                }
+                if skipped > 0.0 {
+                    column = column.push(cosmic::iced::widget::Space::with_height(skipped));
+                }
+                content = content.push(
+                    cosmic::widget::scrollable(column)
+                        .id(LIST_ID.clone())
+                        .on_scroll(Message::ListScrolled)
+                        .height(total.min(LIST_MAX_HEIGHT)),
+                );
+            }
+            #[cfg(feature = "keepass")]
+            {
+                content = content.push(self.view_keepass());
             }
-            content = content.push(column.spacing(5));
             content = content.width(Length::Shrink);
         }
 
+        content = content
+            .push_maybe(self.storage_failure.as_ref().map(view_storage_failure))
+            .push_maybe(self.status.as_deref().map(cosmic::widget::text))
+            .push_maybe(self.recently_deleted.as_ref().map(|deleted| {
+                let label = match deleted.entries.as_slice() {
+                    [(_, entry)] => format!("{} deleted", entry.totp.account_name),
+                    entries => format!("{} entries deleted", entries.len()),
+                };
+                row()
+                    .push(cosmic::widget::text(label))
+                    .push(horizontal_space())
+                    .push(
+                        button::text("Undo")
+                            .on_press_maybe(self.editing_entry.is_none().then_some(Message::Undo)),
+                    )
+                    .align_y(cosmic::iced::Alignment::Center)
+            }))
+            .push_maybe(
+                self.entry_error
+                    .as_deref()
+                    .map(|s| warning(s).on_close(Message::EntryClearError)),
+            );
+
         let dialog = self.pending_delete.and_then(|idx| {
             let entry = self.secret.as_array().get(idx)?;
 
@@ -234,6 +1356,116 @@ impl cosmic::Application for App {
                 );
             Some(element)
         });
+        let dialog = dialog.or_else(|| {
+            self.editing_entry.as_ref().filter(|e| e.confirm_discard)?;
+
+            let element = cosmic::widget::dialog()
+                .title("Discard Changes?")
+                .body("The changes made to this entry will be lost.")
+                .primary_action(button::suggested("Keep Editing").on_press(Message::KeepEditing))
+                .secondary_action(button::destructive("Discard").on_press(Message::DiscardEdit));
+            Some(element)
+        });
+        let dialog = dialog.or_else(|| {
+            let editing = self.editing_entry.as_ref().filter(|e| e.confirm_delete)?;
+
+            let element = cosmic::widget::dialog()
+                .title("Delete Entries")
+                .body(format!(
+                    "Are you sure you want to delete {} entries",
+                    editing.selection().count()
+                ))
+                .primary_action(button::suggested("Cancel").on_press(Message::BulkDeleteCancel))
+                .secondary_action(
+                    button::destructive("Delete").on_press(Message::BulkDeleteConfirm),
+                );
+            Some(element)
+        });
+        let dialog = dialog.or_else(|| {
+            let existing = self.secret.as_array().get(self.pending_duplicate?)?;
+
+            let element = cosmic::widget::dialog()
+                .title("Entry Already Exists")
+                .body(format!(
+                    "{}{} is already in the vault with the same secret. Merging keeps the \
+                     existing entry and fills in its missing icon, group and recovery codes.",
+                    existing.totp.account_name,
+                    existing
+                        .totp
+                        .issuer
+                        .as_ref()
+                        .map_or_else(String::new, |issuer| format!(" ({issuer})"))
+                ))
+                .primary_action(
+                    button::suggested("Merge")
+                        .on_press(Message::ResolveDuplicate(DuplicateAction::Merge)),
+                )
+                .secondary_action(
+                    button::destructive("Replace")
+                        .on_press(Message::ResolveDuplicate(DuplicateAction::Overwrite)),
+                )
+                .tertiary_action(
+                    button::text("Keep Existing")
+                        .on_press(Message::ResolveDuplicate(DuplicateAction::Skip)),
+                );
+            Some(element)
+        });
+        let dialog = dialog.or_else(|| {
+            self.exporting.as_ref().filter(|m| m.confirm_uri_list)?;
+
+            let element = cosmic::widget::dialog()
+                .title("Unencrypted Export")
+                .body(
+                    "The exported file will contain every selected secret in plain text. Anyone \
+                     who can read it can generate your codes, so delete it once you're done.",
+                )
+                .primary_action(button::suggested("Cancel").on_press(Message::ExportUriListCancel))
+                .secondary_action(
+                    button::destructive("Export").on_press(Message::ExportUriListConfirm),
+                );
+            Some(element)
+        });
+        #[cfg(feature = "keepass")]
+        let dialog = dialog.or_else(|| {
+            let password = self.keepass_password.as_ref()?;
+
+            let element = cosmic::widget::dialog()
+                .title("Unlock KeePassXC Database")
+                .body(
+                    self.keepass_path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                )
+                .control(
+                    text_input("Password", password)
+                        .password()
+                        .on_input(Message::KeePassPassword)
+                        .on_submit(|_| Message::KeePassUnlock),
+                )
+                .primary_action(button::suggested("Unlock").on_press(Message::KeePassUnlock))
+                .secondary_action(button::standard("Cancel").on_press(Message::KeePassCancel));
+            Some(element)
+        });
+        let dialog = dialog.or_else(|| {
+            let pending = self.pending_import.as_ref()?;
+
+            let element = cosmic::widget::dialog()
+                .title("Encrypted Import")
+                .body(pending.error.as_ref().map_or_else(
+                    || "This export is encrypted, enter its password to import it.".into(),
+                    |e| format!("{e}, check the password and try again."),
+                ))
+                .control(
+                    text_input("Password", &pending.password)
+                        .password()
+                        .on_input(Message::ImportPassword)
+                        .on_submit(|_| Message::ImportUnlock),
+                )
+                .primary_action(button::suggested("Import").on_press(Message::ImportUnlock))
+                .secondary_action(button::standard("Cancel").on_press(Message::ImportCancel));
+            Some(element)
+        });
 
         let mut popover = cosmic::widget::popover(content).modal(true);
         if let Some(dialog) = dialog {
@@ -254,23 +1486,110 @@ impl cosmic::Application for App {
     }
 
     fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
-        self.popup.map_or_else(Subscription::none, |p| {
-            Subscription::batch(
-                self.secret
-                    .as_array()
+        let shortcuts = shortcuts::subscription(
+            self.config.popup_shortcut.clone(),
+            self.config.copy_shortcut.clone(),
+        )
+        .map(Message::GlobalShortcut);
+        let popup = self.popup.map_or_else(Subscription::none, |_| {
+            // Codes are only generated for the entries that can be seen
+            let showing_list = self.showing_list();
+            let visible = if showing_list {
+                self.display_order()
+            } else {
+                Vec::new()
+            };
+            let vault = self.secret.as_array();
+            let entries = visible
+                .into_iter()
+                .map(|idx| (entry::EntryR::Index(idx.try_into().unwrap()), &vault[idx]));
+            #[cfg(feature = "keepass")]
+            let keepass: &[entry::Entry] = if showing_list { &self.keepass } else { &[] };
+            #[cfg(feature = "keepass")]
+            let entries = entries.chain(
+                keepass
                     .iter()
                     .enumerate()
-                    .map(|(idx, entry)| {
-                        entry
-                            .subscription(p)
-                            .with(entry::EntryR::Index(idx.try_into().unwrap()))
-                            .map(move |(r, m)| Message::Entry(r, m))
-                    }),
-            )
-        })
-    }
-
-    #[allow(
+                    .map(|(idx, entry)| (entry::EntryR::KeePass(idx.try_into().unwrap()), entry)),
+            );
+            // Entries being written also step, for their preview
+            let editing = self
+                .new_entry
+                .iter()
+                .map(|entry| (entry::EntryR::NewEntry, entry))
+                .chain(
+                    self.scratch
+                        .iter()
+                        .map(|entry| (entry::EntryR::Scratch, entry)),
+                )
+                .chain(
+                    self.editing_entry
+                        .iter()
+                        .filter_map(|editing| editing.draft.as_ref())
+                        .map(|draft| (entry::EntryR::Draft, draft)),
+                )
+                .filter(|(_, entry)| entry.problems().is_empty());
+            let entries = entries.chain(editing);
+            let keys = cosmic::iced::keyboard::on_key_press(if showing_list {
+                list_key_press
+            } else {
+                popup_key_press
+            });
+            let editing_draft = self
+                .editing_entry
+                .as_ref()
+                .is_some_and(|editing| editing.draft.is_some());
+            let editor_keys = if editing_draft {
+                cosmic::iced::event::listen_with(editor_key_press)
+            } else {
+                Subscription::none()
+            };
+            // Only the codes being shown need to follow the clock
+            let clock_jumps = clock::jumps().map(|()| Message::ClockJumped);
+            let auto_lock = self.config.lock_hash.is_some() && self.config.auto_lock_seconds > 0;
+            let activity = if self.config.display.idle_hide_seconds > 0 || auto_lock {
+                cosmic::iced::event::listen_with(popup_activity)
+            } else {
+                Subscription::none()
+            };
+            Subscription::batch(
+                entries
+                    .map(|(r, entry)| {
+                        // Restarting the timers gives every code a fresh start
+                        entry
+                            .subscription()
+                            .with((r, self.timers))
+                            .map(move |((r, _), m)| Message::Entry(r, m))
+                    })
+                    .chain([keys, activity, editor_keys, clock_jumps]),
+            )
+        });
+        // Settings can also be changed by hand or by other instances
+        let config = self
+            .core
+            .watch_config::<config::Config>(crate::APP_ID)
+            .map(|update| {
+                for e in update.errors {
+                    warn!("Couldn't read changed setting: {e}");
+                }
+                Message::ConfigChanged(update.config)
+            });
+
+        let session_lock = session::subscription().map(|()| Message::SessionLocked);
+        let resumes = session::resumes().map(|()| Message::Resumed);
+        let server = self.config.ntp_server.trim();
+        let clock = if server.is_empty() {
+            Subscription::none()
+        } else {
+            // The clock may have drifted while asleep, so it's checked again on waking
+            clock::subscription(server.to_string())
+                .with(self.timers)
+                .map(|(_, offset)| Message::ClockChecked(offset))
+        };
+        Subscription::batch([shortcuts, popup, config, session_lock, resumes, clock])
+    }
+
+    #[allow(
         clippy::cognitive_complexity,
         clippy::too_many_lines,
         reason = "as the most important function in the application, it is expected to be complex"
@@ -278,52 +1597,553 @@ impl cosmic::Application for App {
     fn update(&mut self, message: Self::Message) -> Task<Self::Message> {
         match message {
             Message::TogglePopup => return self.toggle_popup(),
-            Message::RetrievedKey(state) => match state {
-                Ok(state) => {
-                    self.secret = state;
+            Message::GlobalShortcut(Ok(id)) => match id.as_str() {
+                shortcuts::COPY_CODE => {
+                    // The vault is only unlocked once the popup has been opened
+                    if self.lock.is_some() {
+                        // The code is copied once the popup has been unlocked
+                        self.copy_on_unlock = true;
+                        return if self.popup.is_none() {
+                            self.toggle_popup()
+                        } else {
+                            Task::none()
+                        };
+                    }
+                    match self.secret {
+                        secrets::State::PendingUser => {
+                            self.copy_on_unlock = true;
+                            return self.get_secret_key();
+                        }
+                        secrets::State::Encrypted
+                        | secrets::State::KeyringLocked
+                        | secrets::State::Corrupt(_) => {
+                            self.copy_on_unlock = true;
+                            return if self.popup.is_none() {
+                                self.toggle_popup()
+                            } else {
+                                Task::none()
+                            };
+                        }
+                        secrets::State::Secrets(_) => {}
+                    }
+                    return self.quick_copy();
                 }
-                Err(e) => {
-                    error!("Failed to retrieve secret key: {e}");
+                shortcuts::OPEN_POPUP => {
+                    let open = if self.popup.is_none() {
+                        self.toggle_popup()
+                    } else {
+                        Task::none()
+                    };
+                    return open.chain(self.update(Message::FocusSearch));
                 }
+                _ => warn!("Unknown global shortcut '{id}'"),
             },
-            Message::SetKey(r) => {
-                if let Err(e) = r {
+            Message::GlobalShortcut(Err(e)) => {
+                error!("Global shortcuts unavailable: {e}");
+            }
+            Message::RetrievedKey(loaded) => {
+                let copy = std::mem::take(&mut self.copy_on_unlock);
+                let loaded = match loaded {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        error!("Failed to retrieve secret key: {e}");
+                        // Copy once a retry has read the vault
+                        self.copy_on_unlock = copy;
+                        return self.storage_failed(StorageOp::Load, e);
+                    }
+                };
+                self.storage_succeeded(StorageOp::Load);
+                self.remember_user();
+                self.secret = loaded.state;
+                self.vault_key = loaded.key;
+                let thumbnails = self.load_thumbnails();
+                if matches!(
+                    self.secret,
+                    secrets::State::Encrypted
+                        | secrets::State::KeyringLocked
+                        | secrets::State::Corrupt(_)
+                ) {
+                    // The code is copied once the vault can be read
+                    self.copy_on_unlock = copy;
+                    if copy && self.popup.is_none() {
+                        return self.toggle_popup();
+                    }
+                } else if self.needs_vault_key() {
+                    // A new vault file can't be saved until it has a passphrase
+                    self.vault_setup = Some(lock::Setup::new(lock::LockKind::Passphrase));
+                } else if copy {
+                    return Task::batch([thumbnails, self.quick_copy()]);
+                }
+                return thumbnails;
+            }
+            Message::SetKey(r) => match r {
+                Ok(()) => self.storage_succeeded(StorageOp::Save),
+                Err(e) => {
                     error!("Failed to set secret key: {e}");
+                    // Still unsaved, so closing the popup tries again
+                    self.dirty = true;
+                    return self.storage_failed(StorageOp::Save, e);
                 }
+            },
+            Message::KeepRecovered => {
+                if let secrets::State::Corrupt(recovery) = &mut self.secret {
+                    let entries = std::mem::take(&mut recovery.entries);
+                    info!("Keeping {} recovered entries", entries.len());
+                    self.secret = secrets::State::Secrets(entries);
+                    let thumbnails = self.load_thumbnails();
+                    if self.needs_vault_key() {
+                        self.vault_setup = Some(lock::Setup::new(lock::LockKind::Passphrase));
+                        return thumbnails;
+                    }
+                    let save = self.set_secret_key();
+                    if std::mem::take(&mut self.copy_on_unlock) {
+                        return Task::batch([thumbnails, save, self.quick_copy()]);
+                    }
+                    return Task::batch([thumbnails, save]);
+                }
+            }
+            Message::StorageRetry(op) => {
+                // Retrying by hand after giving up starts the backoff over
+                if let Some(failure) = &mut self.storage_failure
+                    && !failure.retrying
+                {
+                    failure.attempts = 0;
+                }
+                return match op {
+                    StorageOp::Load if matches!(self.secret, secrets::State::PendingUser) => {
+                        self.get_secret_key()
+                    }
+                    StorageOp::Load => Task::none(),
+                    StorageOp::Save => self.set_secret_key(),
+                };
+            }
+            Message::UseFileStorage => {
+                // Only ever switched by the user, as the vault in the keyring isn't moved over
+                if let Err(e) = self
+                    .config
+                    .set_storage(&self.config_handler, secrets::Storage::File)
+                {
+                    error!("Couldn't save storage setting: {e}");
+                }
+                self.storage_failure = None;
+                return self.get_secret_key();
             }
             Message::UsernameInput(s) => self.user = Some(s),
+            Message::SetStorage(storage) => {
+                if let Err(e) = self.config.set_storage(&self.config_handler, storage) {
+                    error!("Couldn't save storage setting: {e}");
+                }
+                return self.find_users();
+            }
+            Message::SetKeyringService(service) => {
+                if let Err(e) = self
+                    .config
+                    .set_keyring_service(&self.config_handler, service)
+                {
+                    error!("Couldn't save keyring service setting: {e}");
+                }
+                return self.find_users();
+            }
+            Message::SetKeyringCollection(collection) => {
+                if let Err(e) = self
+                    .config
+                    .set_keyring_collection(&self.config_handler, collection)
+                {
+                    error!("Couldn't save keyring collection setting: {e}");
+                }
+                return self.find_users();
+            }
+            Message::FoundUsers(Ok(users)) => {
+                self.found_users = users;
+                self.login_error = None;
+            }
+            Message::FoundUsers(Err(e)) => {
+                warn!("Couldn't look for existing vaults: {e}");
+                self.found_users.clear();
+                self.login_error = Some(format!("Couldn't access the store: {e}"));
+            }
             Message::UsernameSubmit(s) => {
+                // Enter submits the field even while it's blank
+                if s.trim().is_empty() {
+                    return Task::none();
+                }
                 self.user = Some(s);
                 let task = self.update(Message::Save);
                 return Task::batch([task, self.get_secret_key()]);
             }
             Message::Logout => {
-                self.secret = secrets::State::PendingUser;
+                let flush = self.close_vault();
+                self.storage_failure = None;
                 self.user = None;
-                return self.update(Message::Save);
+                return Task::batch([flush, self.update(Message::Save), self.find_users()]);
+            }
+            Message::SwitchUser(idx) => {
+                let Some(user) = self.config.known_users.get(idx).cloned() else {
+                    return Task::none();
+                };
+                if self.user.as_ref() == Some(&user) {
+                    return Task::none();
+                }
+                info!("Switching to user '{user}'");
+                let logout = self.update(Message::Logout);
+                return logout.chain(self.update(Message::UsernameSubmit(user)));
+            }
+            Message::UnlockInput(input) => {
+                if let Some(prompt) = &mut self.lock {
+                    prompt.input = self.config.lock_kind.filter(input);
+                }
+            }
+            Message::UnlockDigit(digit) => {
+                if let Some(prompt) = &mut self.lock {
+                    prompt.input.push(digit);
+                }
+            }
+            Message::UnlockBackspace => {
+                if let Some(prompt) = &mut self.lock {
+                    prompt.input.pop();
+                }
+            }
+            Message::UnlockSubmit => {
+                if let Some(prompt) = &mut self.lock
+                    && prompt.can_submit()
+                {
+                    prompt.checking = true;
+                    prompt.error = None;
+                    let passphrase = std::mem::take(&mut prompt.input);
+                    return Task::perform(lock::verify(passphrase, prompt.hash.clone()), |r| {
+                        cosmic::Action::App(Message::Unlocked(r))
+                    });
+                }
+            }
+            Message::Unlocked(true) => {
+                info!("Unlocked");
+                self.lock = None;
+                if self.config.lock_failures > 0
+                    && let Err(e) = self.config.set_lock_attempts(&self.config_handler, 0, 0)
+                {
+                    error!("Couldn't save unlock attempts: {e}");
+                }
+                self.last_activity = std::time::Instant::now();
+                return Task::batch([self.get_secret_key(), self.schedule_auto_lock()]);
+            }
+            Message::Unlocked(false) => {
+                if let Some(prompt) = &mut self.lock {
+                    let kind = self.config.lock_kind;
+                    let wait = prompt.fail(kind);
+                    warn!("Wrong {}, {} in a row", kind.name(), prompt.failures);
+                    // Saved so restarting the app doesn't allow more attempts sooner
+                    let retry_at = if wait.is_zero() {
+                        0
+                    } else {
+                        lock::unix_time() + wait.as_secs()
+                    };
+                    if let Err(e) = self.config.set_lock_attempts(
+                        &self.config_handler,
+                        prompt.failures,
+                        retry_at,
+                    ) {
+                        error!("Couldn't save unlock attempts: {e}");
+                    }
+                    if !wait.is_zero() {
+                        return Task::perform(tokio::time::sleep(wait), |()| {
+                            cosmic::Action::App(Message::UnlockRetry)
+                        });
+                    }
+                }
+            }
+            Message::UnlockRetry => {
+                if let Some(prompt) = &mut self.lock {
+                    prompt.retry_at = None;
+                    prompt.error = None;
+                }
+            }
+            Message::LockNow => return self.lock(),
+            Message::Resumed => {
+                info!("Woke from sleep, restarting timers");
+                self.timers += 1;
+            }
+            Message::ClockJumped => {
+                info!("The clock changed, restarting timers");
+                self.timers += 1;
+            }
+            Message::SessionLocked => {
+                info!("Session locked");
+                let close = self.update(Message::ClosePopup);
+                // Without an app lock the vault is read from the keyring again once it's opened
+                let drop = if self.config.lock_hash.is_some() {
+                    self.lock()
+                } else {
+                    self.close_vault()
+                };
+                return Task::batch([close, drop]);
+            }
+            Message::AutoLockCheck => {
+                let timeout = self.config.auto_lock_seconds;
+                if self.lock.is_some() || self.config.lock_hash.is_none() || timeout == 0 {
+                    return Task::none();
+                }
+                if self.last_activity.elapsed().as_secs() < timeout {
+                    return self.schedule_auto_lock();
+                }
+                info!("Locking after {timeout} seconds without input");
+                return self.lock();
+            }
+            Message::SetAutoLockSeconds(seconds) => {
+                if let Err(e) = self
+                    .config
+                    .set_auto_lock_seconds(&self.config_handler, seconds)
+                {
+                    error!("Couldn't save auto lock setting: {e}");
+                }
+                return self.schedule_auto_lock();
+            }
+            // Switching kinds starts over, a passphrase rarely makes a valid PIN
+            Message::LockSetup(kind) => self.lock_setup = Some(lock::Setup::new(kind)),
+            Message::LockSetupInput(input) => {
+                if let Some(setup) = &mut self.lock_setup {
+                    setup.secret = setup.kind.filter(input);
+                }
+            }
+            Message::LockSetupConfirm(input) => {
+                if let Some(setup) = &mut self.lock_setup {
+                    setup.confirm = setup.kind.filter(input);
+                }
+            }
+            Message::LockSetupSave => {
+                if let Some(setup) = &self.lock_setup
+                    && setup.validate().is_ok()
+                {
+                    let kind = setup.kind;
+                    let secret = setup.secret.clone();
+                    self.lock_setup = None;
+                    return Task::perform(lock::hash(secret), move |r| {
+                        cosmic::Action::App(Message::LockHashed(kind, r))
+                    });
+                }
+            }
+            Message::LockSetupCancel => self.lock_setup = None,
+            Message::LockHashed(kind, Ok(hash)) => {
+                info!("Setting app lock {}", kind.name());
+                if let Err(e) = self.config.set_lock(&self.config_handler, kind, hash) {
+                    error!("Couldn't save app lock: {e}");
+                }
+                return self.schedule_auto_lock();
+            }
+            Message::LockHashed(_, Err(e)) => {
+                error!("{e}");
+                self.status = Some(e);
+            }
+            Message::VaultPassphraseInput(input) => self.vault_passphrase = input,
+            Message::VaultUnlock => {
+                if let Some(user) = self.user.clone()
+                    && !self.vault_passphrase.is_empty()
+                {
+                    self.vault_error = None;
+                    let passphrase = std::mem::take(&mut self.vault_passphrase);
+                    let location = self.location();
+                    return Task::perform(secrets::unlock(user, location, passphrase), |r| {
+                        cosmic::Action::App(Message::VaultUnlocked(r))
+                    });
+                }
+            }
+            Message::VaultUnlocked(Ok((state, key))) => {
+                self.secret = state;
+                self.vault_key = Some(key);
+                let thumbnails = self.load_thumbnails();
+                if std::mem::take(&mut self.copy_on_unlock) {
+                    return Task::batch([thumbnails, self.quick_copy()]);
+                }
+                return thumbnails;
+            }
+            Message::VaultUnlocked(Err(e)) => {
+                warn!("Couldn't unlock vault: {e}");
+                self.vault_error = Some(e);
+            }
+            Message::KeyringUnlock => {
+                if let Some(user) = self.user.clone()
+                    && !self.unlocking_keyring
+                {
+                    self.vault_error = None;
+                    self.unlocking_keyring = true;
+                    return Task::perform(secrets::unlock_keyring(user, self.location()), |r| {
+                        cosmic::Action::App(Message::KeyringUnlocked(r))
+                    });
+                }
+            }
+            Message::KeyringUnlocked(r) => {
+                self.unlocking_keyring = false;
+                match r {
+                    Ok(()) => return self.get_secret_key(),
+                    Err(e) => {
+                        warn!("Couldn't unlock keyring: {e}");
+                        self.vault_error = Some(e);
+                    }
+                }
+            }
+            Message::VaultSetup => {
+                self.vault_setup = Some(lock::Setup::new(lock::LockKind::Passphrase));
+            }
+            Message::VaultSetupInput(input) => {
+                if let Some(setup) = &mut self.vault_setup {
+                    setup.secret = input;
+                }
+            }
+            Message::VaultSetupConfirm(input) => {
+                if let Some(setup) = &mut self.vault_setup {
+                    setup.confirm = input;
+                }
+            }
+            Message::VaultSetupSave => {
+                if let Some(setup) = &self.vault_setup
+                    && setup.validate().is_ok()
+                {
+                    let passphrase = setup.secret.clone();
+                    self.vault_setup = None;
+                    return Task::perform(secrets::VaultKey::new(passphrase), |r| {
+                        cosmic::Action::App(Message::VaultKeyDerived(r))
+                    });
+                }
+            }
+            Message::VaultSetupCancel => self.vault_setup = None,
+            Message::VaultKeyDerived(Ok(key)) => {
+                if key.is_sealed() {
+                    info!("Binding vault to the TPM");
+                } else {
+                    info!("Encrypting vault with a passphrase");
+                }
+                self.vault_key = Some(key);
+                return self.set_secret_key();
+            }
+            Message::VaultKeyDerived(Err(e)) => {
+                error!("{e}");
+                self.status = Some(e);
+            }
+            #[cfg(feature = "tpm")]
+            Message::VaultSeal => {
+                return Task::perform(secrets::VaultKey::sealed(), |r| {
+                    cosmic::Action::App(Message::VaultKeyDerived(r))
+                });
+            }
+            Message::VaultDecrypt => {
+                info!("Removing vault passphrase");
+                self.vault_key = None;
+                return self.set_secret_key();
+            }
+            Message::RemoveLock => {
+                info!("Removing app lock");
+                if let Err(e) = self.config.set_lock_hash(&self.config_handler, None) {
+                    error!("Couldn't remove app lock: {e}");
+                }
+                self.lock = None;
             }
             Message::Save => {
                 info!("Saving last used user '{:?}'", self.user);
-                if let Err(e) = self.config.set("last-user", self.user.clone()) {
+                if let Err(e) = self.config_handler.set("last-user", self.user.clone()) {
                     error!("Couldn't save last user: {e}");
                 }
-                return self.set_secret_key();
+                // Rapid edits are written to the keyring together once they settle
+                self.dirty = true;
+                self.save_id += 1;
+                let id = self.save_id;
+                return Task::perform(tokio::time::sleep(SAVE_DELAY), move |()| {
+                    cosmic::Action::App(Message::SaveDebounced(id))
+                });
+            }
+            Message::SaveDebounced(id) => {
+                if id == self.save_id && std::mem::take(&mut self.dirty) {
+                    return self.set_secret_key();
+                }
             }
             Message::NewEntry => {
+                self.status = None;
                 if self.new_entry.is_none() {
-                    self.new_entry = Some(entry::Entry::new());
+                    self.new_entry = Some(self.blank_entry());
+                }
+            }
+            Message::QuickGenerate => {
+                if self.scratch.is_none() {
+                    self.scratch = Some(self.blank_entry());
                 }
             }
+            Message::ScratchAdd => {
+                self.status = None;
+                self.new_entry = self.scratch.take();
+            }
+            Message::ScratchClose => self.scratch = None,
             Message::Entry(entry_r, message) => {
+                // Drafts and new entries only take over the shortcut once they're saved
+                if let entry::EntryMessage::QuickCopy(true) = message
+                    && let entry::EntryR::Index(idx) = entry_r
+                {
+                    self.keep_quick_copy(idx as usize);
+                }
+                let before = match entry_r {
+                    entry::EntryR::Draft => self
+                        .editing_entry
+                        .as_ref()
+                        .and_then(|editing| editing.draft.clone()),
+                    _ => None,
+                };
                 let entry = match entry_r {
                     entry::EntryR::NewEntry => self.new_entry.as_mut(),
+                    entry::EntryR::Scratch => self.scratch.as_mut(),
+                    entry::EntryR::Draft => self
+                        .editing_entry
+                        .as_mut()
+                        .and_then(|editing| editing.draft.as_mut()),
                     entry::EntryR::Index(idx) => self.secret.as_mut_array().get_mut(idx as usize),
+                    entry::EntryR::Import(idx) => self
+                        .import_preview
+                        .as_mut()
+                        .and_then(|p| p.candidates.get_mut(idx as usize))
+                        .map(|c| &mut c.entry),
+                    #[cfg(feature = "keepass")]
+                    entry::EntryR::KeePass(idx) => self.keepass.get_mut(idx as usize),
                 };
+                // HOTP counters, recovery codes and usage must be saved as soon as they're used
+                let persist = matches!(
+                    message,
+                    entry::EntryMessage::NextCode
+                        | entry::EntryMessage::RecoveryUse(_)
+                        | entry::EntryMessage::CopyOutput
+                        | entry::EntryMessage::CopyNextOutput
+                        | entry::EntryMessage::ToggleFavorite
+                        | entry::EntryMessage::Archive(_)
+                ) && matches!(entry_r, entry::EntryR::Index(_));
+                let close = self.config.close_on_copy
+                    && self.popup.is_some()
+                    && matches!(
+                        message,
+                        entry::EntryMessage::CopyOutput | entry::EntryMessage::CopyNextOutput
+                    );
+                let clear_after = (self.config.clear_clipboard_after > 0
+                    && matches!(
+                        message,
+                        entry::EntryMessage::CopyOutput
+                            | entry::EntryMessage::CopyNextOutput
+                            | entry::EntryMessage::CopyUri
+                            | entry::EntryMessage::RecoveryUse(_)
+                    ))
+                .then(|| std::time::Duration::from_secs(self.config.clear_clipboard_after));
                 if let Some(entry_mut) = entry {
                     match entry_mut.update(message) {
                         Ok(m) => {
                             self.entry_error = None;
-                            return m.map(move |m| cosmic::Action::App(Message::Entry(entry_r, m)));
+                            if let Some(before) = before {
+                                self.record_edit(before);
+                            }
+                            let mut task =
+                                m.map(move |m| cosmic::Action::App(Message::Entry(entry_r, m)));
+                            if let Some(delay) = clear_after {
+                                task = Task::batch([task, clipboard::clear_after(delay)]);
+                            }
+                            if close {
+                                task = task.chain(self.toggle_popup());
+                            }
+                            if persist {
+                                return Task::batch([task, self.update(Message::Save)]);
+                            }
+                            return task;
                         }
                         Err(e) => {
                             warn!("{e}");
@@ -335,12 +2155,33 @@ impl cosmic::Application for App {
                 }
             }
             Message::EntryClearError => self.entry_error = None,
-            Message::NewEntryCancel => self.new_entry = None,
+            Message::NewEntryCancel => {
+                self.cancel_scan();
+                self.new_entry = None;
+                self.status = None;
+            }
             Message::NewEntryAccept => {
+                if let Some(entry) = &self.new_entry
+                    && let Err(e) = entry.check_savable()
+                {
+                    self.entry_error = Some(e);
+                    return Task::none();
+                }
+                if let Some(entry) = &self.new_entry
+                    && let Some(idx) = self
+                        .secret
+                        .as_array()
+                        .iter()
+                        .position(|e| e.is_duplicate_of(entry))
+                {
+                    self.pending_duplicate = Some(idx);
+                    return Task::none();
+                }
                 if let Some(entry) = self.new_entry.take() {
-                    match self.secret.try_push(entry) {
-                        Ok(()) => {
-                            return self.update(Message::Save);
+                    match self.add_entry(entry, None, DuplicateAction::AddCopy) {
+                        Ok(added) => {
+                            let logo = added.map_or_else(Task::none, |idx| self.fetch_logo(idx));
+                            return Task::batch([self.update(Message::Save), logo]);
                         }
                         Err(e) => {
                             self.new_entry = Some(e);
@@ -349,8 +2190,345 @@ impl cosmic::Application for App {
                     }
                 }
             }
+            Message::ResolveDuplicate(action) => {
+                let Some(entry) = self.new_entry.take() else {
+                    return self.update(Message::Save);
+                };
+                match self.add_entry(entry, self.pending_duplicate.take(), action) {
+                    Ok(added) => {
+                        let logo = added.map_or_else(Task::none, |idx| self.fetch_logo(idx));
+                        return Task::batch([self.update(Message::Save), logo]);
+                    }
+                    Err(e) => {
+                        self.new_entry = Some(e);
+                        error!("Failed to insert entry, not loaded yet?");
+                    }
+                }
+            }
+            Message::ScanQrImage => {
+                self.scanning = true;
+                return Task::perform(qr::scan_file(), |r| {
+                    cosmic::Action::App(Message::ScannedQr(r))
+                });
+            }
+            Message::ScanQrScreen => {
+                self.scanning = true;
+                return Task::perform(qr::scan_screen(), |r| {
+                    cosmic::Action::App(Message::ScannedQr(r))
+                });
+            }
+            #[cfg(feature = "camera")]
+            Message::ScanQrCamera => {
+                let cancel = std::sync::Arc::default();
+                self.scanning = true;
+                self.camera_scan = Some(std::sync::Arc::clone(&cancel));
+                return Task::perform(camera::scan(cancel), |r| {
+                    cosmic::Action::App(Message::ScannedQr(r))
+                });
+            }
+            #[cfg(feature = "camera")]
+            Message::CancelCameraScan => self.cancel_scan(),
+            Message::ScannedQr(codes) => {
+                #[cfg(feature = "camera")]
+                {
+                    self.camera_scan = None;
+                }
+                // The entry or import the scan was for has been closed since
+                if !std::mem::take(&mut self.scanning) {
+                    return Task::none();
+                }
+                match codes {
+                    Ok(codes) => {
+                        // Other codes on screen, such as links, are left alone
+                        let (migrations, uris): (Vec<_>, Vec<_>) = codes
+                            .into_iter()
+                            .filter(|c| {
+                                formats::google::is_migration_uri(c)
+                                    || c.split_once("://").is_some_and(|(scheme, _)| {
+                                        scheme.eq_ignore_ascii_case("otpauth")
+                                    })
+                            })
+                            .partition(|c| formats::google::is_migration_uri(c));
+                        if migrations.is_empty() && uris.is_empty() {
+                            warn!("No otpauth QR codes found");
+                            self.entry_error = Some("No otpauth QR codes found".into());
+                        }
+                        if let Some(code) = uris.first() {
+                            match entry::Entry::from_uri(code) {
+                                Ok(entry) => {
+                                    self.new_entry = Some(entry);
+                                    self.entry_error = None;
+                                }
+                                Err(e) => {
+                                    warn!("{e}");
+                                    self.entry_error = Some(e);
+                                }
+                            }
+                        }
+                        for uri in &migrations {
+                            if let Err(e) = self.import_migration(uri) {
+                                warn!("{e}");
+                                self.entry_error = Some(e);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to scan QR code: {e}");
+                        self.entry_error = Some(e);
+                    }
+                }
+            }
+            Message::SetSortMode(mode) => {
+                if let Err(e) = self.config.set_sort_mode(&self.config_handler, mode) {
+                    error!("Couldn't save sort mode: {e}");
+                }
+            }
+            Message::ToggleSettings => self.show_settings = !self.show_settings,
+            Message::SetAutoCopySearch(auto_copy) => {
+                if let Err(e) = self
+                    .config
+                    .set_auto_copy_search(&self.config_handler, auto_copy)
+                {
+                    error!("Couldn't save auto copy setting: {e}");
+                }
+            }
+            Message::SetCloseOnCopy(close) => {
+                if let Err(e) = self.config.set_close_on_copy(&self.config_handler, close) {
+                    error!("Couldn't save close on copy setting: {e}");
+                }
+            }
+            Message::SetDownloadLogos(download) => {
+                if let Err(e) = self
+                    .config
+                    .set_download_logos(&self.config_handler, download)
+                {
+                    error!("Couldn't save logo download setting: {e}");
+                }
+            }
+            Message::ConfigChanged(config) => {
+                if config != self.config {
+                    info!("Settings changed");
+                    self.config = config;
+                    clock::set_offset(self.config.time_offset);
+                    // A lock removed from outside the app stays until it's unlocked, while a
+                    // changed one is unlocked with the new passphrase
+                    if let Some(prompt) = &mut self.lock
+                        && let Some(hash) = &self.config.lock_hash
+                    {
+                        prompt.hash.clone_from(hash);
+                    }
+                }
+            }
+            Message::SetClearClipboardAfter(seconds) => {
+                if let Err(e) = self
+                    .config
+                    .set_clear_clipboard_after(&self.config_handler, seconds)
+                {
+                    error!("Couldn't save clipboard clearing setting: {e}");
+                }
+            }
+            Message::SetShowNextCode(show) => self.set_display(|d| d.show_next = show),
+            Message::SetGroupDigits(group) => self.set_display(|d| d.group_digits = group),
+            Message::SetProgressBar(bar) => self.set_display(|d| d.progress_bar = bar),
+            Message::SetReduceMotion(reduce) => self.set_display(|d| d.reduce_motion = reduce),
+            Message::SystemReduceMotion(reduce) => self.system_reduce_motion = reduce,
+            Message::SetWarningBelow(seconds) => self.set_display(|d| d.warning_below = seconds),
+            Message::SetCriticalBelow(seconds) => {
+                self.set_display(|d| d.critical_below = seconds);
+            }
+            Message::SetHideCodes(hide) => self.set_display(|d| d.hide_codes = hide),
+            Message::SetRevealSeconds(seconds) => {
+                self.set_display(|d| d.reveal_seconds = seconds);
+            }
+            Message::SetIdleHideSeconds(seconds) => {
+                self.set_display(|d| d.idle_hide_seconds = seconds);
+                return self.schedule_idle_check();
+            }
+            Message::SetScreenShare(screen_share) => {
+                self.set_display(|d| d.screen_share = screen_share);
+            }
+            Message::PopupActivity { deliberate } => {
+                // Moving the pointer over masked codes shouldn't show them
+                if !self.idle {
+                    self.last_activity = std::time::Instant::now();
+                } else if deliberate {
+                    self.idle = false;
+                    self.last_activity = std::time::Instant::now();
+                    return self.schedule_idle_check();
+                }
+            }
+            Message::IdleCheck => {
+                let timeout = self.config.display.idle_hide_seconds;
+                if self.popup.is_none() || self.idle || timeout == 0 {
+                    return Task::none();
+                }
+                if self.last_activity.elapsed().as_secs() < timeout {
+                    return self.schedule_idle_check();
+                }
+                info!("Masking codes after {timeout} seconds without input");
+                self.idle = true;
+                for entry in self.secret.as_mut_array() {
+                    entry.revealed_until = None;
+                }
+            }
+            Message::SetDefaultAlgorithm(algorithm) => {
+                if let Err(e) = self
+                    .config
+                    .set_default_algorithm(&self.config_handler, algorithm)
+                {
+                    error!("Couldn't save default algorithm: {e}");
+                }
+            }
+            Message::SetDefaultDigits(digits) => {
+                if let Err(e) = self.config.set_default_digits(&self.config_handler, digits) {
+                    error!("Couldn't save default digits: {e}");
+                }
+            }
+            Message::SetDefaultStep(step) => {
+                if let Err(e) = self.config.set_default_step(&self.config_handler, step) {
+                    error!("Couldn't save default step: {e}");
+                }
+            }
+            Message::SearchInput(search) => {
+                let before = self.display_order();
+                self.search = search;
+                let after = self.display_order();
+                // A lone result is highlighted as it's what Enter copies
+                self.selected = (after.len() == 1).then_some(0);
+                // Copy once as the search narrows down, not on every further keystroke
+                // Masked codes need revealing on purpose, not as a side effect of searching
+                let masked = after
+                    .first()
+                    .is_some_and(|&idx| self.secret.as_array()[idx].is_masked(self.display()));
+                if self.config.auto_copy_search
+                    && self.selected.is_some()
+                    && before != after
+                    && !masked
+                {
+                    return self.update(Message::CopySelected);
+                }
+            }
+            Message::SearchSubmit => {
+                self.selected.get_or_insert(0);
+                return self.update(Message::CopySelected);
+            }
+            Message::FocusSearch => {
+                return cosmic::widget::text_input::focus(SEARCH_ID.clone());
+            }
+            Message::MoveSelection { up } => {
+                let len = self.display_order().len();
+                self.selected = match self.selected {
+                    _ if len == 0 => None,
+                    None if up => Some(len - 1),
+                    None => Some(0),
+                    Some(pos) if up => Some(pos.saturating_sub(1)),
+                    Some(pos) => Some((pos + 1).min(len - 1)),
+                };
+                return self.scroll_to_selected();
+            }
+            Message::ListScrolled(viewport) => self.list_viewport = Some(viewport),
+            Message::CopySelected => {
+                if let Some(idx) = self
+                    .selected
+                    .and_then(|pos| self.display_order().get(pos).copied())
+                {
+                    let display = self.display();
+                    let message = if self.secret.as_array()[idx].is_masked(display) {
+                        entry::EntryMessage::Reveal(std::time::Duration::from_secs(
+                            display.reveal_seconds,
+                        ))
+                    } else {
+                        entry::EntryMessage::CopyOutput
+                    };
+                    return self.update(Message::Entry(
+                        entry::EntryR::Index(idx.try_into().unwrap()),
+                        message,
+                    ));
+                }
+            }
+            Message::ClosePopup => {
+                if self.popup.is_some() {
+                    return self.toggle_popup();
+                }
+            }
             Message::EditEntries => {
-                self.editing_entry = Some(Editing { entry: None });
+                self.editing_entry = Some(Editing::default());
+            }
+            Message::EditSelect(idx, selected) => {
+                if let Some(editing) = &mut self.editing_entry {
+                    if editing.selected.len() <= idx {
+                        editing.selected.resize(idx + 1, false);
+                    }
+                    editing.selected[idx] = selected;
+                }
+            }
+            Message::BulkGroupInput(group) => {
+                if let Some(editing) = &mut self.editing_entry {
+                    editing.group = group;
+                }
+            }
+            Message::BulkMoveToGroup | Message::BulkArchive => {
+                let Some(editing) = &mut self.editing_entry else {
+                    return Task::none();
+                };
+                let message = if matches!(message, Message::BulkMoveToGroup) {
+                    let group = std::mem::take(&mut editing.group);
+                    let group = group.trim();
+                    entry::EntryMessage::Group((!group.is_empty()).then(|| group.to_string()))
+                } else {
+                    entry::EntryMessage::Archive(true)
+                };
+                let selection: Vec<_> = editing.selection().collect();
+                if let entry::EntryMessage::Archive(_) = message {
+                    // Archived entries drop out of the list the selection refers to
+                    editing.selected.clear();
+                }
+                for idx in selection {
+                    if let Some(entry) = self.secret.as_mut_array().get_mut(idx)
+                        && let Err(e) = entry.update(message.clone())
+                    {
+                        warn!("{e}");
+                    }
+                }
+                return self.update(Message::Save);
+            }
+            Message::BulkExport => {
+                if let Some(editing) = &self.editing_entry {
+                    let mut selected = editing.selected.clone();
+                    selected.resize(self.secret.as_array().len(), false);
+                    self.exporting = Some(Export {
+                        selected,
+                        codes: Vec::new(),
+                        page: 0,
+                        encrypted: None,
+                        confirm_uri_list: false,
+                    });
+                }
+            }
+            Message::BulkDelete => {
+                if let Some(editing) = &mut self.editing_entry {
+                    editing.confirm_delete = true;
+                }
+            }
+            Message::BulkDeleteCancel => {
+                if let Some(editing) = &mut self.editing_entry {
+                    editing.confirm_delete = false;
+                }
+            }
+            Message::BulkDeleteConfirm => {
+                let Some(editing) = &mut self.editing_entry else {
+                    return Task::none();
+                };
+                let selection: Vec<_> = editing.selection().collect();
+                *editing = Editing::default();
+                // Delete from the back so the remaining indices stay valid
+                let mut deleted: Vec<_> = selection
+                    .into_iter()
+                    .rev()
+                    .filter_map(|idx| Some((idx, self.secret.delete(idx)?)))
+                    .collect();
+                deleted.reverse();
+                return self.defer_delete(deleted);
             }
             Message::MoveEntry { entry, up } => 'mv: {
                 if up && entry == 0 {
@@ -362,43 +2540,1329 @@ impl cosmic::Application for App {
                     break 'mv;
                 }
                 self.secret.as_mut_array().swap(entry, next);
+                if let Some(editing) = &mut self.editing_entry
+                    && next < editing.selected.len()
+                    && entry < editing.selected.len()
+                {
+                    editing.selected.swap(entry, next);
+                }
                 return self.update(Message::Save);
             }
             Message::EditEntry(e) => {
-                self.editing_entry = Some(Editing { entry: Some(e) });
+                if let Some(entry) = self.secret.as_array().get(e) {
+                    self.editing_entry = Some(Editing::entry(e, entry));
+                }
             }
             Message::FinishEdit { only_current } => {
+                let mut logo = Task::none();
+                if let Some(Editing {
+                    entry: Some(idx),
+                    draft: Some(draft),
+                    ..
+                }) = &mut self.editing_entry
+                {
+                    if let Err(e) = draft.check_savable() {
+                        self.entry_error = Some(e);
+                        return Task::none();
+                    }
+                    if let Some(entry) = self.secret.as_mut_array().get_mut(*idx) {
+                        *entry = draft.clone();
+                        entry.show_secret = false;
+                    }
+                    let idx = *idx;
+                    if draft.quick_copy {
+                        self.keep_quick_copy(idx);
+                    }
+                    logo = self.fetch_logo(idx);
+                }
                 if only_current {
-                    self.editing_entry = Some(Editing { entry: None });
+                    self.editing_entry = Some(Editing::default());
                 } else {
                     self.editing_entry = None;
                 }
-                return self.update(Message::Save);
+                return Task::batch([self.update(Message::Save), logo]);
+            }
+            Message::CancelEdit => {
+                if let Some(editing) = &mut self.editing_entry {
+                    let original = editing
+                        .entry
+                        .and_then(|idx| self.secret.as_array().get(idx));
+                    if editing.draft.as_ref().is_some_and(|draft| {
+                        original.is_none_or(|original| draft.differs_from(original))
+                    }) {
+                        editing.confirm_discard = true;
+                    } else {
+                        *editing = Editing::default();
+                    }
+                }
+            }
+            Message::EditUndo => {
+                if let Some(editing) = &mut self.editing_entry
+                    && let Some(draft) = &mut editing.draft
+                    && let Some(previous) = editing.undo.pop()
+                {
+                    editing.redo.push(std::mem::replace(draft, previous));
+                }
+            }
+            Message::EditRedo => {
+                if let Some(editing) = &mut self.editing_entry
+                    && let Some(draft) = &mut editing.draft
+                    && let Some(next) = editing.redo.pop()
+                {
+                    editing.undo.push(std::mem::replace(draft, next));
+                }
+            }
+            Message::KeepEditing => {
+                if let Some(editing) = &mut self.editing_entry {
+                    editing.confirm_discard = false;
+                }
+            }
+            Message::DiscardEdit => {
+                if let Some(editing) = &mut self.editing_entry {
+                    *editing = Editing::default();
+                }
             }
             Message::DeleteEntry(e) => self.pending_delete = Some(e),
+            Message::DuplicateEntry(idx) => {
+                let Some(entry) = self.secret.as_array().get(idx) else {
+                    return Task::none();
+                };
+                if self.secret.try_insert(idx + 1, entry.duplicate()).is_err() {
+                    error!("Failed to insert entry, not loaded yet?");
+                    return Task::none();
+                }
+                // It's the saved entry that's copied, without unsaved changes
+                self.editing_entry = self
+                    .secret
+                    .as_array()
+                    .get(idx + 1)
+                    .map(|duplicate| Editing::entry(idx + 1, duplicate));
+                return self.update(Message::Save);
+            }
             Message::ClearPendingDelete => self.pending_delete = None,
             Message::AcceptPendingDelete => {
-                self.secret.delete(self.pending_delete.take().unwrap());
+                let idx = self.pending_delete.take().unwrap();
+                if let Some(editing) = &mut self.editing_entry
+                    && editing.entry == Some(idx)
+                {
+                    *editing = Editing::default();
+                }
+                if let Some(entry) = self.secret.delete(idx) {
+                    return self.defer_delete(vec![(idx, entry)]);
+                }
+            }
+            Message::Undo => {
+                // Restoring entries shifts the indices the editor refers to
+                if self.editing_entry.is_some() {
+                    return Task::none();
+                }
+                if let Some(deleted) = self.recently_deleted.take() {
+                    for (idx, entry) in deleted.entries {
+                        if self.secret.try_insert(idx, entry).is_err() {
+                            error!("Failed to restore entry, not loaded yet?");
+                        }
+                    }
+                }
+            }
+            Message::UndoExpired(id) => {
+                if self.recently_deleted.as_ref().is_some_and(|d| d.id == id) {
+                    self.recently_deleted = None;
+                    return self.update(Message::Save);
+                }
             }
             Message::StartMigration => self.migrating = true,
-        }
-        cosmic::app::Task::none()
-    }
-
-    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
-        Some(cosmic::applet::style())
-    }
-}
-
-impl App {
-    pub fn toggle_popup(&mut self) -> cosmic::app::Task<Message> {
+            Message::StartExport => {
+                self.exporting = Some(Export {
+                    selected: vec![true; self.secret.as_array().len()],
+                    codes: Vec::new(),
+                    page: 0,
+                    encrypted: None,
+                    confirm_uri_list: false,
+                });
+            }
+            Message::ExportSelect(idx, selected) => {
+                if let Some(export) = &mut self.exporting
+                    && let Some(s) = export.selected.get_mut(idx)
+                {
+                    *s = selected;
+                }
+            }
+            Message::ExportGenerate => {
+                if let Some(export) = &mut self.exporting {
+                    let entries = self
+                        .secret
+                        .as_array()
+                        .iter()
+                        .zip(&export.selected)
+                        .filter_map(|(entry, selected)| selected.then_some(entry))
+                        .filter(|entry| formats::google::is_exportable(entry))
+                        .collect::<Vec<_>>();
+                    let skipped = export.selected.iter().filter(|s| **s).count() - entries.len();
+                    if skipped > 0 {
+                        self.status = Some(format!(
+                            "Skipped {skipped} entries Google Authenticator doesn't support"
+                        ));
+                    }
+                    match formats::google::encode(&entries)
+                        .into_iter()
+                        .map(cosmic::iced::widget::qr_code::Data::new)
+                        .collect::<Result<Vec<_>, _>>()
+                    {
+                        Ok(codes) => {
+                            info!(
+                                "Exporting {} entries as {} codes",
+                                entries.len(),
+                                codes.len()
+                            );
+                            export.codes = codes;
+                            export.page = 0;
+                        }
+                        Err(e) => error!("Couldn't create transfer QR code: {e}"),
+                    }
+                }
+            }
+            Message::ExportPage(page) => {
+                if let Some(export) = &mut self.exporting {
+                    export.page = page;
+                }
+            }
+            Message::ExportEncrypted(format) => {
+                if let Some(export) = &mut self.exporting {
+                    export.encrypted = Some((format, String::new(), String::new()));
+                }
+            }
+            Message::ExportPassword(password) => {
+                if let Some(export) = &mut self.exporting
+                    && let Some((_, p, _)) = &mut export.encrypted
+                {
+                    *p = password;
+                }
+            }
+            Message::ExportPasswordConfirm(password) => {
+                if let Some(export) = &mut self.exporting
+                    && let Some((_, _, p)) = &mut export.encrypted
+                {
+                    *p = password;
+                }
+            }
+            Message::ExportEncryptedSave => {
+                if let Some(export) = self.exporting.take()
+                    && let Some((format, password, _)) = export.encrypted
+                {
+                    let entries = self
+                        .secret
+                        .as_array()
+                        .iter()
+                        .zip(&export.selected)
+                        .filter_map(|(entry, selected)| selected.then(|| entry.clone()))
+                        .collect::<Vec<_>>();
+                    let (file_name, encode): (
+                        _,
+                        Box<dyn FnOnce() -> Result<Vec<u8>, String> + Send>,
+                    ) = match format {
+                        EncryptedExport::Aegis => (
+                            "aegis-export.json",
+                            Box::new(move || formats::aegis::export(&entries, &password)),
+                        ),
+                        EncryptedExport::Backup => (
+                            "totp-backup.age",
+                            Box::new(move || formats::backup::export(&entries, &password)),
+                        ),
+                    };
+                    return Task::perform(formats::export_file(file_name, encode), |r| {
+                        cosmic::Action::App(Message::Exported(r))
+                    });
+                }
+            }
+            Message::ExportUriList => {
+                if let Some(export) = &mut self.exporting {
+                    export.confirm_uri_list = true;
+                }
+            }
+            Message::ExportUriListCancel => {
+                if let Some(export) = &mut self.exporting {
+                    export.confirm_uri_list = false;
+                }
+            }
+            Message::ExportUriListConfirm => {
+                if let Some(export) = self.exporting.take() {
+                    let entries = self
+                        .secret
+                        .as_array()
+                        .iter()
+                        .zip(&export.selected)
+                        .filter_map(|(entry, selected)| selected.then(|| entry.clone()))
+                        .collect::<Vec<_>>();
+                    return Task::perform(
+                        formats::export_file("otpauth-uris.txt", move || {
+                            Ok(formats::uri_list::export(&entries))
+                        }),
+                        |r| cosmic::Action::App(Message::Exported(r)),
+                    );
+                }
+            }
+            Message::Exported(r) => match r {
+                Ok(Some(path)) => self.status = Some(format!("Exported to {}", path.display())),
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Failed to export entries: {e}");
+                    self.entry_error = Some(e);
+                }
+            },
+            Message::FinishExport => self.exporting = None,
+            Message::StartVerify => {
+                let names = self
+                    .secret
+                    .as_array()
+                    .iter()
+                    .map(|entry| match &entry.totp.issuer {
+                        Some(issuer) => format!("{} ({issuer})", entry.totp.account_name),
+                        None => entry.totp.account_name.clone(),
+                    })
+                    .collect();
+                self.verifying = Some(Verify {
+                    names,
+                    entry: None,
+                    code: String::new(),
+                    result: None,
+                });
+            }
+            Message::VerifyEntry(idx) => {
+                if let Some(verify) = &mut self.verifying {
+                    verify.entry = Some(idx);
+                    verify.result = None;
+                }
+            }
+            Message::VerifyInput(code) => {
+                if let Some(verify) = &mut self.verifying {
+                    verify.code = code;
+                    verify.result = None;
+                }
+            }
+            Message::VerifyCheck => {
+                if let Some(verify) = &mut self.verifying
+                    && let Some(entry) =
+                        verify.entry.and_then(|idx| self.secret.as_array().get(idx))
+                {
+                    verify.result = Some(verify_code(entry, &verify.code));
+                }
+            }
+            Message::FinishVerify => self.verifying = None,
+            Message::StartGenerator => {
+                self.generator = Some(Generator::new(self.blank_entry(), GENERATOR_BYTES));
+            }
+            Message::GeneratorBytes(bytes) => {
+                if let Some(generator) = self.generator.take() {
+                    self.generator = Some(Generator::new(generator.entry, bytes));
+                }
+            }
+            Message::GeneratorName(name) => {
+                if let Some(generator) = &mut self.generator {
+                    generator.entry.totp.account_name = name;
+                    generator.entry.recalc_icon();
+                    generator.refresh_qr();
+                }
+            }
+            Message::GeneratorIssuer(issuer) => {
+                if let Some(generator) = &mut self.generator {
+                    generator.entry.totp.issuer = (!issuer.is_empty()).then_some(issuer);
+                    generator.entry.recalc_icon();
+                    generator.refresh_qr();
+                }
+            }
+            Message::GeneratorCopy => {
+                if let Some(generator) = &self.generator {
+                    let task = clipboard::write_sensitive(generator.entry.secret.clone());
+                    if self.config.clear_clipboard_after > 0 {
+                        return Task::batch([
+                            task,
+                            clipboard::clear_after(std::time::Duration::from_secs(
+                                self.config.clear_clipboard_after,
+                            )),
+                        ]);
+                    }
+                    return task;
+                }
+            }
+            Message::GeneratorRegenerate => {
+                if let Some(generator) = self.generator.take() {
+                    self.generator = Some(Generator::new(generator.entry, generator.bytes));
+                }
+            }
+            Message::GeneratorAdd => {
+                if let Some(generator) = self.generator.take() {
+                    self.status = None;
+                    self.new_entry = Some(generator.entry);
+                }
+            }
+            Message::FinishGenerator => self.generator = None,
+            #[cfg(feature = "keepass")]
+            Message::KeePassPick => {
+                return Task::perform(keepass::pick_database(), |p| {
+                    cosmic::Action::App(Message::KeePassPicked(p))
+                });
+            }
+            #[cfg(feature = "keepass")]
+            Message::KeePassPicked(path) => {
+                if let Some(path) = path {
+                    if let Err(e) = self.config_handler.set("keepass-database", Some(&path)) {
+                        error!("Couldn't save KeePassXC database path: {e}");
+                    }
+                    self.keepass_path = Some(path);
+                    self.keepass.clear();
+                    self.keepass_unlocked = false;
+                    self.keepass_password = Some(String::new());
+                }
+            }
+            #[cfg(feature = "keepass")]
+            Message::KeePassPrompt => self.keepass_password = Some(String::new()),
+            #[cfg(feature = "keepass")]
+            Message::KeePassPassword(password) => self.keepass_password = Some(password),
+            #[cfg(feature = "keepass")]
+            Message::KeePassUnlock => {
+                if let Some(password) = self.keepass_password.take()
+                    && let Some(path) = self.keepass_path.clone()
+                {
+                    return Task::perform(keepass::open(path, password), |r| {
+                        cosmic::Action::App(Message::KeePassOpened(r))
+                    });
+                }
+            }
+            #[cfg(feature = "keepass")]
+            Message::KeePassCancel => self.keepass_password = None,
+            #[cfg(feature = "keepass")]
+            Message::KeePassOpened(entries) => match entries {
+                Ok(entries) => {
+                    info!("Loaded {} entries from KeePassXC", entries.len());
+                    self.keepass = entries;
+                    self.keepass_unlocked = true;
+                }
+                Err(e) => {
+                    warn!("Failed to open KeePassXC database: {e}");
+                    self.entry_error = Some(e);
+                }
+            },
+            #[cfg(feature = "keepass")]
+            Message::KeePassClose => {
+                self.keepass.clear();
+                self.keepass_path = None;
+                self.keepass_unlocked = false;
+                if let Err(e) = self
+                    .config_handler
+                    .set::<Option<std::path::PathBuf>>("keepass-database", None)
+                {
+                    error!("Couldn't clear KeePassXC database path: {e}");
+                }
+            }
+            Message::ClockChecked(Ok(offset)) => {
+                info!("The clock is {offset:.3} seconds behind");
+                self.clock_offset = Some(offset);
+            }
+            Message::ClockChecked(Err(e)) => {
+                // Keep the last known offset, the clock rarely jumps between checks
+                warn!("Couldn't check the clock: {e}");
+            }
+            Message::NtpServerInput(server) => self.ntp_server_input = Some(server),
+            Message::SetNtpServer(server) => {
+                self.ntp_server_input = None;
+                if let Err(e) = self.config.set_ntp_server(&self.config_handler, server) {
+                    error!("Couldn't save NTP server setting: {e}");
+                }
+                self.clock_offset = None;
+            }
+            Message::SetTimeOffset(seconds) => {
+                if let Err(e) = self.config.set_time_offset(&self.config_handler, seconds) {
+                    error!("Couldn't save time offset setting: {e}");
+                }
+                clock::set_offset(seconds);
+            }
+            Message::SetClockWarningSeconds(seconds) => {
+                if let Err(e) = self
+                    .config
+                    .set_clock_warning_seconds(&self.config_handler, seconds)
+                {
+                    error!("Couldn't save clock warning setting: {e}");
+                }
+            }
+            Message::SelfTest => {
+                let failures = selftest::run();
+                for failure in &failures {
+                    error!("Self-test failed: {failure}");
+                }
+                self.self_test = Some(failures);
+            }
+            Message::ImportIconPack => {
+                return Task::perform(icon_packs::import(), |r| {
+                    cosmic::Action::App(Message::IconPackImported(r))
+                });
+            }
+            Message::IconPackImported(imported) => match imported {
+                Ok(Some(name)) => {
+                    self.status = Some(format!("Installed icon pack {name}"));
+                    for entry in self.secret.as_mut_array() {
+                        entry.recalc_icon();
+                    }
+                    return self.update(Message::Save);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Failed to import icon pack: {e}");
+                    self.entry_error = Some(e);
+                }
+            },
+            Message::Import => {
+                self.status = None;
+                return Task::perform(formats::import_file(), |r| {
+                    cosmic::Action::App(Message::Imported(r))
+                });
+            }
+            Message::Imported(imported) => match imported {
+                Ok(formats::Imported::Encrypted(data)) => {
+                    self.pending_import = Some(PendingImport {
+                        data,
+                        password: String::new(),
+                        error: None,
+                    });
+                }
+                Ok(formats::Imported::Entries(entries)) if entries.is_empty() => {}
+                Ok(formats::Imported::Entries(entries)) => {
+                    info!("Parsed {} entries to import", entries.len());
+                    self.preview_import(entries);
+                }
+                Err(e) => {
+                    warn!("Failed to import entries: {e}");
+                    self.entry_error = Some(e);
+                }
+            },
+            Message::ImportPassword(password) => {
+                if let Some(pending) = &mut self.pending_import {
+                    pending.password = password;
+                }
+            }
+            Message::ImportUnlock => {
+                // Kept until it's unlocked, so a wrong password can be tried again
+                if let Some(pending) = &self.pending_import {
+                    return Task::perform(
+                        formats::decrypt_import(
+                            std::sync::Arc::clone(&pending.data),
+                            pending.password.clone(),
+                        ),
+                        |r| cosmic::Action::App(Message::ImportUnlocked(r)),
+                    );
+                }
+            }
+            Message::ImportUnlocked(imported) => match imported {
+                Ok(imported) => {
+                    self.pending_import = None;
+                    return self.update(Message::Imported(Ok(imported)));
+                }
+                Err(e) => {
+                    warn!("Failed to decrypt import: {e}");
+                    if let Some(pending) = &mut self.pending_import {
+                        pending.password.clear();
+                        pending.error = Some(e);
+                    }
+                }
+            },
+            Message::ImportCancel => self.pending_import = None,
+            Message::ImportPreviewSelect(idx, selected) => {
+                if let Some(preview) = &mut self.import_preview
+                    && let Some(candidate) = preview.candidates.get_mut(idx)
+                {
+                    candidate.selected = selected;
+                }
+            }
+            Message::ImportPreviewAction(idx, action) => {
+                if let Some(preview) = &mut self.import_preview
+                    && let Some(candidate) = preview.candidates.get_mut(idx)
+                {
+                    candidate.action = action;
+                }
+            }
+            Message::ImportPreviewAccept => {
+                let Some(preview) = self.import_preview.take() else {
+                    return Task::none();
+                };
+                self.imported_batches.clear();
+                let mut count = 0;
+                for candidate in preview.candidates.into_iter().filter(|c| c.selected) {
+                    if candidate.existing.is_some() && candidate.action == DuplicateAction::Skip {
+                        continue;
+                    }
+                    // An entry deleted while the preview was open no longer clashes
+                    let existing = candidate
+                        .existing
+                        .and_then(|id| self.secret.as_array().iter().position(|e| e.id == id));
+                    if self
+                        .add_entry(candidate.entry, existing, candidate.action)
+                        .is_err()
+                    {
+                        error!("Failed to import entry, not loaded yet?");
+                        self.entry_error =
+                            Some("Couldn't import entries, the vault isn't loaded".into());
+                        break;
+                    }
+                    count += 1;
+                }
+                info!("Imported {count} entries");
+                self.status = Some(format!("Imported {count} entries"));
+                return Task::batch([self.update(Message::Save), self.load_thumbnails()]);
+            }
+            Message::ImportPreviewCancel => {
+                self.cancel_scan();
+                self.import_preview = None;
+                self.status = None;
+                self.imported_batches.clear();
+            }
+        }
+        cosmic::app::Task::none()
+    }
+
+    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
+        Some(cosmic::applet::style())
+    }
+}
+
+impl App {
+    fn view_settings(&self) -> cosmic::Element<Message> {
+        use cosmic::widget::{
+            button, column, dropdown, horizontal_space, row, settings, spin_button, text_input,
+            toggler,
+        };
+
+        let config = &self.config;
+        let clipboard = settings::section()
+            .title("Clipboard")
+            .add(settings::item(
+                "Close the popup after copying",
+                toggler(config.close_on_copy).on_toggle(Message::SetCloseOnCopy),
+            ))
+            .add(settings::item(
+                "Copy the only search result",
+                toggler(config.auto_copy_search).on_toggle(Message::SetAutoCopySearch),
+            ));
+        let security = settings::section()
+            .title("Security")
+            .add(settings::item(
+                "Clear copied codes after seconds (0 to keep)",
+                spin_button(
+                    config.clear_clipboard_after.to_string(),
+                    config.clear_clipboard_after,
+                    5,
+                    0,
+                    300,
+                    Message::SetClearClipboardAfter,
+                ),
+            ))
+            .add(settings::item(
+                "Hide codes until clicked",
+                toggler(config.display.hide_codes).on_toggle(Message::SetHideCodes),
+            ))
+            .add(settings::item(
+                "Hide codes after seconds without use (0 to never)",
+                spin_button(
+                    config.display.idle_hide_seconds.to_string(),
+                    config.display.idle_hide_seconds,
+                    5,
+                    0,
+                    600,
+                    Message::SetIdleHideSeconds,
+                ),
+            ))
+            .add(settings::item(
+                "Seconds a clicked code stays shown",
+                spin_button(
+                    config.display.reveal_seconds.to_string(),
+                    config.display.reveal_seconds,
+                    1,
+                    1,
+                    120,
+                    Message::SetRevealSeconds,
+                ),
+            ));
+        let security = match (&config.lock_hash, &self.lock_setup) {
+            (_, Some(setup)) => security.add(
+                column()
+                    .push(settings::item(
+                        "Unlock with",
+                        dropdown(
+                            &lock::LockKind::LABELS,
+                            lock::LockKind::ALL.iter().position(|k| *k == setup.kind),
+                            |idx| Message::LockSetup(lock::LockKind::ALL[idx]),
+                        ),
+                    ))
+                    .push(view_setup(
+                        setup,
+                        Message::LockSetupInput,
+                        Message::LockSetupConfirm,
+                        Message::LockSetupSave,
+                        Message::LockSetupCancel,
+                    ))
+                    .spacing(5),
+            ),
+            (None, None) => security.add(settings::item(
+                "Lock the popup with a passphrase or PIN",
+                button::standard("Set Up").on_press(Message::LockSetup(config.lock_kind)),
+            )),
+            (Some(_), None) => security
+                .add(settings::item(
+                    format!("The popup is locked with a {}", config.lock_kind.name()),
+                    row()
+                        .push(
+                            button::standard("Change")
+                                .on_press(Message::LockSetup(config.lock_kind)),
+                        )
+                        .push(button::destructive("Remove").on_press(Message::RemoveLock))
+                        .spacing(5),
+                ))
+                .add(settings::item(
+                    "Lock after seconds without use (0 to never)",
+                    spin_button(
+                        config.auto_lock_seconds.to_string(),
+                        config.auto_lock_seconds,
+                        30,
+                        0,
+                        3600,
+                        Message::SetAutoLockSeconds,
+                    ),
+                )),
+        };
+        // A vault file has nothing but this to encrypt it
+        let removable = config.storage.encrypts();
+        let security = match (&self.vault_key, &self.vault_setup) {
+            (_, Some(setup)) => security.add(view_setup(
+                setup,
+                Message::VaultSetupInput,
+                Message::VaultSetupConfirm,
+                Message::VaultSetupSave,
+                Message::VaultSetupCancel,
+            )),
+            (None, None) => {
+                let security = security.add(settings::item(
+                    "Encrypt the vault with a passphrase as well as the keyring",
+                    button::standard("Set Up").on_press(Message::VaultSetup),
+                ));
+                #[cfg(feature = "tpm")]
+                let security = security.add(settings::item(
+                    "Bind the vault to this computer's TPM",
+                    button::standard("Bind").on_press(Message::VaultSeal),
+                ));
+                security
+            }
+            (Some(key), None) if key.is_sealed() => security.add(settings::item(
+                "The vault can only be read on this computer",
+                button::destructive("Unbind")
+                    .on_press_maybe(removable.then_some(Message::VaultDecrypt)),
+            )),
+            (Some(_), None) => security.add(settings::item(
+                "The vault is encrypted with a passphrase",
+                row()
+                    .push(button::standard("Change").on_press(Message::VaultSetup))
+                    .push(
+                        button::destructive("Remove")
+                            .on_press_maybe(removable.then_some(Message::VaultDecrypt)),
+                    )
+                    .spacing(5),
+            )),
+        };
+        let display = settings::section()
+            .title("Display")
+            .add(settings::item(
+                "Show the next code",
+                toggler(config.display.show_next).on_toggle(Message::SetShowNextCode),
+            ))
+            .add(settings::item(
+                "Group digits",
+                toggler(config.display.group_digits).on_toggle(Message::SetGroupDigits),
+            ))
+            .add(settings::item(
+                "Show time left as a bar",
+                toggler(config.display.progress_bar).on_toggle(Message::SetProgressBar),
+            ))
+            .add(settings::item(
+                if self.system_reduce_motion {
+                    "Reduce motion (on system wide)"
+                } else {
+                    "Reduce motion"
+                },
+                toggler(config.display.reduce_motion).on_toggle(Message::SetReduceMotion),
+            ))
+            .add(settings::item(
+                "Warn when seconds left are below",
+                spin_button(
+                    config.display.warning_below.to_string(),
+                    config.display.warning_below,
+                    1,
+                    0,
+                    60,
+                    Message::SetWarningBelow,
+                ),
+            ))
+            .add(settings::item(
+                "Alert when seconds left are below",
+                spin_button(
+                    config.display.critical_below.to_string(),
+                    config.display.critical_below,
+                    1,
+                    0,
+                    60,
+                    Message::SetCriticalBelow,
+                ),
+            ));
+        let defaults = settings::section()
+            .title("New Entries")
+            .add(settings::item(
+                "Algorithm",
+                dropdown(
+                    &["SHA1", "SHA256", "SHA512"],
+                    match config.default_algorithm {
+                        totp_rs::Algorithm::SHA1 | totp_rs::Algorithm::Steam => Some(0),
+                        totp_rs::Algorithm::SHA256 => Some(1),
+                        totp_rs::Algorithm::SHA512 => Some(2),
+                    },
+                    |idx| {
+                        Message::SetDefaultAlgorithm(match idx {
+                            1 => totp_rs::Algorithm::SHA256,
+                            2 => totp_rs::Algorithm::SHA512,
+                            _ => totp_rs::Algorithm::SHA1,
+                        })
+                    },
+                ),
+            ))
+            .add(settings::item(
+                "Digits",
+                spin_button(
+                    config.default_digits.to_string(),
+                    config.default_digits,
+                    1,
+                    1,
+                    9,
+                    Message::SetDefaultDigits,
+                ),
+            ))
+            .add(settings::item(
+                "Step",
+                spin_button(
+                    config.default_step.to_string(),
+                    config.default_step,
+                    1,
+                    1,
+                    3600,
+                    Message::SetDefaultStep,
+                ),
+            ));
+        let icons = settings::section()
+            .title("Icons")
+            .add(settings::item(
+                match icon_packs::count() {
+                    0 => "No icon packs installed".to_string(),
+                    1 => "1 icon pack installed".to_string(),
+                    n => format!("{n} icon packs installed"),
+                },
+                button::standard("Import Aegis Icon Pack").on_press(Message::ImportIconPack),
+            ))
+            .add(settings::item(
+                "Download logos for well known issuers",
+                toggler(config.download_logos).on_toggle(Message::SetDownloadLogos),
+            ));
+        let clock = settings::section()
+            .title("Clock")
+            .add(settings::item(
+                "NTP server to check the clock against",
+                text_input(
+                    "None",
+                    self.ntp_server_input
+                        .as_deref()
+                        .unwrap_or(&config.ntp_server),
+                )
+                .on_input(Message::NtpServerInput)
+                .on_submit(Message::SetNtpServer),
+            ))
+            .add(settings::item(
+                "Warn when off by more than seconds (0 to never)",
+                spin_button(
+                    config.clock_warning_seconds.to_string(),
+                    config.clock_warning_seconds,
+                    1,
+                    0,
+                    60,
+                    Message::SetClockWarningSeconds,
+                ),
+            ))
+            .add(settings::item(
+                "Seconds to add to the clock for codes",
+                spin_button(
+                    config.time_offset.to_string(),
+                    config.time_offset,
+                    1,
+                    -3600,
+                    3600,
+                    Message::SetTimeOffset,
+                ),
+            ));
+        // Offers to make up for the drift found, for clocks that can't be fixed
+        #[allow(clippy::cast_possible_truncation)]
+        let drift = self.clock_offset.map(|offset| offset.round() as i64);
+        let clock = match drift {
+            Some(drift) if drift != config.time_offset => clock.add(settings::item(
+                format!(
+                    "The NTP server is {} seconds {}",
+                    drift.unsigned_abs(),
+                    if drift > 0 { "ahead" } else { "behind" }
+                ),
+                button::standard("Use as Offset").on_press(Message::SetTimeOffset(drift)),
+            )),
+            _ => clock,
+        };
+        let diagnostics = settings::section().title("Diagnostics").add(settings::item(
+            match &self.self_test {
+                None => "Check code generation against the RFC 6238 test vectors".to_string(),
+                Some(failures) if failures.is_empty() => {
+                    format!("All {} test codes are correct", selftest::CHECKS)
+                }
+                Some(failures) => format!(
+                    "{} of {} test codes are wrong: {}",
+                    failures.len(),
+                    selftest::CHECKS,
+                    failures.join(", ")
+                ),
+            },
+            button::standard("Run Self-Test").on_press(Message::SelfTest),
+        ));
+        column()
+            .push(clipboard)
+            .push(security)
+            .push(display)
+            .push(defaults)
+            .push(icons)
+            .push(clock)
+            .push(diagnostics)
+            .push(
+                row()
+                    .push(horizontal_space())
+                    .push(button::suggested("Close").on_press(Message::ToggleSettings)),
+            )
+            .spacing(5)
+            .into()
+    }
+
+    /// Choosing the vault to open, or naming a new one.
+    fn view_login(&self) -> cosmic::Element<Message> {
+        use cosmic::widget::{button, column, horizontal_space, row, settings, text, text_input};
+
+        let name = self.user.as_deref().unwrap_or_default();
+        let existing = self.found_users.iter().any(|user| user == name);
+        let submit = (!name.trim().is_empty()).then(|| Message::UsernameSubmit(name.to_string()));
+
+        let found = (!self.found_users.is_empty()).then(|| {
+            self.found_users
+                .iter()
+                .fold(column().spacing(5), |found, user| {
+                    found.push(
+                        button::standard(user.as_str())
+                            .on_press(Message::UsernameSubmit(user.clone())),
+                    )
+                })
+                .push(text::heading("Or create a new one"))
+        });
+        let mut storage = settings::section().title("Storage").add(settings::item(
+            "Store secrets in",
+            cosmic::widget::dropdown(
+                &secrets::Storage::LABELS,
+                secrets::Storage::ALL
+                    .iter()
+                    .position(|s| *s == self.config.storage),
+                |idx| Message::SetStorage(secrets::Storage::ALL[idx]),
+            ),
+        ));
+        if self.config.storage.encrypts() {
+            storage = storage.add(settings::item(
+                "Service name",
+                text_input(crate::APP_ID, &self.config.keyring_service)
+                    .on_input(Message::SetKeyringService),
+            ));
+        }
+        if self.config.storage.has_collection() {
+            let placeholder = if self.config.storage == secrets::Storage::KWallet {
+                "kdewallet"
+            } else {
+                "Default"
+            };
+            storage = storage.add(settings::item(
+                "Collection",
+                text_input(placeholder, &self.config.keyring_collection)
+                    .on_input(Message::SetKeyringCollection),
+            ));
+        }
+
+        column()
+            .push(text::title3("Open a Vault"))
+            .push(text::caption(
+                "Secrets are kept in a vault under a name of your choice. Use different names to \
+                 keep separate vaults, like one for work and one for personal accounts.",
+            ))
+            .push_maybe(found)
+            .push(settings::item(
+                "Name",
+                text_input("Vault name", name)
+                    .on_input(Message::UsernameInput)
+                    .on_submit(Message::UsernameSubmit),
+            ))
+            .push(storage)
+            .push_maybe(self.login_error.as_deref().map(text::caption))
+            .push(row().push(horizontal_space()).push(
+                button::suggested(if existing { "Open" } else { "Create" }).on_press_maybe(submit),
+            ))
+            .spacing(5)
+            .into()
+    }
+
+    /// The unlock prompt, with a keypad when unlocking with a PIN.
+    fn view_lock(&self, prompt: &lock::Prompt) -> cosmic::Element<Message> {
+        use cosmic::widget::{button, column, horizontal_space, icon, row, text, text_input};
+
+        let submit = prompt.can_submit().then_some(Message::UnlockSubmit);
+        let input = text_input(
+            lock::LockKind::LABELS[self.config.lock_kind as usize],
+            &prompt.input,
+        )
+        .password()
+        .on_input(Message::UnlockInput)
+        .on_submit(|_| Message::UnlockSubmit);
+        let content = column()
+            .push(text::title3("Locked"))
+            .push(input)
+            .push_maybe(prompt.error.as_deref().map(text::caption))
+            .spacing(5);
+
+        if self.config.lock_kind == lock::LockKind::Passphrase {
+            return content
+                .push(
+                    row()
+                        .push(horizontal_space())
+                        .push(button::suggested("Unlock").on_press_maybe(submit)),
+                )
+                .into();
+        }
+
+        let key = |content: cosmic::Element<'static, Message>, message: Option<Message>| {
+            button::custom(container(content).center(Length::Fill))
+                .width(Length::Fill)
+                .height(Length::Fixed(48.0))
+                .on_press_maybe(message)
+        };
+        let digit = |d: char| {
+            key(
+                text::title3(d.to_string()).into(),
+                Some(Message::UnlockDigit(d)),
+            )
+        };
+        let mut keypad = column().spacing(5);
+        for digits in ["123", "456", "789"] {
+            keypad = keypad.push(
+                digits
+                    .chars()
+                    .fold(row().spacing(5), |row, d| row.push(digit(d))),
+            );
+        }
+        keypad = keypad.push(
+            row()
+                .push(key(
+                    icon::from_name("edit-clear-symbolic").icon().into(),
+                    (!prompt.input.is_empty()).then_some(Message::UnlockBackspace),
+                ))
+                .push(digit('0'))
+                .push(
+                    key(
+                        icon::from_name("object-select-symbolic").icon().into(),
+                        submit.filter(|_| !prompt.input.is_empty()),
+                    )
+                    .class(cosmic::theme::Button::Suggested),
+                )
+                .spacing(5),
+        );
+        content.push(keypad).into()
+    }
+
+    #[cfg(feature = "keepass")]
+    fn view_keepass(&self) -> cosmic::Element<Message> {
+        use cosmic::widget::{button, column, horizontal_space, icon, row, text};
+
+        let Some(path) = &self.keepass_path else {
+            return column().into();
+        };
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+
+        let header = row()
+            .push(text::heading(format!("KeePassXC: {name}")))
+            .push(horizontal_space())
+            .push(
+                button::icon(icon::from_name("window-close-symbolic"))
+                    .on_press(Message::KeePassClose),
+            )
+            .align_y(cosmic::iced::Alignment::Center);
+        if !self.keepass_unlocked {
+            return column()
+                .push(header)
+                .push(button::standard("Unlock").on_press(Message::KeePassPrompt))
+                .spacing(5)
+                .into();
+        }
+        if self.keepass.is_empty() {
+            return column()
+                .push(header)
+                .push(text::caption("This database has no entries with codes"))
+                .spacing(5)
+                .into();
+        }
+
+        let mut column = column().push(header);
+        for (idx, entry) in self.keepass.iter().enumerate() {
+            column =
+                column.push(entry.view::<true>(self.display()).map(move |m| {
+                    Message::Entry(entry::EntryR::KeePass(idx.try_into().unwrap()), m)
+                }));
+        }
+        column.spacing(5).into()
+    }
+
+    /// Copies the code of the entry picked for the global shortcut.
+    fn quick_copy(&mut self) -> Task<Message> {
+        let Some(idx) = self
+            .secret
+            .as_array()
+            .iter()
+            .position(|e| e.quick_copy && !e.archived && !e.sensitive)
+        else {
+            warn!("No entry is set to be copied with the global shortcut");
+            return Task::none();
+        };
+        let r = entry::EntryR::Index(idx.try_into().unwrap());
+        // Codes are only kept up to date while the popup is open
+        let time = clock::now().as_secs();
+        let refresh = self.update(Message::Entry(
+            r,
+            entry::EntryMessage::Stepped(cosmic::iced::time::Instant::now(), time),
+        ));
+        Task::batch([
+            refresh,
+            self.update(Message::Entry(r, entry::EntryMessage::CopyOutput)),
+        ])
+    }
+
+    fn set_display(&mut self, change: impl FnOnce(&mut entry::DisplayOptions)) {
+        let mut display = self.config.display;
+        change(&mut display);
+        if let Err(e) = self.config.set_display(&self.config_handler, display) {
+            error!("Couldn't save display settings: {e}");
+        }
+    }
+
+    /// The display options, with the system wide reduce motion setting applied.
+    fn display(&self) -> entry::DisplayOptions {
+        entry::DisplayOptions {
+            reduce_motion: self.config.display.reduce_motion || self.system_reduce_motion,
+            hide_codes: self.config.display.hide_codes || self.idle,
+            ..self.config.display
+        }
+    }
+
+    /// The rows of the main list, with headings where the favorites start and end.
+    fn list_rows(&self) -> Vec<ListRow> {
+        let entries = self.secret.as_array();
+        let order = self.display_order();
+        let mut rows = Vec::with_capacity(order.len() + 2);
+        for (pos, &idx) in order.iter().enumerate() {
+            if pos == 0 && entries[idx].favorite {
+                rows.push(ListRow::Heading("Favorites"));
+            } else if pos > 0 && entries[order[pos - 1]].favorite && !entries[idx].favorite {
+                rows.push(ListRow::Heading("All Entries"));
+            }
+            rows.push(ListRow::Entry { pos, idx });
+        }
+        rows
+    }
+
+    /// Scrolls the main list just enough to show the keyboard selection.
+    fn scroll_to_selected(&self) -> Task<Message> {
+        let Some(selected) = self.selected else {
+            return Task::none();
+        };
+        let display = self.display();
+        let entries = self.secret.as_array();
+        let mut y = 0.0;
+        for row in self.list_rows() {
+            let row_height = row.height(entries, display);
+            if let ListRow::Entry { pos, .. } = row
+                && pos == selected
+            {
+                let (top, height) = self.list_viewport.map_or((0.0, LIST_MAX_HEIGHT), |v| {
+                    (v.absolute_offset().y, v.bounds().height)
+                });
+                let y = if y < top {
+                    y
+                } else if y + row_height > top + height {
+                    y + row_height - height
+                } else {
+                    return Task::none();
+                };
+                return cosmic::iced::widget::scrollable::scroll_to(
+                    LIST_ID.clone(),
+                    cosmic::iced::widget::scrollable::AbsoluteOffset { x: 0.0, y },
+                );
+            }
+            y += row_height;
+        }
+        Task::none()
+    }
+
+    /// Records the draft as it was before a change, so the change can be undone.
+    fn record_edit(&mut self, before: entry::Entry) {
+        let Some(editing) = &mut self.editing_entry else {
+            return;
+        };
+        if !editing
+            .draft
+            .as_ref()
+            .is_some_and(|draft| draft.differs_from(&before))
+        {
+            return;
+        }
+        if editing.undo.len() == EDIT_HISTORY {
+            editing.undo.remove(0);
+        }
+        editing.undo.push(before);
+        editing.redo.clear();
+    }
+
+    /// Adds the current user to the users that can be switched to.
+    fn remember_user(&mut self) {
+        let Some(user) = &self.user else {
+            return;
+        };
+        if self.config.known_users.contains(user) {
+            return;
+        }
+        let mut known_users = self.config.known_users.clone();
+        known_users.push(user.clone());
+        if let Err(e) = self
+            .config
+            .set_known_users(&self.config_handler, known_users)
+        {
+            error!("Couldn't save known users: {e}");
+        }
+    }
+
+    /// The current user, which can be switched to the others that are known.
+    fn view_user_switcher(&self) -> cosmic::Element<Message> {
+        let user = self.user.as_deref().unwrap_or_default();
+        if self.config.known_users.len() < 2 {
+            return cosmic::widget::text(user).into();
+        }
+        cosmic::widget::dropdown(
+            &self.config.known_users,
+            self.config.known_users.iter().position(|u| u == user),
+            Message::SwitchUser,
+        )
+        .into()
+    }
+
+    /// Schedules another attempt at a failed read or write, until it's failed too often.
+    fn storage_failed(&mut self, op: StorageOp, error: String) -> Task<Message> {
+        let attempts = self
+            .storage_failure
+            .as_ref()
+            .filter(|failure| failure.op == op)
+            .map_or(0, |failure| failure.attempts)
+            + 1;
+        let wait = secrets::retry_delay(attempts);
+        self.storage_failure = Some(StorageFailure {
+            op,
+            attempts,
+            error,
+            retrying: wait.is_some(),
+        });
+        wait.map_or_else(Task::none, |wait| {
+            info!("Retrying in {} seconds", wait.as_secs());
+            Task::perform(tokio::time::sleep(wait), move |()| {
+                cosmic::Action::App(Message::StorageRetry(op))
+            })
+        })
+    }
+
+    fn storage_succeeded(&mut self, op: StorageOp) {
+        if self.storage_failure.as_ref().is_some_and(|f| f.op == op) {
+            self.storage_failure = None;
+        }
+    }
+
+    /// Whether the vault is stored in a file that doesn't have a key to be encrypted with yet.
+    fn needs_vault_key(&self) -> bool {
+        !self.config.storage.encrypts()
+            && self.vault_key.is_none()
+            && matches!(self.secret, secrets::State::Secrets(_))
+    }
+
+    /// Why codes may be rejected, if the clock is further off than the settings allow.
+    fn clock_warning(&self) -> Option<String> {
+        let offset = self.clock_offset?;
+        // The offset from the settings makes up for some or all of the drift
+        #[allow(clippy::cast_precision_loss)]
+        let offset = offset - self.config.time_offset as f64;
+        let threshold = self.config.clock_warning_seconds;
+        // Whole seconds are plenty for a warning
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let seconds = offset.abs().round() as u64;
+        if threshold == 0 || seconds <= threshold {
+            return None;
+        }
+        Some(format!(
+            "The clock is {seconds} seconds {} and codes may be rejected, check the time settings",
+            if offset > 0.0 { "slow" } else { "fast" }
+        ))
+    }
+
+    /// A new entry with the default parameters from the settings.
+    fn blank_entry(&self) -> entry::Entry {
+        let mut entry = entry::Entry::new();
+        entry.totp.algorithm = self.config.default_algorithm;
+        entry.totp.digits = self.config.default_digits;
+        entry.totp.step = self.config.default_step;
+        entry
+    }
+
+    /// Whether the popup is showing the main list of codes.
+    fn showing_list(&self) -> bool {
+        matches!(self.secret, secrets::State::Secrets(_))
+            && self.import_preview.is_none()
+            && self.new_entry.is_none()
+            && self.scratch.is_none()
+            && self.exporting.is_none()
+            && self.verifying.is_none()
+            && self.generator.is_none()
+            && self.editing_entry.is_none()
+            && !self.show_settings
+            && !self.needs_vault_key()
+    }
+
+    pub fn toggle_popup(&mut self) -> cosmic::app::Task<Message> {
         info!("Toggling popup window");
 
         if let Some(id) = self.popup.take() {
             info!("Popup exists, removing");
-            return cosmic::iced::platform_specific::shell::wayland::commands::popup::destroy_popup(
-                id,
-            );
+            // Edits still waiting out the save delay are written now, as the applet may be closed
+            // along with the popup
+            let flush = if std::mem::take(&mut self.dirty) {
+                self.set_secret_key()
+            } else {
+                Task::none()
+            };
+            return Task::batch([
+                cosmic::iced::platform_specific::shell::wayland::commands::popup::destroy_popup(id),
+                flush,
+            ]);
         }
 
         info!("Popup doesn't exist, creating");
@@ -416,34 +3880,701 @@ impl App {
         );
         settings.positioner.size = Some((300, 600));
         self.popup = Some(id);
+        self.idle = false;
+        self.last_activity = std::time::Instant::now();
 
         let popup_task =
             cosmic::iced::platform_specific::shell::wayland::commands::popup::get_popup(settings);
         let secret_task = match &self.secret {
-            secrets::State::PendingUser => self.get_secret_key(),
-            secrets::State::Secrets(_) => Task::none(),
+            // The vault isn't read until the app lock has been unlocked
+            secrets::State::PendingUser if self.lock.is_none() && self.user.is_none() => {
+                self.find_users()
+            }
+            secrets::State::PendingUser if self.lock.is_none() => self.get_secret_key(),
+            secrets::State::PendingUser
+            | secrets::State::Encrypted
+            | secrets::State::KeyringLocked
+            | secrets::State::Corrupt(_)
+            | secrets::State::Secrets(_) => Task::none(),
         };
 
-        Task::batch([popup_task, secret_task])
+        Task::batch([popup_task, secret_task, self.schedule_idle_check()])
+    }
+
+    /// Closes the vault and shows the unlock prompt.
+    fn lock(&mut self) -> Task<Message> {
+        let Some(hash) = self.config.lock_hash.clone() else {
+            return Task::none();
+        };
+        info!("Locking");
+        let flush = self.close_vault();
+        let (failures, retry_at) = (self.config.lock_failures, self.config.lock_retry_at);
+        self.lock
+            .get_or_insert_with(|| lock::Prompt::new(hash, failures, retry_at));
+        flush
+    }
+
+    /// Writes out and drops the vault and anything being edited, keeping the user.
+    fn close_vault(&mut self) -> Task<Message> {
+        self.cancel_scan();
+        // Pending deletions can't be undone once the vault is gone
+        self.recently_deleted = None;
+        self.dirty = false;
+        let flush = self.write_vault(true);
+        self.secret = secrets::State::PendingUser;
+        self.vault_key = None;
+        self.vault_passphrase.clear();
+        self.vault_error = None;
+        self.vault_setup = None;
+        self.new_entry = None;
+        self.scratch = None;
+        self.entry_error = None;
+        self.editing_entry = None;
+        self.pending_delete = None;
+        self.pending_duplicate = None;
+        self.exporting = None;
+        self.verifying = None;
+        self.generator = None;
+        self.pending_import = None;
+        self.import_preview = None;
+        self.show_settings = false;
+        self.lock_setup = None;
+        self.search.clear();
+        self.selected = None;
+        #[cfg(feature = "keepass")]
+        {
+            self.keepass.clear();
+            self.keepass_password = None;
+            self.keepass_unlocked = false;
+        }
+        flush
+    }
+
+    /// Checks for inactivity once the app lock could be due to lock again.
+    fn schedule_auto_lock(&self) -> Task<Message> {
+        let timeout = std::time::Duration::from_secs(self.config.auto_lock_seconds);
+        if timeout.is_zero() || self.lock.is_some() || self.config.lock_hash.is_none() {
+            return Task::none();
+        }
+        Task::perform(
+            tokio::time::sleep(timeout.saturating_sub(self.last_activity.elapsed())),
+            |()| cosmic::Action::App(Message::AutoLockCheck),
+        )
+    }
+
+    /// Checks for inactivity once the popup could have been idle for long enough.
+    fn schedule_idle_check(&self) -> Task<Message> {
+        let timeout = std::time::Duration::from_secs(self.config.display.idle_hide_seconds);
+        if timeout.is_zero() || self.popup.is_none() {
+            return Task::none();
+        }
+        Task::perform(
+            tokio::time::sleep(timeout.saturating_sub(self.last_activity.elapsed())),
+            |()| cosmic::Action::App(Message::IdleCheck),
+        )
+    }
+
+    /// Keeps deleted entries around for a while so the deletion can be undone.
+    fn defer_delete(&mut self, entries: Vec<(usize, entry::Entry)>) -> Task<Message> {
+        if entries.is_empty() {
+            return Task::none();
+        }
+        self.undo_id += 1;
+        let id = self.undo_id;
+        self.recently_deleted = Some(Deleted { entries, id });
+        Task::perform(tokio::time::sleep(UNDO_TIMEOUT), move |()| {
+            cosmic::Action::App(Message::UndoExpired(id))
+        })
+    }
+
+    /// The indices of the entries matching the search, in the order they're shown in the popup.
+    pub fn display_order(&self) -> Vec<usize> {
+        let entries = self.secret.as_array();
+        let query = self.search.to_lowercase();
+        let mut order: Vec<usize> = (0..entries.len())
+            .filter(|&idx| !entries[idx].archived && entries[idx].matches(&query))
+            .collect();
+        if self.config.sort_mode == SortMode::RecentlyUsed {
+            // Never used entries sort last, keeping their manual order
+            order.sort_by_key(|&idx| std::cmp::Reverse(entries[idx].last_used));
+        }
+        // Favorites are listed first
+        order.sort_by_key(|&idx| !entries[idx].favorite);
+        order
+    }
+
+    /// Adds an entry to the vault, resolving a clash with the entry at `existing` using `action`.
+    ///
+    /// Returns where the entry ended up, if it wasn't skipped, or the entry if the vault isn't
+    /// loaded.
+    pub fn add_entry(
+        &mut self,
+        entry: entry::Entry,
+        existing: Option<usize>,
+        action: DuplicateAction,
+    ) -> Result<Option<usize>, entry::Entry> {
+        let added = match (existing, action) {
+            (Some(_), DuplicateAction::Skip) => None,
+            (Some(idx), DuplicateAction::Overwrite) => {
+                if let Some(existing) = self.secret.as_mut_array().get_mut(idx) {
+                    *existing = entry;
+                }
+                Some(idx)
+            }
+            (Some(idx), DuplicateAction::Merge) => {
+                if let Some(existing) = self.secret.as_mut_array().get_mut(idx) {
+                    existing.merge(entry);
+                }
+                Some(idx)
+            }
+            (None, _) | (Some(_), DuplicateAction::AddCopy) => {
+                self.secret.try_push(entry)?;
+                self.secret.as_array().len().checked_sub(1)
+            }
+        };
+        if let Some(idx) = added
+            && self
+                .secret
+                .as_array()
+                .get(idx)
+                .is_some_and(|e| e.quick_copy)
+        {
+            self.keep_quick_copy(idx);
+        }
+
+        Ok(added)
+    }
+
+    /// Downloads the logo for the saved entry at `idx`, if logos are downloaded at all.
+    fn fetch_logo(&self, idx: usize) -> Task<Message> {
+        let (true, Some(entry), Ok(r)) = (
+            self.config.download_logos,
+            self.secret.as_array().get(idx),
+            u32::try_from(idx),
+        ) else {
+            return Task::none();
+        };
+        entry
+            .fetch_brand_icon()
+            .map(move |m| cosmic::Action::App(Message::Entry(entry::EntryR::Index(r), m)))
+    }
+
+    /// Leaves the entry at `idx` as the only one copied by the global shortcut.
+    fn keep_quick_copy(&mut self, idx: usize) {
+        for (other, entry) in self.secret.as_mut_array().iter_mut().enumerate() {
+            entry.quick_copy &= other == idx;
+        }
+    }
+
+    /// Adds parsed entries to the import preview, flagging the ones already in the vault.
+    pub fn preview_import(&mut self, entries: Vec<entry::Entry>) {
+        let preview = self
+            .import_preview
+            .get_or_insert_with(ImportPreview::default);
+        for entry in entries {
+            let existing = self
+                .secret
+                .as_array()
+                .iter()
+                .find(|e| e.is_duplicate_of(&entry))
+                .map(|e| e.id.clone());
+            // Only the first of several copies in the same import is picked
+            let repeated = preview
+                .candidates
+                .iter()
+                .any(|c| c.entry.is_duplicate_of(&entry));
+            preview.candidates.push(ImportCandidate {
+                entry,
+                selected: !repeated,
+                existing,
+                repeated,
+                action: DuplicateAction::Skip,
+            });
+        }
+    }
+
+    /// Previews the entries of a Google Authenticator transfer code, skipping scanned codes.
+    pub fn import_migration(&mut self, uri: &str) -> Result<(), String> {
+        let batch = formats::google::decode(uri)?;
+        if self.imported_batches.contains(&(batch.id, batch.index)) {
+            info!("Skipping already scanned migration code");
+            return Ok(());
+        }
+
+        let count = batch.entries.len();
+        self.preview_import(batch.entries);
+        self.imported_batches.push((batch.id, batch.index));
+
+        let scanned = self
+            .imported_batches
+            .iter()
+            .filter(|(id, _)| *id == batch.id)
+            .count();
+        let mut status = format!(
+            "Found {count} entries in code {} of {}",
+            batch.index + 1,
+            batch.size
+        );
+        let incomplete = i32::try_from(scanned).is_ok_and(|s| s < batch.size);
+        if incomplete {
+            status.push_str(", scan the next code to continue");
+        }
+        if let Some(preview) = &mut self.import_preview {
+            preview.incomplete_batch = incomplete;
+        }
+        info!("{status}");
+        self.status = Some(status);
+
+        Ok(())
+    }
+
+    /// Creates the thumbnails of the vault's image icons in the background.
+    fn load_thumbnails(&self) -> Task<Message> {
+        Task::batch(
+            self.secret
+                .as_array()
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, entry)| {
+                    let r = entry::EntryR::Index(u32::try_from(idx).ok()?);
+                    Some(
+                        entry
+                            .load_thumbnail()
+                            .map(move |m| cosmic::Action::App(Message::Entry(r, m))),
+                    )
+                }),
+        )
+    }
+
+    /// Where the vault is kept, as set in the config.
+    fn location(&self) -> secrets::Location {
+        secrets::Location {
+            storage: self.config.storage,
+            service: self.config.keyring_service.clone(),
+            collection: self.config.keyring_collection.clone(),
+        }
+    }
+
+    /// Looks for existing vaults to offer at login.
+    fn find_users(&self) -> Task<Message> {
+        Task::perform(secrets::find_users(self.location()), |r| {
+            cosmic::Action::App(Message::FoundUsers(r))
+        })
     }
 
     pub fn get_secret_key(&self) -> Task<Message> {
         self.user.clone().map_or_else(Task::none, |user| {
-            Task::perform(secrets::get_secret_key(user), |s| {
+            Task::perform(secrets::get_secret_key(user, self.location()), |s| {
                 cosmic::Action::App(Message::RetrievedKey(s))
             })
         })
     }
     pub fn set_secret_key(&self) -> Task<Message> {
+        self.write_vault(false)
+    }
+
+    /// Writes the vault, letting storage know if it's the last write before the vault is closed.
+    fn write_vault(&self, closing: bool) -> Task<Message> {
         self.user
             .clone()
             .map_or_else(Task::none, |user| match &self.secret {
-                secrets::State::PendingUser => Task::none(),
-                secrets::State::Secrets(hash_map) => {
-                    Task::perform(secrets::set_secret_key(user, hash_map.clone()), |s| {
-                        cosmic::Action::App(Message::SetKey(s))
-                    })
+                secrets::State::PendingUser
+                | secrets::State::Encrypted
+                | secrets::State::KeyringLocked
+                | secrets::State::Corrupt(_) => Task::none(),
+                secrets::State::Secrets(entries) => {
+                    let mut entries: Vec<_> = entries.iter().collect();
+                    // Deletions are only saved once they can't be undone
+                    if let Some(deleted) = &self.recently_deleted {
+                        for (idx, entry) in &deleted.entries {
+                            entries.insert((*idx).min(entries.len()), entry);
+                        }
+                    }
+                    match secrets::serialize(entries) {
+                        Ok(entries) => Task::perform(
+                            secrets::set_secret_key(
+                                user,
+                                self.location(),
+                                entries,
+                                self.vault_key.clone(),
+                                closing,
+                            ),
+                            |s| cosmic::Action::App(Message::SetKey(s)),
+                        ),
+                        Err(e) => Task::done(cosmic::Action::App(Message::SetKey(Err(e)))),
+                    }
                 }
             })
     }
+
+    /// Drops the result of a running QR scan, stopping it if it's using the camera.
+    fn cancel_scan(&mut self) {
+        self.scanning = false;
+        #[cfg(feature = "camera")]
+        {
+            if let Some(cancel) = self.camera_scan.take() {
+                cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Where a code falls relative to the entry's current one, and whether it would be accepted.
+fn verify_code(entry: &entry::Entry, code: &str) -> String {
+    let now = clock::now().as_secs();
+    let hotp = matches!(entry.kind, entry::EntryKind::Hotp { .. });
+    let Some(offset) = entry.find_code(code, now, VERIFY_SEARCH_STEPS) else {
+        return format!(
+            "The code doesn't match any within {VERIFY_SEARCH_STEPS} {} either way, the secret \
+             or settings may differ",
+            if hotp { "counter values" } else { "steps" }
+        );
+    };
+    if offset == 0 {
+        return "The code matches the current one".into();
+    }
+    let steps = offset.unsigned_abs();
+    let direction = if offset < 0 { "behind" } else { "ahead of" };
+    let distance = if hotp {
+        format!("{steps} counter values {direction} the current one")
+    } else {
+        format!(
+            "{steps} steps {direction} the current one, about {} seconds",
+            steps.saturating_mul(entry.totp.step)
+        )
+    };
+    if steps <= u64::from(entry.totp.skew) {
+        format!("The code is valid, {distance}")
+    } else if hotp {
+        format!("The code is {distance}, outside the skew, so the counters are out of step")
+    } else {
+        format!("The code is {distance}, outside the skew, so one of the clocks is off")
+    }
+}
+
+/// The generated secret and its QR code, with the names authenticators show for it.
+fn view_generator(generator: &Generator) -> cosmic::Element<'_, Message> {
+    use cosmic::widget::{
+        button, column, container, horizontal_space, row, settings, spin_button, text, text_input,
+    };
+
+    let entry = &generator.entry;
+    column()
+        .push(text::title1("New Secret"))
+        .push(text::caption(
+            "A random secret for setting up TOTP on your own server. Give the server the secret, \
+             and scan the QR code with the devices that should generate its codes.",
+        ))
+        .push(
+            settings::section()
+                .add(settings::item(
+                    "Length in bytes",
+                    spin_button(
+                        generator.bytes.to_string(),
+                        generator.bytes,
+                        1,
+                        10,
+                        64,
+                        Message::GeneratorBytes,
+                    ),
+                ))
+                .add(settings::item(
+                    "Name",
+                    text_input("Account", &entry.totp.account_name)
+                        .on_input(Message::GeneratorName),
+                ))
+                .add(settings::item(
+                    "Issuer",
+                    text_input("None", entry.totp.issuer.as_deref().unwrap_or_default())
+                        .on_input(Message::GeneratorIssuer),
+                ))
+                .add(settings::item_row(vec![
+                    text::monotext(&entry.secret).into(),
+                    horizontal_space().into(),
+                    button::standard("Copy")
+                        .on_press(Message::GeneratorCopy)
+                        .into(),
+                ])),
+        )
+        .push_maybe(
+            generator
+                .qr
+                .as_ref()
+                .map(|qr| container(cosmic::iced::widget::qr_code(qr)).center_x(Length::Fill)),
+        )
+        .push(
+            row()
+                .push(button::standard("Regenerate").on_press(Message::GeneratorRegenerate))
+                .push(horizontal_space())
+                .push(button::standard("Add as Entry").on_press(Message::GeneratorAdd))
+                .push(button::suggested("Close").on_press(Message::FinishGenerator))
+                .spacing(5),
+        )
+        .spacing(5)
+        .into()
+}
+
+/// Choosing an entry and a code to check against it.
+fn view_verify(verify: &Verify) -> cosmic::Element<'_, Message> {
+    use cosmic::widget::{
+        button, column, dropdown, horizontal_space, row, settings, text, text_input,
+    };
+
+    column()
+        .push(text::title1("Verify Code"))
+        .push(text::caption(
+            "Check a code shown by another device or given by someone else against an entry, \
+             to find clock drift or confirm a shared secret.",
+        ))
+        .push(
+            settings::section()
+                .add(settings::item(
+                    "Entry",
+                    dropdown(&verify.names, verify.entry, Message::VerifyEntry),
+                ))
+                .add(settings::item(
+                    "Code",
+                    text_input("123456", &verify.code)
+                        .on_input(Message::VerifyInput)
+                        .on_submit(|_| Message::VerifyCheck),
+                )),
+        )
+        .push_maybe(verify.result.as_deref().map(text::body))
+        .push(
+            row()
+                .push(horizontal_space())
+                .push(
+                    button::standard("Check").on_press_maybe(
+                        (verify.entry.is_some() && !verify.code.trim().is_empty())
+                            .then_some(Message::VerifyCheck),
+                    ),
+                )
+                .push(button::suggested("Close").on_press(Message::FinishVerify))
+                .spacing(5),
+        )
+        .spacing(5)
+        .into()
+}
+
+/// Why an entry can't be saved yet, pointing at the settings marked with problems if there are any.
+fn savable_caption<'a>(
+    entry: &entry::Entry,
+    savable: &Result<(), String>,
+) -> Option<cosmic::Element<'a, Message>> {
+    let error = savable.as_ref().err()?;
+    Some(
+        cosmic::widget::text::caption(if entry.problems().is_empty() {
+            error.clone()
+        } else {
+            "Fix the settings marked above".into()
+        })
+        .into(),
+    )
+}
+
+/// Shown instead of the list while the vault has no entries, with the ways to add some.
+fn view_empty() -> cosmic::Element<'static, Message> {
+    use cosmic::widget::{button, column, container, icon, row, text};
+
+    let action = |name, label, message| {
+        button::custom(
+            container(
+                column()
+                    .push(icon::from_name(name).size(32))
+                    .push(text(label))
+                    .spacing(5)
+                    .align_x(cosmic::iced::Alignment::Center),
+            )
+            .center(Length::Fill),
+        )
+        .class(cosmic::theme::Button::Standard)
+        .width(Length::Fill)
+        .height(Length::Fixed(96.0))
+        .on_press(message)
+    };
+    column()
+        .push(text::title3("No Entries Yet"))
+        .push(text::caption(
+            "Add an entry by hand, scan the QR code a site shows you, or import entries from \
+             another authenticator.",
+        ))
+        .push(
+            row()
+                .push(action("list-add-symbolic", "Add Entry", Message::NewEntry))
+                .push(action(
+                    "view-fullscreen-symbolic",
+                    "Scan QR",
+                    Message::ScanQrScreen,
+                ))
+                .push(action("document-open-symbolic", "Import", Message::Import))
+                .spacing(5),
+        )
+        .spacing(10)
+        .into()
+}
+
+/// What went wrong reading or writing the vault, with a way to retry once retries have run out.
+fn view_storage_failure(failure: &StorageFailure) -> cosmic::Element<'_, Message> {
+    use cosmic::widget::{button, column, horizontal_space, row, text};
+
+    let summary = match (failure.op, failure.retrying) {
+        (StorageOp::Load, true) => "Couldn't read the vault, trying again...",
+        (StorageOp::Load, false) => "Couldn't read the vault",
+        (StorageOp::Save, true) => "Couldn't save the vault, trying again...",
+        (StorageOp::Save, false) => "Couldn't save the vault",
+    };
+    let no_keyring = failure.op == StorageOp::Load && failure.error == secrets::NO_KEYRING;
+    column()
+        .push(text(summary))
+        .push(text::caption(&failure.error))
+        .push_maybe((!failure.retrying).then(|| {
+            row()
+                .push(horizontal_space())
+                .push_maybe(no_keyring.then(|| {
+                    button::standard("Use a File Instead").on_press(Message::UseFileStorage)
+                }))
+                .push(button::standard("Retry").on_press(Message::StorageRetry(failure.op)))
+                .spacing(5)
+        }))
+        .spacing(5)
+        .into()
+}
+
+/// The inputs for a new passphrase or PIN and its confirmation.
+fn view_setup(
+    setup: &lock::Setup,
+    on_input: fn(String) -> Message,
+    on_confirm: fn(String) -> Message,
+    save: Message,
+    cancel: Message,
+) -> cosmic::Element<'_, Message> {
+    use cosmic::widget::{button, column, horizontal_space, row, text, text_input};
+
+    let valid = setup.validate();
+    let (new, repeat) = match setup.kind {
+        lock::LockKind::Passphrase => ("New passphrase", "Confirm passphrase"),
+        lock::LockKind::Pin => ("New PIN", "Confirm PIN"),
+    };
+    let submit = save.clone();
+    column()
+        .push(text_input(new, &setup.secret).password().on_input(on_input))
+        .push(
+            text_input(repeat, &setup.confirm)
+                .password()
+                .on_input(on_confirm)
+                .on_submit(move |_| submit.clone()),
+        )
+        .push(
+            row()
+                // Nagging before anything has been confirmed isn't helpful
+                .push_maybe(
+                    valid
+                        .err()
+                        .filter(|_| !setup.confirm.is_empty())
+                        .map(text::caption),
+                )
+                .push(horizontal_space())
+                .push(button::standard("Cancel").on_press(cancel))
+                .push(button::suggested("Save").on_press_maybe(valid.is_ok().then_some(save)))
+                .spacing(5)
+                .align_y(cosmic::iced::Alignment::Center),
+        )
+        .spacing(5)
+        .into()
+}
+
+/// Whether the desktop has animations turned off, as far as the settings portal knows.
+async fn system_reduce_motion() -> bool {
+    let settings = match ashpd::desktop::settings::Settings::new().await {
+        Ok(settings) => settings,
+        Err(e) => {
+            warn!("Couldn't access settings portal: {e}");
+            return false;
+        }
+    };
+    settings
+        .read::<bool>("org.gnome.desktop.interface", "enable-animations")
+        .await
+        .map_or_else(
+            |e| {
+                info!("No system animation setting: {e}");
+                false
+            },
+            |enabled| !enabled,
+        )
+}
+
+/// Input that counts as using the popup, for masking codes when it's left open.
+fn popup_activity(
+    event: cosmic::iced::Event,
+    _status: cosmic::iced::event::Status,
+    _window: cosmic::iced::window::Id,
+) -> Option<Message> {
+    use cosmic::iced::{Event, keyboard, mouse};
+
+    match event {
+        Event::Mouse(mouse::Event::CursorMoved { .. } | mouse::Event::WheelScrolled { .. }) => {
+            Some(Message::PopupActivity { deliberate: false })
+        }
+        Event::Mouse(mouse::Event::ButtonPressed(_))
+        | Event::Keyboard(keyboard::Event::KeyPressed { .. }) => {
+            Some(Message::PopupActivity { deliberate: true })
+        }
+        _ => None,
+    }
+}
+
+/// Undo and redo in the entry editor, which also work while a text field has focus.
+fn editor_key_press(
+    event: cosmic::iced::Event,
+    _status: cosmic::iced::event::Status,
+    _window: cosmic::iced::window::Id,
+) -> Option<Message> {
+    use cosmic::iced::{
+        Event,
+        keyboard::{self, Key},
+    };
+
+    let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = event else {
+        return None;
+    };
+    if !modifiers.command() {
+        return None;
+    }
+    match key.as_ref() {
+        Key::Character("z" | "Z") if modifiers.shift() => Some(Message::EditRedo),
+        Key::Character("z" | "Z") => Some(Message::EditUndo),
+        Key::Character("y" | "Y") => Some(Message::EditRedo),
+        _ => None,
+    }
+}
+
+/// Keys that apply anywhere in the popup.
+fn popup_key_press(
+    key: cosmic::iced::keyboard::Key,
+    _modifiers: cosmic::iced::keyboard::Modifiers,
+) -> Option<Message> {
+    use cosmic::iced::keyboard::{Key, key::Named};
+
+    matches!(key, Key::Named(Named::Escape)).then_some(Message::ClosePopup)
+}
+
+/// Keys for navigating the main list, only received while no widget has captured them.
+fn list_key_press(
+    key: cosmic::iced::keyboard::Key,
+    modifiers: cosmic::iced::keyboard::Modifiers,
+) -> Option<Message> {
+    use cosmic::iced::keyboard::{Key, key::Named};
+
+    match key.as_ref() {
+        Key::Named(Named::ArrowUp) | Key::Character("k") => {
+            Some(Message::MoveSelection { up: true })
+        }
+        Key::Named(Named::ArrowDown) | Key::Character("j") => {
+            Some(Message::MoveSelection { up: false })
+        }
+        Key::Named(Named::Enter) => Some(Message::CopySelected),
+        Key::Character("/") => Some(Message::FocusSearch),
+        _ => popup_key_press(key, modifiers),
+    }
 }
@@ -0,0 +1,136 @@
+//! Logos for well known issuers, picked automatically instead of initials.
+//!
+//! Only the table of issuers is bundled, the logos themselves are downloaded from Simple Icons when
+//! an entry for the issuer is saved, if that's turned on in the settings, and kept in the app's
+//! data directory.
+
+use std::path::PathBuf;
+
+use tracing::info;
+
+pub struct Brand {
+    /// The issuer names that map to this brand, lowercase without punctuation.
+    pub names: &'static [&'static str],
+    /// The brand's Simple Icons slug.
+    pub slug: &'static str,
+}
+
+const fn brand(names: &'static [&'static str], slug: &'static str) -> Brand {
+    Brand { names, slug }
+}
+
+pub const BRANDS: &[Brand] = &[
+    brand(&["1password"], "1password"),
+    brand(&["amazon", "amazonwebservices", "aws"], "amazon"),
+    brand(&["apple", "appleid", "icloud"], "apple"),
+    brand(&["atlassian", "jira", "confluence"], "atlassian"),
+    brand(&["battlenet", "blizzard"], "battledotnet"),
+    brand(&["binance"], "binance"),
+    brand(&["bitbucket"], "bitbucket"),
+    brand(&["bitwarden"], "bitwarden"),
+    brand(&["cloudflare"], "cloudflare"),
+    brand(&["coinbase"], "coinbase"),
+    brand(&["digitalocean"], "digitalocean"),
+    brand(&["discord"], "discord"),
+    brand(&["docker", "dockerhub"], "docker"),
+    brand(&["dropbox"], "dropbox"),
+    brand(&["ea", "electronicarts"], "ea"),
+    brand(&["epicgames", "epic"], "epicgames"),
+    brand(&["facebook", "meta"], "facebook"),
+    brand(&["fastmail"], "fastmail"),
+    brand(&["firefox", "firefoxaccounts", "mozilla"], "firefox"),
+    brand(&["github"], "github"),
+    brand(&["gitlab"], "gitlab"),
+    brand(&["google", "gmail"], "google"),
+    brand(&["hetzner"], "hetzner"),
+    brand(&["instagram"], "instagram"),
+    brand(&["mastodon"], "mastodon"),
+    brand(&["namecheap"], "namecheap"),
+    brand(&["nextcloud"], "nextcloud"),
+    brand(&["npm", "npmjs"], "npm"),
+    brand(&["ovh", "ovhcloud"], "ovh"),
+    brand(&["patreon"], "patreon"),
+    brand(&["paypal"], "paypal"),
+    brand(&["porkbun"], "porkbun"),
+    brand(&["proton", "protonmail", "protonme"], "proton"),
+    brand(&["pypi"], "pypi"),
+    brand(&["reddit"], "reddit"),
+    brand(&["steam", "steampowered"], "steam"),
+    brand(&["stripe"], "stripe"),
+    brand(&["tailscale"], "tailscale"),
+    brand(&["tuta", "tutanota"], "tuta"),
+    brand(&["twitch"], "twitch"),
+    brand(&["twitter", "x"], "x"),
+    brand(&["ubisoft"], "ubisoft"),
+    brand(&["zoho"], "zoho"),
+];
+
+pub fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Finds the brand for an issuer, given either as a name or as a domain.
+pub fn lookup(issuer: &str) -> Option<&'static Brand> {
+    let issuer = issuer.trim().to_lowercase();
+    let domain = issuer.strip_prefix("www.").unwrap_or(&issuer);
+    // "github.com" and "accounts.google.com" should match as well as "GitHub"
+    let host = domain
+        .rsplit_once('.')
+        .map(|(host, _)| host.rsplit('.').next().unwrap_or(host));
+    let candidates = [Some(normalize(&issuer)), host.map(normalize)];
+
+    BRANDS.iter().find(|b| {
+        candidates
+            .iter()
+            .flatten()
+            .any(|c| b.names.contains(&c.as_str()))
+    })
+}
+
+fn icon_path(brand: &Brand) -> Result<PathBuf, String> {
+    Ok(super::icons::data_dir()?
+        .join("brands")
+        .join(format!("{}.svg", brand.slug)))
+}
+
+/// The logo for an issuer from the installed icon packs, or if it's a known brand whose logo has
+/// been downloaded.
+pub fn find(issuer: &str) -> Option<PathBuf> {
+    super::icon_packs::find(issuer).or_else(|| {
+        lookup(issuer)
+            .and_then(|b| icon_path(b).ok())
+            .filter(|p| p.exists())
+    })
+}
+
+/// The brand for an issuer whose logo hasn't been downloaded yet.
+pub fn missing(issuer: &str) -> Option<&'static Brand> {
+    lookup(issuer).filter(|b| icon_path(b).is_ok_and(|p| !p.exists()))
+}
+
+pub async fn download(brand: &'static Brand) -> Result<PathBuf, String> {
+    let url = format!("https://cdn.simpleicons.org/{}", brand.slug);
+    info!("Downloading logo for {} from {url}", brand.slug);
+    let data = reqwest::get(url)
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| format!("Couldn't download logo: {e}"))?
+        .bytes()
+        .await
+        .map_err(|e| format!("Couldn't download logo: {e}"))?;
+
+    let path = icon_path(brand)?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("Couldn't create logo directory: {e}"))?;
+    }
+    tokio::fs::write(&path, data)
+        .await
+        .map_err(|e| format!("Couldn't store logo: {e}"))?;
+
+    Ok(path)
+}
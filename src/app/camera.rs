@@ -0,0 +1,235 @@
+use std::{
+    cell::RefCell,
+    os::fd::OwnedFd,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use pipewire as pw;
+use pw::spa::{
+    self,
+    param::{
+        format::{FormatProperties, MediaSubtype, MediaType},
+        video::{VideoFormat, VideoInfoRaw},
+    },
+    pod::Pod,
+};
+use tracing::{info, warn};
+
+/// How long to keep looking for a QR code before giving up.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(60);
+/// Decoding is far slower than the camera framerate, so only look at a few frames a second.
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Default)]
+struct Frame {
+    format: VideoInfoRaw,
+    last_scan: Option<Instant>,
+}
+
+/// Opens the camera through the desktop portal and decodes frames until a QR code is found.
+///
+/// Returns an empty list if `cancel` was set before anything was found.
+pub async fn scan(cancel: Arc<AtomicBool>) -> Result<Vec<String>, String> {
+    info!("Requesting camera access");
+    let camera = ashpd::desktop::camera::Camera::new()
+        .await
+        .map_err(|e| format!("Couldn't access camera portal: {e}"))?;
+    if !camera
+        .is_present()
+        .await
+        .map_err(|e| format!("Couldn't query cameras: {e}"))?
+    {
+        return Err("No camera available".into());
+    }
+    camera
+        .request_access()
+        .await
+        .and_then(|r| r.response())
+        .map_err(|e| format!("Camera access denied: {e}"))?;
+    let fd = camera
+        .open_pipe_wire_remote()
+        .await
+        .map_err(|e| format!("Couldn't open camera stream: {e}"))?;
+
+    tokio::task::spawn_blocking(move || capture(fd, cancel))
+        .await
+        .map_err(|e| format!("Couldn't join camera thread: {e}"))?
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn capture(fd: OwnedFd, cancel: Arc<AtomicBool>) -> Result<Vec<String>, String> {
+    pw::init();
+
+    let mainloop = pw::main_loop::MainLoop::new(None)
+        .map_err(|e| format!("Couldn't create PipeWire loop: {e}"))?;
+    let context = pw::context::Context::new(&mainloop)
+        .map_err(|e| format!("Couldn't create PipeWire context: {e}"))?;
+    let core = context
+        .connect_fd(fd, None)
+        .map_err(|e| format!("Couldn't connect to PipeWire: {e}"))?;
+    let stream = pw::stream::Stream::new(
+        &core,
+        "cosmic-totp-client-camera",
+        pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Camera",
+        },
+    )
+    .map_err(|e| format!("Couldn't create camera stream: {e}"))?;
+
+    let found = Rc::new(RefCell::new(None));
+    let _listener = stream
+        .add_local_listener_with_user_data(Frame::default())
+        .param_changed(|_, frame, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            if frame.format.parse(param).is_err() {
+                warn!("Couldn't parse camera format");
+            }
+        })
+        .process({
+            let mainloop = mainloop.clone();
+            let found = found.clone();
+            move |stream, frame| {
+                let Some(mut buffer) = stream.dequeue_buffer() else {
+                    return;
+                };
+                if frame.last_scan.is_some_and(|t| t.elapsed() < SCAN_INTERVAL) {
+                    return;
+                }
+                frame.last_scan = Some(Instant::now());
+
+                let Some(data) = buffer.datas_mut().first_mut() else {
+                    return;
+                };
+                let stride = data.chunk().stride();
+                let Some(bytes) = data.data() else {
+                    return;
+                };
+                let Some(image) = to_luma(&frame.format, stride, bytes) else {
+                    return;
+                };
+                if let Ok(codes) = super::qr::decode(&image) {
+                    *found.borrow_mut() = Some(codes);
+                    mainloop.quit();
+                }
+            }
+        })
+        .register()
+        .map_err(|e| format!("Couldn't listen to camera stream: {e}"))?;
+
+    let format = spa::pod::object!(
+        spa::utils::SpaTypes::ObjectParamFormat,
+        spa::param::ParamType::EnumFormat,
+        spa::pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        spa::pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+        spa::pod::property!(
+            FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::YUY2,
+            VideoFormat::YUY2,
+            VideoFormat::GRAY8,
+            VideoFormat::RGBx,
+            VideoFormat::BGRx,
+        ),
+        spa::pod::property!(
+            FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            spa::utils::Rectangle {
+                width: 640,
+                height: 480
+            },
+            spa::utils::Rectangle {
+                width: 1,
+                height: 1
+            },
+            spa::utils::Rectangle {
+                width: 4096,
+                height: 4096
+            }
+        ),
+    );
+    let format = spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(format),
+    )
+    .map_err(|e| format!("Couldn't serialise camera format: {e:?}"))?
+    .0
+    .into_inner();
+    let mut params = [Pod::from_bytes(&format).ok_or("Invalid camera format")?];
+
+    stream
+        .connect(
+            spa::utils::Direction::Input,
+            None,
+            pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )
+        .map_err(|e| format!("Couldn't connect camera stream: {e}"))?;
+
+    let deadline = Instant::now() + SCAN_TIMEOUT;
+    let timed_out = Rc::new(RefCell::new(false));
+    let timer = mainloop.loop_().add_timer({
+        let mainloop = mainloop.clone();
+        let timed_out = timed_out.clone();
+        move |_| {
+            if Instant::now() > deadline {
+                *timed_out.borrow_mut() = true;
+                mainloop.quit();
+            } else if cancel.load(Ordering::Relaxed) {
+                mainloop.quit();
+            }
+        }
+    });
+    timer
+        .update_timer(Some(SCAN_INTERVAL), Some(SCAN_INTERVAL))
+        .into_result()
+        .map_err(|e| format!("Couldn't start camera timer: {e}"))?;
+
+    info!("Scanning camera for QR codes");
+    mainloop.run();
+
+    if let Some(codes) = found.take() {
+        Ok(codes)
+    } else if timed_out.take() {
+        Err("No QR code found in time".into())
+    } else {
+        info!("Camera scan cancelled");
+        Ok(Vec::new())
+    }
+}
+
+/// Converts a raw frame into a greyscale image, approximating luma with the green channel for RGB.
+fn to_luma(format: &VideoInfoRaw, stride: i32, bytes: &[u8]) -> Option<image::GrayImage> {
+    let stride = usize::try_from(stride).ok()?;
+    let (bpp, offset) = match format.format() {
+        VideoFormat::GRAY8 => (1, 0),
+        VideoFormat::YUY2 => (2, 0),
+        VideoFormat::RGBx | VideoFormat::BGRx => (4, 1),
+        _ => return None,
+    };
+    let size = format.size();
+
+    Some(image::GrayImage::from_fn(
+        size.width,
+        size.height,
+        |x, y| {
+            let idx = y as usize * stride + x as usize * bpp + offset;
+            image::Luma([bytes.get(idx).copied().unwrap_or_default()])
+        },
+    ))
+}
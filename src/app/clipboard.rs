@@ -0,0 +1,110 @@
+use std::{borrow::Cow, sync::Mutex, time::Duration};
+
+use cosmic::iced::clipboard::mime::{AllowedMimeTypes, AsMimeTypes};
+
+/// Asks clipboard managers that honour it to keep the contents out of their history.
+const PASSWORD_HINT: &str = "x-kde-passwordManagerHint";
+const TEXT: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+];
+const IMAGES: [&str; 6] = [
+    "image/png",
+    "image/jpeg",
+    "image/webp",
+    "image/gif",
+    "image/bmp",
+    "image/svg+xml",
+];
+
+/// The last sensitive text copied, so clearing doesn't remove anything the user copied since.
+static LAST_SENSITIVE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Text that shouldn't outlive its use, like codes and secrets.
+struct Sensitive(String);
+impl AsMimeTypes for Sensitive {
+    fn available(&self) -> Cow<'static, [String]> {
+        TEXT.iter()
+            .chain([&PASSWORD_HINT])
+            .map(|&mime| mime.to_string())
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn as_bytes(&self, mime_type: &str) -> Option<Cow<'static, [u8]>> {
+        if mime_type == PASSWORD_HINT {
+            Some(Cow::Borrowed(b"secret"))
+        } else if TEXT.contains(&mime_type) {
+            Some(Cow::Owned(self.0.clone().into_bytes()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Copies `text` to the clipboard, marked as sensitive.
+pub fn write_sensitive<Message>(text: String) -> cosmic::Task<Message> {
+    if let Ok(mut last) = LAST_SENSITIVE.lock() {
+        *last = Some(text.clone());
+    }
+    cosmic::iced::clipboard::write_data(Sensitive(text))
+}
+
+/// Empties the clipboard after `delay`, if it still holds the last sensitive text copied.
+pub fn clear_after<Message: Send + 'static>(delay: Duration) -> cosmic::Task<Message> {
+    cosmic::Task::future(tokio::time::sleep(delay))
+        .then(|()| cosmic::iced::clipboard::read())
+        .then(|current| {
+            let last = LAST_SENSITIVE.lock().ok().and_then(|mut l| {
+                if l.is_some() && *l == current {
+                    l.take()
+                } else {
+                    None
+                }
+            });
+            last.map_or_else(cosmic::Task::none, |_| {
+                cosmic::iced::clipboard::write(String::new())
+            })
+        })
+}
+
+/// An image read from the clipboard.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub data: Vec<u8>,
+    pub mime: String,
+}
+impl AllowedMimeTypes for Image {
+    fn allowed() -> Cow<'static, [String]> {
+        IMAGES
+            .iter()
+            .map(|&mime| mime.to_string())
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+impl TryFrom<(Vec<u8>, String)> for Image {
+    type Error = String;
+
+    fn try_from((data, mime): (Vec<u8>, String)) -> Result<Self, Self::Error> {
+        Ok(Self { data, mime })
+    }
+}
+impl Image {
+    /// The file extension for the image's format.
+    pub fn extension(&self) -> Option<&'static str> {
+        if self.mime == "image/svg+xml" {
+            return Some("svg");
+        }
+        image::ImageFormat::from_mime_type(&self.mime)
+            .and_then(|f| f.extensions_str().first().copied())
+    }
+}
+
+/// Reads an image from the clipboard, if there is one.
+pub fn read_image() -> cosmic::Task<Option<Image>> {
+    cosmic::iced::clipboard::read_data::<Image>()
+}
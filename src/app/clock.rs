@@ -0,0 +1,191 @@
+//! The time codes are generated for, and checking the system clock against an NTP server, as
+//! codes from a clock that's off are rejected by the sites they're for.
+//!
+//! A single SNTP request is enough to tell drift of a few seconds apart, which is all that
+//! matters with steps of 30 seconds, so there's no need for a full NTP client.
+
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::{AtomicI64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use cosmic::iced::{
+    Subscription,
+    futures::{StreamExt, stream},
+};
+use tokio::net::UdpSocket;
+
+/// How often the clock is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often the wall clock is compared with the monotonic one.
+const JUMP_CHECK: Duration = Duration::from_secs(1);
+
+/// How far the clocks can disagree before the wall clock is taken to have been changed.
+const JUMP_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// How long to wait for the server to answer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Seconds from the NTP epoch in 1900 to the Unix one.
+const NTP_EPOCH_OFFSET: f64 = 2_208_988_800.0;
+
+/// Seconds added to the system clock, for clocks that can't be corrected.
+static OFFSET: AtomicI64 = AtomicI64::new(0);
+
+pub fn set_offset(seconds: i64) {
+    OFFSET.store(seconds, Ordering::Relaxed);
+}
+
+pub fn offset() -> i64 {
+    OFFSET.load(Ordering::Relaxed)
+}
+
+/// The time since the Unix epoch that codes are generated for, with the offset applied.
+pub fn now() -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    shift(now, offset())
+}
+
+/// Moves a time by `seconds` either way.
+pub fn shift(time: Duration, seconds: i64) -> Duration {
+    let shift = Duration::from_secs(seconds.unsigned_abs());
+    if seconds < 0 {
+        time.saturating_sub(shift)
+    } else {
+        time.saturating_add(shift)
+    }
+}
+
+/// Yields whenever the wall clock is changed, such as by NTP stepping it or by hand, or was
+/// stopped, as the monotonic clock doesn't move while the system sleeps.
+pub fn jumps() -> Subscription<()> {
+    Subscription::run_with_id(
+        "clock-jumps",
+        stream::unfold(
+            (Instant::now(), SystemTime::now()),
+            |(mut monotonic, mut wall)| async move {
+                loop {
+                    tokio::time::sleep(JUMP_CHECK).await;
+                    let (monotonic_now, wall_now) = (Instant::now(), SystemTime::now());
+                    let expected = wall + monotonic_now.duration_since(monotonic);
+                    let disagreement = wall_now
+                        .duration_since(expected)
+                        .unwrap_or_else(|e| e.duration());
+                    (monotonic, wall) = (monotonic_now, wall_now);
+                    if disagreement > JUMP_THRESHOLD {
+                        return Some(((), (monotonic, wall)));
+                    }
+                }
+            },
+        ),
+    )
+}
+
+/// Checks the clock against `server` now and then, giving how many seconds it's behind.
+pub fn subscription(server: String) -> Subscription<Result<f64, String>> {
+    Subscription::run_with_id(
+        ("clock", server.clone()),
+        stream::unfold(true, |first| async move {
+            if !first {
+                tokio::time::sleep(CHECK_INTERVAL).await;
+            }
+            Some(((), false))
+        })
+        .then(move |()| offset(server.clone())),
+    )
+}
+
+/// How many seconds the system clock is behind the server's, negative if it's ahead.
+pub async fn offset(server: String) -> Result<f64, String> {
+    tokio::time::timeout(TIMEOUT, query(&server))
+        .await
+        .map_err(|_| format!("{server} didn't answer"))?
+}
+
+async fn query(server: &str) -> Result<f64, String> {
+    let address = tokio::net::lookup_host((server, 123))
+        .await
+        .map_err(|e| format!("Couldn't look up {server}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("{server} has no addresses"))?;
+    // The socket has to be of the same family as the server's address
+    let local: SocketAddr = if address.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local)
+        .await
+        .map_err(|e| format!("Couldn't open a socket: {e}"))?;
+    socket
+        .connect(address)
+        .await
+        .map_err(|e| format!("Couldn't reach {server}: {e}"))?;
+
+    // A client request of version 3, with only the transmit timestamp set for the server to echo
+    let mut request = [0; 48];
+    request[0] = 0x1b;
+    let sent = system_time();
+    request[40..48].copy_from_slice(&ntp_timestamp(sent));
+    socket
+        .send(&request)
+        .await
+        .map_err(|e| format!("Couldn't send to {server}: {e}"))?;
+    let mut response = [0; 48];
+    let len = socket
+        .recv(&mut response)
+        .await
+        .map_err(|e| format!("Couldn't hear from {server}: {e}"))?;
+    let received = system_time();
+    if len < response.len() {
+        return Err(format!("{server} sent a short answer"));
+    }
+    // Anything but a server's answer to this very request could be stale or forged
+    if response[0] & 0x07 != 4 || response[24..32] != request[40..48] {
+        return Err(format!("{server} sent an unexpected answer"));
+    }
+    // A stratum of 0 is a kiss-o'-death, telling the client to back off
+    if response[1] == 0 {
+        return Err(format!("{server} refused to answer"));
+    }
+
+    let (Some(server_received), Some(server_sent)) =
+        (timestamp(&response[32..40]), timestamp(&response[40..48]))
+    else {
+        return Err(format!("{server} didn't send its time"));
+    };
+    // The round trip is assumed to take as long both ways
+    Ok(((server_received - sent) + (server_sent - received)) / 2.0)
+}
+
+/// An NTP timestamp as Unix seconds, `None` if it was left empty.
+fn timestamp(bytes: &[u8]) -> Option<f64> {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    (seconds != 0 || fraction != 0)
+        .then(|| f64::from(seconds) - NTP_EPOCH_OFFSET + f64::from(fraction) / f64::from(u32::MAX))
+}
+
+/// Unix seconds as an NTP timestamp.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn ntp_timestamp(unix: f64) -> [u8; 8] {
+    let ntp = unix + NTP_EPOCH_OFFSET;
+    let seconds = ntp as u32;
+    let fraction = (ntp.fract() * f64::from(u32::MAX)) as u32;
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+    bytes
+}
+
+/// The system clock as Unix seconds, without the offset as it's the clock being checked.
+fn system_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
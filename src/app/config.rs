@@ -0,0 +1,176 @@
+//! The applet's settings, stored with cosmic-config.
+
+use cosmic::cosmic_config::{
+    self, ConfigGet, CosmicConfigEntry, cosmic_config_derive::CosmicConfigEntry,
+};
+use tracing::{error, info};
+
+use super::{SortMode, entry::DisplayOptions, lock::LockKind, secrets::Storage, shortcuts};
+
+#[derive(Debug, Clone, PartialEq, CosmicConfigEntry)]
+#[version = 1]
+pub struct Config {
+    pub sort_mode: SortMode,
+    /// The preferred trigger for the global shortcut that opens the popup.
+    pub popup_shortcut: String,
+    /// The preferred trigger for the global shortcut that copies the quick copy entry's code.
+    pub copy_shortcut: String,
+    /// Copy the code as soon as the search leaves a single entry, without waiting for Enter.
+    pub auto_copy_search: bool,
+    /// Close the popup once a code has been copied.
+    pub close_on_copy: bool,
+    /// Seconds until a copied code is cleared from the clipboard, 0 to leave it.
+    pub clear_clipboard_after: u64,
+    /// Download the logos of well known issuers from Simple Icons when an entry is saved.
+    pub download_logos: bool,
+    pub display: DisplayOptions,
+    /// The parameters new entries start with.
+    pub default_algorithm: totp_rs::Algorithm,
+    pub default_digits: usize,
+    pub default_step: u64,
+    /// The argon2 hash of the passphrase or PIN the popup is locked with, if any.
+    pub lock_hash: Option<String>,
+    pub lock_kind: LockKind,
+    /// Wrong passphrases or PINs in a row, kept so restarting the app doesn't reset the backoff.
+    pub lock_failures: u32,
+    /// When another unlock attempt is allowed in Unix seconds, 0 if one already is.
+    pub lock_retry_at: u64,
+    /// Seconds without using the popup until the app lock locks again, 0 to stay unlocked.
+    pub auto_lock_seconds: u64,
+    /// Where the vault is kept.
+    pub storage: Storage,
+    /// The service name the vault is stored under, the app id when empty.
+    pub keyring_service: String,
+    /// The Secret Service collection the vault is stored in, the default one when empty.
+    pub keyring_collection: String,
+    /// The users whose vaults have been opened, to switch between in the popup.
+    pub known_users: Vec<String>,
+    /// The NTP server the clock is checked against, empty to not check it.
+    pub ntp_server: String,
+    /// Seconds the clock can be off before the popup warns about it.
+    pub clock_warning_seconds: u64,
+    /// Seconds added to the clock when generating codes, for clocks that can't be corrected.
+    pub time_offset: i64,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sort_mode: SortMode::default(),
+            popup_shortcut: shortcuts::DEFAULT_OPEN_POPUP_TRIGGER.into(),
+            copy_shortcut: shortcuts::DEFAULT_COPY_CODE_TRIGGER.into(),
+            auto_copy_search: false,
+            close_on_copy: false,
+            clear_clipboard_after: 0,
+            download_logos: false,
+            display: DisplayOptions::default(),
+            default_algorithm: totp_rs::Algorithm::SHA1,
+            default_digits: 6,
+            default_step: 30,
+            lock_hash: None,
+            lock_kind: LockKind::default(),
+            lock_failures: 0,
+            lock_retry_at: 0,
+            auto_lock_seconds: 300,
+            storage: Storage::default(),
+            keyring_service: String::new(),
+            keyring_collection: String::new(),
+            known_users: Vec::new(),
+            ntp_server: "pool.ntp.org".into(),
+            clock_warning_seconds: 5,
+            time_offset: 0,
+        }
+    }
+}
+impl Config {
+    /// Reads the settings, falling back to the defaults for any that are missing or invalid.
+    pub fn load(handler: &cosmic_config::Config) -> Self {
+        match Self::get_entry(handler) {
+            Ok(config) => config,
+            // Settings from before this struct were stored under their own keys
+            Err((_, mut config)) if handler.get::<SortMode>("sort_mode").is_err() => {
+                config.migrate(handler);
+                if let Err(e) = config.write_entry(handler) {
+                    error!("Couldn't save migrated settings: {e}");
+                }
+                config
+            }
+            Err((errors, config)) => {
+                for e in errors {
+                    info!("Using default for setting: {e}");
+                }
+                config
+            }
+        }
+    }
+
+    /// Sets the app lock's kind and hash in one transaction, so a hash is never saved with the
+    /// kind of the lock it replaced.
+    pub fn set_lock(
+        &mut self,
+        handler: &cosmic_config::Config,
+        kind: LockKind,
+        hash: String,
+    ) -> Result<(), cosmic_config::Error> {
+        let mut config = self.clone();
+        config.lock_kind = kind;
+        config.lock_hash = Some(hash);
+        config.write_entry(handler)?;
+        *self = config;
+        Ok(())
+    }
+
+    /// Records the wrong unlock attempts in a row and when the next is allowed in one transaction.
+    pub fn set_lock_attempts(
+        &mut self,
+        handler: &cosmic_config::Config,
+        failures: u32,
+        retry_at: u64,
+    ) -> Result<(), cosmic_config::Error> {
+        let mut config = self.clone();
+        config.lock_failures = failures;
+        config.lock_retry_at = retry_at;
+        config.write_entry(handler)?;
+        *self = config;
+        Ok(())
+    }
+
+    fn migrate(&mut self, handler: &cosmic_config::Config) {
+        info!("Migrating settings");
+        if let Ok(mode) = handler.get("sort-mode") {
+            self.sort_mode = mode;
+        }
+        if let Ok(trigger) = handler.get("popup-shortcut") {
+            self.popup_shortcut = trigger;
+        }
+        if let Ok(trigger) = handler.get("copy-shortcut") {
+            self.copy_shortcut = trigger;
+        }
+        if let Ok(auto_copy) = handler.get("auto-copy-search") {
+            self.auto_copy_search = auto_copy;
+        }
+        if let Ok(close) = handler.get("close-on-copy") {
+            self.close_on_copy = close;
+        }
+        if let Ok(download) = handler.get("download-logos") {
+            self.download_logos = download;
+        }
+        if let Ok(show) = handler.get("show-next-code") {
+            self.display.show_next = show;
+        }
+        if let Ok(group) = handler.get("group-digits") {
+            self.display.group_digits = group;
+        }
+        if let Ok(bar) = handler.get("progress-bar") {
+            self.display.progress_bar = bar;
+        }
+        if let Ok(reduce) = handler.get("reduce-motion") {
+            self.display.reduce_motion = reduce;
+        }
+        if let Ok(seconds) = handler.get("warning-below") {
+            self.display.warning_below = seconds;
+        }
+        if let Ok(seconds) = handler.get("critical-below") {
+            self.display.critical_below = seconds;
+        }
+    }
+}
@@ -1,61 +1,359 @@
-use std::{path::PathBuf, sync::OnceLock, time::Duration};
+use std::{
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use base64::{Engine, engine::general_purpose::STANDARD_NO_PAD as BASE64};
+use chrono::{DateTime, Local, Utc};
 use cosmic::{
     Apply,
-    iced::{Alignment, Length, Subscription, font::Weight, futures::StreamExt, widget},
-    iced_widget::stack,
+    iced::{
+        Alignment, Length, Subscription,
+        font::Weight,
+        widget::{
+            self,
+            text::{LineHeight, Wrapping},
+        },
+    },
     widget::{
         button, canvas, column, container, row,
         text::{self},
     },
 };
-use tokio::time::{Instant, interval_at};
-use tracing::info;
+use md5::{Digest, Md5};
+use tokio::time::Instant;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Copy, Hash)]
 pub enum EntryR {
     NewEntry,
+    /// The copy of an entry being edited.
+    Draft,
+    /// The throwaway entry of the quick generator.
+    Scratch,
     Index(u32),
+    Import(u32),
+    #[cfg(feature = "keepass")]
+    KeePass(u32),
 }
 
 #[derive(Debug, Clone)]
 pub enum EntryMessage {
     GetIconFile,
     SetIconFile(PathBuf),
+    /// Uses an icon from the icon theme, or the initials again if empty.
+    SetIconName(String),
+    /// Uses an emoji, or the initials again if empty.
+    SetIconEmoji(String),
+    /// Sets the background of the initials, `None` for the default.
+    SetIconColor(Option<[u8; 3]>),
+    IconUrlInput(String),
+    /// Downloads the icon at the entered URL.
+    FetchIcon,
+    /// An icon was stored in the app's data directory.
+    StoredIcon(Result<PathBuf, String>),
+    /// Uses the image on the clipboard.
+    PasteIcon,
+    PastedIcon(Option<super::clipboard::Image>),
+    /// Opens the crop editor for an image file.
+    CropIconFile(PathBuf),
+    CropLoaded(Result<super::icons::Crop, String>),
+    CropZoom(f32),
+    CropX(f32),
+    CropY(f32),
+    CropApply,
+    CropCancel,
+    /// The issuer's logo finished downloading.
+    BrandIcon(Result<PathBuf, String>),
     NameEdit(String),
     Algorithm(totp_rs::Algorithm),
     Digits(usize),
     Step(u64),
     Skew(u8),
     Secret(String),
+    /// Shows or masks the secret in the editor.
+    ShowSecret(bool),
+    Encoding(SecretEncoding),
+    MicrosoftPadding(bool),
     CancelledIconFile,
     Issuer(Option<String>),
+    Group(Option<String>),
+    Kind(EntryKind),
+    Counter(u64),
+    Pin(String),
+    NextCode,
     Stepped(cosmic::iced::time::Instant, u64),
-    Animate(cosmic::iced::time::Instant),
-    Noop,
     CopyOutput,
+    /// Copies the code for the step after the current one.
+    CopyNextOutput,
+    /// Shows the masked code for a while.
+    Reveal(Duration),
+    /// Masks the code again, if its reveal has run out.
+    Conceal,
+    CopyUri,
+    ToggleQr,
+    /// Seconds added to the time for this entry's codes.
+    TimeOffset(i64),
+    CalibrateInput(String),
+    /// Sets the time offset from a code the server accepts.
+    Calibrate,
+    RecoveryInput(String),
+    RecoveryAdd,
+    RecoveryUse(usize),
+    RecoveryRemove(usize),
+    ToggleFavorite,
+    QuickCopy(bool),
+    Sensitive(bool),
+    Preset(usize),
+    Archive(bool),
+    /// The thumbnail of an image icon was created, with the path of the image it's for.
+    Thumbnail(PathBuf, Result<PathBuf, String>),
+}
+
+/// How codes are derived from the secret.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum EntryKind {
+    #[default]
+    Totp,
+    /// Counter based codes, where `counter` is the value of the currently shown code.
+    Hotp { counter: u64 },
+    /// Mobile-OTP, an MD5 of the time step, the hex secret and the PIN.
+    Motp { pin: String },
+}
+
+/// A setting of an entry that can be invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Secret,
+    Pin,
+    Digits,
+    Skew,
+    Step,
+}
+
+/// How the secret text is turned into the raw key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SecretEncoding {
+    #[default]
+    Base32,
+    Hex,
+    /// The text itself is the key.
+    Raw,
+    Base64,
+}
+impl SecretEncoding {
+    const ALL: [Self; 4] = [Self::Base32, Self::Hex, Self::Raw, Self::Base64];
+    const LABELS: [&str; 4] = ["Base32", "Hex", "Raw", "Base64"];
+
+    fn decode(self, secret: &str, microsoft_padding: bool) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Base32 => {
+                let mut secret = secret.to_string();
+                if microsoft_padding {
+                    secret.push_str("000000");
+                }
+                totp_rs::Secret::Encoded(secret)
+                    .to_bytes()
+                    .map_err(|e| e.to_string())
+            }
+            Self::Hex => hex::decode(secret.trim()).map_err(|e| e.to_string()),
+            Self::Raw => Ok(secret.as_bytes().to_vec()),
+            Self::Base64 => BASE64
+                .decode(secret.trim().trim_end_matches('='))
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// How codes are shown in the main list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DisplayOptions {
+    /// Show the code for the following step under the current one.
+    pub show_next: bool,
+    /// Split codes into groups of three or four characters, as most providers show them.
+    pub group_digits: bool,
+    /// Show the time left as a thin bar under the entry instead of a ring beside it.
+    pub progress_bar: bool,
+    /// Only move the countdown once a second, on top of the system wide setting.
+    pub reduce_motion: bool,
+    /// Seconds left at which the countdown turns to the warning color, 0 to never.
+    pub warning_below: u64,
+    /// Seconds left at which the countdown turns to the destructive color, 0 to never.
+    pub critical_below: u64,
+    /// Mask codes until their entry is clicked.
+    pub hide_codes: bool,
+    /// How long a clicked code stays revealed.
+    pub reveal_seconds: u64,
+    /// Seconds without input before shown codes are masked again, 0 to never.
+    pub idle_hide_seconds: u64,
+    /// Hide every code and name while the screen is being shared, codes can still be copied.
+    pub screen_share: bool,
+}
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            show_next: false,
+            group_digits: false,
+            progress_bar: false,
+            reduce_motion: false,
+            warning_below: 10,
+            critical_below: 5,
+            hide_codes: false,
+            reveal_seconds: 10,
+            idle_hide_seconds: 0,
+            screen_share: false,
+        }
+    }
+}
+impl DisplayOptions {
+    fn urgency(self, remaining: u64) -> Urgency {
+        if remaining < self.critical_below {
+            Urgency::Critical
+        } else if remaining < self.warning_below {
+            Urgency::Warning
+        } else {
+            Urgency::Normal
+        }
+    }
+
+    /// The name as it should be shown.
+    fn name(self, name: &str) -> String {
+        if self.screen_share {
+            "\u{2022}".repeat(name.chars().count())
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// A placeholder the length of the code, always grouped so it reads as a code.
+    fn mask(self, code: &str) -> String {
+        Self {
+            group_digits: true,
+            ..self
+        }
+        .format(&"\u{2022}".repeat(code.chars().count()))
+    }
+
+    /// The code as it should be shown, copying always uses the raw code.
+    fn format(self, code: &str) -> String {
+        let chars: Vec<char> = code.chars().collect();
+        if !self.group_digits || chars.len() <= 4 {
+            return code.to_string();
+        }
+        let size = if chars.len() % 3 == 0 {
+            3
+        } else if chars.len() % 4 == 0 {
+            4
+        } else {
+            // Odd lengths like Steam's five characters are split into two uneven halves
+            let (first, second) = chars.split_at(chars.len() / 2);
+            return format!(
+                "{} {}",
+                first.iter().collect::<String>(),
+                second.iter().collect::<String>()
+            );
+        };
+        chars
+            .chunks(size)
+            .map(|c| c.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A one-time recovery code, kept in the vault alongside the entry it belongs to.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RecoveryCode {
+    pub code: String,
+    pub used: bool,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Entry {
+    /// Identifies the entry's own item in the keyring.
+    #[serde(default = "random_id")]
+    pub id: String,
     pub icon: TotpIcon,
+    /// The background of the initials icon, as RGB.
+    #[serde(default)]
+    pub icon_color: Option<[u8; 3]>,
     pub totp: totp_rs::TOTP,
     pub secret: String,
+    #[serde(default)]
+    pub encoding: SecretEncoding,
+    /// Pads the 10 character secrets handed out by some Microsoft accounts to a valid length.
+    #[serde(default)]
+    pub microsoft_padding: bool,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub kind: EntryKind,
+    /// Favorites are shown above the other entries.
+    #[serde(default)]
+    pub favorite: bool,
+    /// Archived entries stay in the vault but are hidden and generate no codes.
+    #[serde(default)]
+    pub archived: bool,
+    /// Copied by the global shortcut without opening the popup, set on at most one entry.
+    #[serde(default)]
+    pub quick_copy: bool,
+    /// Always masked, and only copied once revealed, whatever the display settings.
+    #[serde(default)]
+    pub sensitive: bool,
+    #[serde(default)]
+    pub recovery_codes: Vec<RecoveryCode>,
+    /// Seconds added to the time for codes, for servers whose own clock is off.
+    #[serde(default)]
+    pub time_offset: i64,
+    /// Unknown for entries saved before timestamps were tracked.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub modified_at: Option<DateTime<Utc>>,
+    /// How many times the code has been copied.
+    #[serde(default)]
+    pub copy_count: u64,
+    #[serde(default)]
+    pub last_used: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub recovery_input: String,
+    /// A code the server accepts, to calibrate the time offset with.
+    #[serde(skip)]
+    pub calibrate_input: String,
+    #[serde(skip)]
+    pub icon_url: String,
+    /// Whether the secret is shown in the editor, where it's masked otherwise.
+    #[serde(skip)]
+    pub show_secret: bool,
+    /// The image being framed before it's used as the icon.
+    #[serde(skip)]
+    pub icon_crop: Option<super::icons::Crop>,
+    /// Set while the icon at `icon_url` is downloading.
+    #[serde(skip)]
+    pub fetching_icon: bool,
     #[serde(skip)]
     pub output: String,
     #[serde(skip)]
-    pub percentage: f32,
+    pub next_output: String,
+    /// When the code goes back to being masked, if it's been revealed.
+    #[serde(skip)]
+    pub revealed_until: Option<std::time::Instant>,
     #[serde(skip, default = "std::time::Instant::now")]
     pub last_output: std::time::Instant,
-    #[serde(skip, default = "std::time::Instant::now")]
-    pub current_output: std::time::Instant,
+    #[serde(skip)]
+    pub qr: Option<Arc<widget::qr_code::Data>>,
 }
 impl Entry {
     pub fn new() -> Self {
         Self {
+            id: random_id(),
             icon: TotpIcon::Initials {
                 initials: "-".into(),
             },
+            icon_color: None,
             totp: totp_rs::TOTP {
                 algorithm: totp_rs::Algorithm::SHA1,
                 digits: 6,
@@ -66,14 +364,272 @@ impl Entry {
                 issuer: None,
             },
             secret: String::new(),
+            encoding: SecretEncoding::Base32,
+            microsoft_padding: false,
+            group: None,
+            kind: EntryKind::Totp,
+            favorite: false,
+            archived: false,
+            quick_copy: false,
+            sensitive: false,
+            recovery_codes: Vec::new(),
+            time_offset: 0,
+            recovery_input: String::new(),
+            calibrate_input: String::new(),
+            icon_url: String::new(),
+            show_secret: false,
+            icon_crop: None,
+            fetching_icon: false,
+            created_at: Some(Utc::now()),
+            modified_at: Some(Utc::now()),
+            copy_count: 0,
+            last_used: None,
             output: String::new(),
-            percentage: 0.0,
+            next_output: String::new(),
+            revealed_until: None,
             last_output: std::time::Instant::now(),
-            current_output: std::time::Instant::now(),
+            qr: None,
         }
     }
 
+    pub fn from_totp(totp: totp_rs::TOTP) -> Self {
+        let mut entry = Self::new();
+        entry.secret = totp.get_secret_base32();
+        entry.totp = totp;
+        entry.recalc_icon();
+
+        entry
+    }
+
+    pub fn from_uri(uri: &str) -> Result<Self, String> {
+        if let Some(rest) = uri.strip_prefix("otpauth://motp/") {
+            let mut entry = Self::from_uri(&format!("otpauth://totp/{rest}"))?;
+            entry.kind = EntryKind::Motp { pin: String::new() };
+            entry.secret = hex::encode(&entry.totp.secret);
+            return Ok(entry);
+        }
+        if let Some(rest) = uri.strip_prefix("otpauth://hotp/") {
+            let url = url::Url::parse(uri).map_err(|e| format!("Invalid otpauth URI: {e}"))?;
+            let counter = url
+                .query_pairs()
+                .find(|(k, _)| k == "counter")
+                .map(|(_, v)| v.parse())
+                .transpose()
+                .map_err(|e| format!("Invalid HOTP counter: {e}"))?
+                .unwrap_or_default();
+
+            let mut entry = Self::from_uri(&format!("otpauth://totp/{rest}"))?;
+            entry.kind = EntryKind::Hotp { counter };
+            entry.output = entry.generate(0);
+            return Ok(entry);
+        }
+
+        totp_rs::TOTP::from_url_unchecked(uri)
+            .map(Self::from_totp)
+            .map_err(|e| format!("Invalid otpauth URI: {e}"))
+    }
+
+    /// The otpauth URI for this entry, including the counter for HOTP entries.
+    ///
+    /// mOTP URIs follow Aegis and leave out the PIN.
+    pub fn uri(&self) -> String {
+        match &self.kind {
+            EntryKind::Totp => self.totp.get_url(),
+            EntryKind::Hotp { counter } => {
+                let url = self
+                    .totp
+                    .get_url()
+                    .replacen("otpauth://totp/", "otpauth://hotp/", 1);
+                format!("{url}&counter={counter}")
+            }
+            EntryKind::Motp { .. } => {
+                self.totp
+                    .get_url()
+                    .replacen("otpauth://totp/", "otpauth://motp/", 1)
+            }
+        }
+    }
+
+    /// Finds `code` within `steps` steps of the one for `time`, returning how many steps away it
+    /// is. HOTP entries search around their counter instead.
+    pub fn find_code(&self, code: &str, time: u64, steps: i64) -> Option<i64> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        let step = i64::try_from(self.totp.step.max(1)).ok()?;
+        let generate = |offset: i64| match &self.kind {
+            EntryKind::Hotp { counter } => Some(
+                totp_rs::TOTP {
+                    step: 1,
+                    ..self.totp.clone()
+                }
+                .generate(counter.checked_add_signed(offset)?),
+            ),
+            _ => Some(self.generate(time.checked_add_signed(offset.checked_mul(step)?)?)),
+        };
+        // Nearest first, as short codes can repeat further out
+        std::iter::once(0)
+            .chain((1..=steps).flat_map(|n| [-n, n]))
+            .find(|&offset| generate(offset).is_some_and(|c| c == code))
+    }
+
+    /// Generates the code for `time`, which HOTP entries ignore in favour of their counter.
+    pub fn generate(&self, time: u64) -> String {
+        // Edits aren't refused, so parameters that would overflow or divide by zero can get here
+        if !self.can_generate() {
+            return String::new();
+        }
+        let time = time.saturating_add_signed(self.time_offset);
+        match &self.kind {
+            EntryKind::Totp => self.totp.generate(time),
+            EntryKind::Hotp { counter } => totp_rs::TOTP {
+                step: 1,
+                ..self.totp.clone()
+            }
+            .generate(*counter),
+            EntryKind::Motp { pin } => {
+                let input = format!(
+                    "{}{}{pin}",
+                    time / self.totp.step.max(1),
+                    hex::encode(&self.totp.secret)
+                );
+                let mut code = hex::encode(Md5::digest(input));
+                code.truncate(self.totp.digits);
+                code
+            }
+        }
+    }
+
+    /// Whether the digits and step can be used to generate a code, even if it's a wrong one.
+    fn can_generate(&self) -> bool {
+        match self.kind {
+            EntryKind::Totp if self.totp.algorithm == totp_rs::Algorithm::Steam => {
+                self.totp.step > 0
+            }
+            EntryKind::Totp => self.totp.step > 0 && (1..=9).contains(&self.totp.digits),
+            EntryKind::Hotp { .. } => (1..=9).contains(&self.totp.digits),
+            EntryKind::Motp { .. } => true,
+        }
+    }
+
+    /// Whether both entries are for the same account with the same secret.
+    pub fn is_duplicate_of(&self, other: &Self) -> bool {
+        self.totp.issuer == other.totp.issuer
+            && self.totp.account_name == other.totp.account_name
+            && self.totp.secret == other.totp.secret
+    }
+
+    /// Whether the issuer, account or group contains the lowercase `query`.
+    pub fn matches(&self, query: &str) -> bool {
+        self.totp
+            .issuer
+            .iter()
+            .chain([&self.totp.account_name])
+            .chain(&self.group)
+            .any(|s| s.to_lowercase().contains(query))
+    }
+
+    /// A short description of how recently the code was used.
+    pub fn usage_summary(&self) -> String {
+        self.last_used.map_or_else(
+            || "Never used".into(),
+            |t| {
+                format!(
+                    "Copied {} times, last on {}",
+                    self.copy_count,
+                    format_time(t)
+                )
+            },
+        )
+    }
+
+    /// A copy of the entry's settings for another account, without its history or recovery codes.
+    pub fn duplicate(&self) -> Self {
+        let mut entry = Self::new();
+        entry.icon = self.icon.clone();
+        entry.icon_color = self.icon_color;
+        entry.totp = self.totp.clone();
+        entry.totp.account_name.push_str(" (copy)");
+        entry.secret = self.secret.clone();
+        entry.encoding = self.encoding;
+        entry.microsoft_padding = self.microsoft_padding;
+        entry.group = self.group.clone();
+        entry.kind = self.kind.clone();
+        entry.sensitive = self.sensitive;
+        entry.output = self.output.clone();
+        entry.next_output = self.next_output.clone();
+        entry
+    }
+
+    /// Whether anything that's saved differs from another entry, ignoring what's only shown.
+    pub fn differs_from(&self, other: &Self) -> bool {
+        serde_json::to_value(self).ok() != serde_json::to_value(other).ok()
+    }
+
+    /// Fills in the details this entry is missing from a duplicate of it.
+    pub fn merge(&mut self, other: Self) {
+        if matches!(self.icon, TotpIcon::Initials { .. })
+            && !matches!(other.icon, TotpIcon::Initials { .. })
+        {
+            self.icon = other.icon;
+        }
+        if self.icon_color.is_none() {
+            self.icon_color = other.icon_color;
+        }
+        if self.group.is_none() {
+            self.group = other.group;
+        }
+        if self.recovery_codes.is_empty() {
+            self.recovery_codes = other.recovery_codes;
+        }
+        self.favorite |= other.favorite;
+    }
+
     pub fn update(&mut self, message: EntryMessage) -> Result<cosmic::Task<EntryMessage>, String> {
+        // Any change to the parameters invalidates the displayed QR code. Problems with them are
+        // shown by their fields rather than refusing the change, so they can be typed in steps.
+        let changes_params = matches!(
+            message,
+            EntryMessage::NameEdit(_)
+                | EntryMessage::Algorithm(_)
+                | EntryMessage::Digits(_)
+                | EntryMessage::Step(_)
+                | EntryMessage::Skew(_)
+                | EntryMessage::Secret(_)
+                | EntryMessage::Encoding(_)
+                | EntryMessage::MicrosoftPadding(_)
+                | EntryMessage::Issuer(_)
+                | EntryMessage::Kind(_)
+                | EntryMessage::Preset(_)
+                | EntryMessage::Counter(_)
+                | EntryMessage::Pin(_)
+                | EntryMessage::NextCode
+        );
+        if changes_params {
+            self.qr = None;
+        }
+        let raster = self.icon.raster().map(|(path, _)| path.clone());
+        if changes_params
+            || matches!(
+                message,
+                EntryMessage::SetIconFile(_)
+                    | EntryMessage::SetIconName(_)
+                    | EntryMessage::SetIconEmoji(_)
+                    | EntryMessage::SetIconColor(_)
+                    | EntryMessage::StoredIcon(Ok(_))
+                    | EntryMessage::Group(_)
+                    | EntryMessage::ToggleFavorite
+                    | EntryMessage::QuickCopy(_)
+                    | EntryMessage::Sensitive(_)
+                    | EntryMessage::Archive(_)
+                    | EntryMessage::RecoveryAdd
+                    | EntryMessage::RecoveryUse(_)
+                    | EntryMessage::RecoveryRemove(_)
+                    | EntryMessage::TimeOffset(_)
+                    | EntryMessage::Calibrate
+            )
+        {
+            self.modified_at = Some(Utc::now());
+        }
+
         match message {
             EntryMessage::GetIconFile => {
                 return Ok(cosmic::Task::perform(
@@ -82,7 +638,7 @@ impl Entry {
                         .pick_file(),
                     move |s| {
                         s.map_or(EntryMessage::CancelledIconFile, |s| {
-                            EntryMessage::SetIconFile(s.path().to_path_buf())
+                            EntryMessage::CropIconFile(s.path().to_path_buf())
                         })
                     },
                 ));
@@ -93,6 +649,99 @@ impl Entry {
                     handle: OnceLock::new(),
                 };
             }
+            // Vector icons scale to any size, so only raster images are cropped
+            EntryMessage::CropIconFile(path) if path.extension().is_some_and(|e| e == "svg") => {
+                return Ok(cosmic::Task::done(EntryMessage::SetIconFile(path)));
+            }
+            EntryMessage::CropIconFile(path) => {
+                return Ok(cosmic::Task::perform(
+                    super::icons::Crop::load(path),
+                    EntryMessage::CropLoaded,
+                ));
+            }
+            EntryMessage::PasteIcon => {
+                return Ok(super::clipboard::read_image().map(EntryMessage::PastedIcon));
+            }
+            EntryMessage::PastedIcon(image) => {
+                let image = image.ok_or("There's no image on the clipboard")?;
+                let extension = image
+                    .extension()
+                    .ok_or("The image on the clipboard isn't a supported format")?;
+                return Ok(cosmic::Task::done(EntryMessage::StoredIcon(
+                    super::icons::store(&image.data, extension),
+                )));
+            }
+            EntryMessage::CropLoaded(crop) => self.icon_crop = Some(crop?),
+            EntryMessage::CropZoom(zoom) => {
+                if let Some(crop) = &mut self.icon_crop {
+                    crop.zoom = zoom;
+                    crop.update_preview();
+                }
+            }
+            EntryMessage::CropX(x) => {
+                if let Some(crop) = &mut self.icon_crop {
+                    crop.x = x;
+                    crop.update_preview();
+                }
+            }
+            EntryMessage::CropY(y) => {
+                if let Some(crop) = &mut self.icon_crop {
+                    crop.y = y;
+                    crop.update_preview();
+                }
+            }
+            EntryMessage::CropApply => {
+                if let Some(crop) = self.icon_crop.take() {
+                    return Ok(cosmic::Task::perform(
+                        crop.store(),
+                        EntryMessage::StoredIcon,
+                    ));
+                }
+            }
+            EntryMessage::CropCancel => self.icon_crop = None,
+            EntryMessage::SetIconName(name) => {
+                if name.trim().is_empty() {
+                    self.icon = TotpIcon::Initials {
+                        initials: String::new(),
+                    };
+                    self.recalc_icon();
+                } else {
+                    self.icon = TotpIcon::Named { name };
+                }
+            }
+            EntryMessage::SetIconEmoji(emoji) => {
+                if emoji.trim().is_empty() {
+                    self.icon = TotpIcon::Initials {
+                        initials: String::new(),
+                    };
+                    self.recalc_icon();
+                } else {
+                    self.icon = TotpIcon::Emoji {
+                        emoji: emoji.trim().to_string(),
+                    };
+                }
+            }
+            EntryMessage::SetIconColor(color) => self.icon_color = color,
+            EntryMessage::IconUrlInput(url) => self.icon_url = url,
+            EntryMessage::BrandIcon(result) => match result {
+                Ok(_) => self.recalc_icon(),
+                Err(e) => warn!("{e}"),
+            },
+            EntryMessage::FetchIcon => {
+                self.fetching_icon = true;
+                return Ok(cosmic::Task::perform(
+                    super::icons::download(self.icon_url.clone()),
+                    EntryMessage::StoredIcon,
+                ));
+            }
+            EntryMessage::StoredIcon(result) => {
+                self.fetching_icon = false;
+                self.icon = TotpIcon::Image {
+                    path: result?,
+                    handle: OnceLock::new(),
+                };
+                self.icon_url.clear();
+            }
             EntryMessage::NameEdit(s) => {
                 self.totp.account_name = s;
                 self.recalc_icon();
@@ -103,7 +752,14 @@ impl Entry {
             EntryMessage::Skew(s) => self.totp.skew = s,
             EntryMessage::Secret(s) => {
                 self.secret = s;
-                // FIXME: Add this validation to all the necessary steps, and display it properly.
+                self.recalc_secret()?;
+            }
+            EntryMessage::Encoding(encoding) => {
+                self.encoding = encoding;
+                self.recalc_secret()?;
+            }
+            EntryMessage::MicrosoftPadding(pad) => {
+                self.microsoft_padding = pad;
                 self.recalc_secret()?;
             }
             EntryMessage::CancelledIconFile => info!("User cancelled icon file set"),
@@ -111,88 +767,739 @@ impl Entry {
                 self.totp.issuer = s;
                 self.recalc_icon();
             }
+            EntryMessage::Group(s) => self.group = s,
+            EntryMessage::Kind(kind) => {
+                // Decoded first, so a secret that doesn't suit the kind leaves the entry as it was
+                let secret = self
+                    .decode_secret_as(&kind)
+                    .map_err(|e| format!("Invalid secret: {e}"))?;
+                let was_motp = matches!(self.kind, EntryKind::Motp { .. });
+                self.kind = kind;
+                self.totp.secret = secret;
+                // mOTP uses 10 second steps and hex secrets, and can have longer codes
+                if let EntryKind::Motp { .. } = self.kind {
+                    self.totp.step = 10;
+                    self.totp.digits = 6;
+                } else if was_motp {
+                    self.totp.step = 30;
+                    self.totp.digits = self.totp.digits.min(9);
+                }
+                self.output = self.generate(0);
+            }
+            EntryMessage::Pin(pin) => self.kind = EntryKind::Motp { pin },
+            EntryMessage::Counter(counter) => {
+                self.kind = EntryKind::Hotp { counter };
+                self.output = self.generate(0);
+            }
+            EntryMessage::NextCode => {
+                if let EntryKind::Hotp { counter } = &mut self.kind {
+                    *counter = counter.saturating_add(1);
+                }
+                self.output = self.generate(0);
+            }
             EntryMessage::Stepped(instant, time) => {
-                self.output = self.totp.generate(time);
+                self.output = self.generate(time);
+                self.next_output = self.generate(time + self.totp.step);
                 self.last_output = instant;
-                self.percentage = 0.0;
-                self.current_output = instant;
-            }
-            #[allow(clippy::cast_precision_loss)]
-            EntryMessage::Animate(instant) => {
-                self.current_output = instant;
-                self.percentage = self
-                    .current_output
-                    .duration_since(self.last_output)
-                    .as_secs_f32()
-                    / self.totp.step as f32;
-            }
-            EntryMessage::Noop => {}
+            }
             EntryMessage::CopyOutput => {
-                return Ok(cosmic::iced::clipboard::write(self.output.clone()));
+                self.copy_count += 1;
+                self.last_used = Some(Utc::now());
+                return Ok(super::clipboard::write_sensitive(self.output.clone()));
+            }
+            EntryMessage::CopyNextOutput => {
+                let time = super::clock::now().as_secs() + self.totp.step;
+                self.copy_count += 1;
+                self.last_used = Some(Utc::now());
+                return Ok(super::clipboard::write_sensitive(self.generate(time)));
+            }
+            EntryMessage::Reveal(duration) => {
+                self.revealed_until = Some(std::time::Instant::now() + duration);
+                return Ok(cosmic::Task::perform(tokio::time::sleep(duration), |()| {
+                    EntryMessage::Conceal
+                }));
+            }
+            EntryMessage::ShowSecret(show) => self.show_secret = show,
+            EntryMessage::Conceal => {
+                if self
+                    .revealed_until
+                    .is_some_and(|t| t <= std::time::Instant::now())
+                {
+                    self.revealed_until = None;
+                }
+            }
+            EntryMessage::CopyUri => {
+                return Ok(super::clipboard::write_sensitive(self.uri()));
+            }
+            EntryMessage::ToggleQr => {
+                self.qr = if self.qr.is_some() {
+                    None
+                } else {
+                    Some(Arc::new(
+                        widget::qr_code::Data::new(self.uri())
+                            .map_err(|e| format!("Couldn't create QR code: {e}"))?,
+                    ))
+                };
+            }
+            EntryMessage::ToggleFavorite => self.favorite = !self.favorite,
+            EntryMessage::QuickCopy(quick_copy) => self.quick_copy = quick_copy,
+            EntryMessage::Sensitive(sensitive) => {
+                self.sensitive = sensitive;
+                // The shortcut would copy the code without it being revealed
+                self.quick_copy &= !sensitive;
+            }
+            EntryMessage::Preset(idx) => {
+                if let Some(preset) = super::presets::PRESETS.get(idx) {
+                    self.totp.issuer = Some(preset.issuer.to_string());
+                    self.totp.algorithm = preset.algorithm;
+                    self.totp.digits = preset.digits;
+                    self.totp.step = preset.step;
+                    self.kind = EntryKind::Totp;
+                    self.recalc_icon();
+                }
+            }
+            EntryMessage::Archive(archived) => self.archived = archived,
+            EntryMessage::TimeOffset(seconds) => self.time_offset = seconds,
+            EntryMessage::CalibrateInput(code) => self.calibrate_input = code,
+            EntryMessage::Calibrate => {
+                let step = i64::try_from(self.totp.step.max(1)).unwrap_or(i64::MAX);
+                let offset = self
+                    .find_code(
+                        &self.calibrate_input,
+                        super::clock::now().as_secs(),
+                        CALIBRATE_SECONDS / step,
+                    )
+                    .ok_or("The code doesn't match any within an hour either way")?;
+                self.time_offset = self.time_offset.saturating_add(offset.saturating_mul(step));
+                self.calibrate_input.clear();
+            }
+            EntryMessage::RecoveryInput(s) => self.recovery_input = s,
+            EntryMessage::RecoveryAdd => {
+                // Codes are usually handed out as a block, so accept a whole paste at once
+                let input = std::mem::take(&mut self.recovery_input);
+                self.recovery_codes
+                    .extend(input.split_whitespace().map(|code| RecoveryCode {
+                        code: code.to_string(),
+                        used: false,
+                    }));
+            }
+            EntryMessage::RecoveryUse(idx) => {
+                if let Some(code) = self.recovery_codes.get_mut(idx) {
+                    code.used = true;
+                    return Ok(super::clipboard::write_sensitive(code.code.clone()));
+                }
+            }
+            EntryMessage::RecoveryRemove(idx) => {
+                if idx < self.recovery_codes.len() {
+                    self.recovery_codes.remove(idx);
+                }
+            }
+            EntryMessage::Thumbnail(source, result) => {
+                if let Some((path, handle)) = self.icon.raster()
+                    && *path == source
+                {
+                    let thumbnail = result.unwrap_or_else(|e| {
+                        warn!("Couldn't create icon thumbnail: {e}");
+                        source
+                    });
+                    _ = handle.set(widget::image::Handle::from_path(thumbnail));
+                }
             }
         }
 
+        if self.icon.raster().map(|(path, _)| path) != raster.as_ref() {
+            return Ok(self.load_thumbnail());
+        }
         Ok(cosmic::Task::none())
     }
 
+    /// Creates the thumbnail of an image icon in the background, unless it's been loaded.
+    pub fn load_thumbnail(&self) -> cosmic::Task<EntryMessage> {
+        match self.icon.raster() {
+            Some((path, handle)) if handle.get().is_none() => {
+                let path = path.clone();
+                cosmic::Task::perform(super::icons::thumbnail(path.clone()), move |r| {
+                    EntryMessage::Thumbnail(path, r)
+                })
+            }
+            _ => cosmic::Task::none(),
+        }
+    }
+
+    /// The background of the initials icon when none was chosen, derived from the issuer or name.
+    fn default_icon_color(&self) -> [u8; 3] {
+        super::icons::initials_color(
+            self.totp
+                .issuer
+                .as_deref()
+                .unwrap_or(&self.totp.account_name),
+        )
+    }
+
+    pub fn view_icon(&self, radius: f32) -> cosmic::Element<std::convert::Infallible> {
+        let [r, g, b] = self.icon_color.unwrap_or_else(|| self.default_icon_color());
+        self.icon
+            .view(radius, Some(cosmic::iced::Color::from_rgb8(r, g, b)))
+    }
+
+    /// Picks the icon from the issuer or name, unless the user chose one.
     pub fn recalc_icon(&mut self) {
-        if matches!(self.icon, TotpIcon::Initials { .. }) {
-            self.icon = TotpIcon::default_for_name(
-                self.totp
-                    .issuer
-                    .as_deref()
-                    .unwrap_or_else(|| &self.totp.account_name),
-            );
+        if matches!(
+            self.icon,
+            TotpIcon::Initials { .. } | TotpIcon::Brand { .. }
+        ) {
+            self.icon = match self.totp.issuer.as_deref().and_then(super::brands::find) {
+                // Keeps the loaded thumbnail
+                Some(path) if matches!(&self.icon, TotpIcon::Brand { path: p, .. } if *p == path) =>
+                {
+                    return;
+                }
+                Some(path) => TotpIcon::Brand {
+                    path,
+                    handle: OnceLock::new(),
+                },
+                None => TotpIcon::default_for_name(
+                    self.totp
+                        .issuer
+                        .as_deref()
+                        .unwrap_or_else(|| &self.totp.account_name),
+                ),
+            };
         }
     }
 
-    pub fn recalc_secret(&mut self) -> Result<(), String> {
-        let mut secret = self.secret.clone();
-        // Special case the microsoft authenticator 10-length secrets
-        if secret.len() == 10 {
-            secret.push_str("000000");
+    /// Downloads the issuer's logo if it's a known brand that isn't stored yet.
+    pub fn fetch_brand_icon(&self) -> cosmic::Task<EntryMessage> {
+        if !matches!(self.icon, TotpIcon::Initials { .. }) {
+            return cosmic::Task::none();
         }
-        let raw = totp_rs::Secret::Encoded(secret)
-            .to_bytes()
+        self.totp
+            .issuer
+            .as_deref()
+            .and_then(super::brands::missing)
+            .map_or_else(cosmic::Task::none, |brand| {
+                cosmic::Task::perform(super::brands::download(brand), EntryMessage::BrandIcon)
+            })
+    }
+
+    pub fn recalc_secret(&mut self) -> Result<(), String> {
+        self.totp.secret = self
+            .decode_secret()
             .map_err(|e| format!("Invalid secret: {e}"))?;
-        self.totp.secret = raw;
 
         Ok(())
     }
 
-    pub fn view_settings(&self, new: bool) -> cosmic::Element<EntryMessage> {
+    fn decode_secret(&self) -> Result<Vec<u8>, String> {
+        self.decode_secret_as(&self.kind)
+    }
+
+    /// Decodes the secret the way an entry of `kind` would.
+    fn decode_secret_as(&self, kind: &EntryKind) -> Result<Vec<u8>, String> {
+        if let EntryKind::Motp { .. } = kind {
+            return hex::decode(self.secret.trim()).map_err(|e| e.to_string());
+        }
+
+        self.encoding.decode(
+            &self.secret,
+            self.microsoft_padding && self.secret.len() == 10,
+        )
+    }
+
+    /// Checks that the entry is valid and has everything needed to generate codes.
+    pub fn check_savable(&self) -> Result<(), String> {
+        if self.totp.account_name.trim().is_empty() {
+            return Err("The entry needs a name".into());
+        }
+        if self.secret.trim().is_empty() {
+            return Err("The entry needs a secret".into());
+        }
+
+        self.validate()
+    }
+
+    /// Checks that the parameters work together, listing every problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|(_, problem)| problem.as_str())
+            .collect();
+        Err(format!("Invalid entry: {}", problems.join(", ")))
+    }
+
+    /// The problems with the parameters, with the field each one is about.
+    pub fn problems(&self) -> Vec<(Field, String)> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = self.decode_secret() {
+            problems.push((Field::Secret, format!("the secret is invalid ({e})")));
+        }
+        match &self.kind {
+            EntryKind::Totp if self.totp.algorithm == totp_rs::Algorithm::Steam => {
+                if self.totp.digits != 5 {
+                    problems.push((Field::Digits, "Steam codes must have 5 digits".into()));
+                }
+            }
+            EntryKind::Totp | EntryKind::Hotp { .. } => {
+                // Larger codes overflow the truncated HMAC
+                if !(1..=9).contains(&self.totp.digits) {
+                    problems.push((
+                        Field::Digits,
+                        "codes must have between 1 and 9 digits".into(),
+                    ));
+                }
+            }
+            EntryKind::Motp { pin } => {
+                if !(1..=32).contains(&self.totp.digits) {
+                    problems.push((
+                        Field::Digits,
+                        "mOTP codes must have between 1 and 32 digits".into(),
+                    ));
+                }
+                if pin.is_empty() {
+                    problems.push((Field::Pin, "mOTP needs a PIN".into()));
+                }
+            }
+        }
+        if !matches!(self.kind, EntryKind::Hotp { .. }) && self.totp.step == 0 {
+            problems.push((Field::Step, "the step must be at least 1 second".into()));
+        }
+        if u64::from(self.totp.skew).saturating_mul(self.totp.step) > 3600 {
+            problems.push((Field::Skew, "the skew covers more than an hour".into()));
+        }
+
+        problems
+    }
+
+    pub fn view_settings(
+        &self,
+        new: bool,
+        display: DisplayOptions,
+    ) -> cosmic::Element<EntryMessage> {
         use cosmic::widget::{button, container, dropdown, settings, text, text_input};
 
-        let icon_setting = button::custom(self.icon.view(20.0).map(|s| match s {}))
+        let problems = self.problems();
+
+        let icon_setting = button::custom(self.view_icon(20.0).map(|s| match s {}))
             .on_press(EntryMessage::GetIconFile);
         // settings::item_row(Vec::new())
         let home_row = settings::item_row(Vec::new())
             .push(icon_setting)
             .push(text_input("Name", &self.totp.account_name).on_input(EntryMessage::NameEdit));
+        let preset = new.then(|| {
+            settings::item(
+                "Preset",
+                dropdown(
+                    &super::presets::PRESETS,
+                    super::presets::PRESETS
+                        .iter()
+                        .position(|p| self.totp.issuer.as_deref() == Some(p.issuer)),
+                    EntryMessage::Preset,
+                ),
+            )
+        });
         let issuer = settings::item(
             "Issuer",
             text_input("None", self.totp.issuer.as_deref().unwrap_or_default())
                 .on_input(|s| EntryMessage::Issuer((!s.is_empty()).then_some(s))),
         );
-        let secret = settings::item(
-            "Secret",
-            text_input("XXXXXXXX", &self.secret).on_input(EntryMessage::Secret),
+        let group = settings::item(
+            "Group",
+            text_input("None", self.group.as_deref().unwrap_or_default())
+                .on_input(|s| EntryMessage::Group((!s.is_empty()).then_some(s))),
+        );
+        let basic = settings::section().add(home_row);
+        let basic = match preset {
+            Some(preset) => basic.add(preset),
+            None => basic,
+        }
+        .add(issuer);
+        let basic = self
+            .add_secret_settings(basic, &problems)
+            .add(group)
+            .add(settings::item(
+                "Sensitive",
+                cosmic::widget::toggler(self.sensitive).on_toggle(EntryMessage::Sensitive),
+            ))
+            .add(settings::item(
+                "Copy with global shortcut",
+                cosmic::widget::toggler(self.quick_copy)
+                    .on_toggle_maybe((!self.sensitive).then_some(EntryMessage::QuickCopy)),
+            ));
+        let advanced = self.add_param_settings(settings::section().title("Advanced"), &problems);
+        let export = (!new).then(|| {
+            settings::section()
+                .title("Export")
+                .add(settings::item(
+                    "otpauth URI",
+                    button::standard("Copy").on_press(EntryMessage::CopyUri),
+                ))
+                .add(settings::item(
+                    "QR code",
+                    button::standard(if self.qr.is_some() { "Hide" } else { "Show" })
+                        .on_press(EntryMessage::ToggleQr),
+                ))
+        });
+        let qr = self
+            .qr
+            .as_deref()
+            .map(|data| container(widget::qr_code(data)).center_x(Length::Fill));
+        let details = (!new).then(|| {
+            settings::section()
+                .title("Details")
+                .add(settings::item(
+                    "Created",
+                    text::body(
+                        self.created_at
+                            .map_or_else(|| "Unknown".into(), format_time),
+                    ),
+                ))
+                .add(settings::item(
+                    "Modified",
+                    text::body(
+                        self.modified_at
+                            .map_or_else(|| "Unknown".into(), format_time),
+                    ),
+                ))
+                .add(settings::item(
+                    "Copied",
+                    text::body(format!("{} times", self.copy_count)),
+                ))
+                .add(settings::item(
+                    "Last used",
+                    text::body(self.last_used.map_or_else(|| "Never".into(), format_time)),
+                ))
+        });
+        let icon_name = match &self.icon {
+            TotpIcon::Named { name } => name.as_str(),
+            _ => "",
+        };
+        let theme_icons = cosmic::widget::flex_row(
+            super::icons::THEME_ICONS
+                .iter()
+                .map(|&name| {
+                    button::icon(cosmic::widget::icon::from_name(name))
+                        .selected(name == icon_name)
+                        .on_press(EntryMessage::SetIconName(name.into()))
+                        .into()
+                })
+                .collect(),
+        );
+        let icon_emoji = match &self.icon {
+            TotpIcon::Emoji { emoji } => emoji.as_str(),
+            _ => "",
+        };
+        let emojis = cosmic::widget::flex_row(
+            super::icons::EMOJIS
+                .iter()
+                .map(|&emoji| {
+                    button::custom(text::title4(emoji).center())
+                        .width(Length::Fixed(36.0))
+                        .height(Length::Fixed(36.0))
+                        .class(if emoji == icon_emoji {
+                            cosmic::theme::Button::Suggested
+                        } else {
+                            cosmic::theme::Button::Icon
+                        })
+                        .on_press(EntryMessage::SetIconEmoji(emoji.into()))
+                        .into()
+                })
+                .collect(),
+        );
+        let swatch = |color: Option<[u8; 3]>| {
+            let [r, g, b] = color.unwrap_or_else(|| self.default_icon_color());
+            let fill = cosmic::iced::Color::from_rgb8(r, g, b);
+            button::custom(
+                container(cosmic::widget::Space::new(
+                    Length::Fixed(20.0),
+                    Length::Fixed(20.0),
+                ))
+                .style(move |t: &cosmic::Theme| container::Style {
+                    background: Some(cosmic::iced::Background::Color(fill)),
+                    border: cosmic::iced::Border {
+                        color: t.cosmic().secondary.divider.into(),
+                        width: 1.0,
+                        radius: 10.0.into(),
+                    },
+                    ..Default::default()
+                }),
+            )
+            .padding(4)
+            .class(if self.icon_color == color {
+                cosmic::theme::Button::Suggested
+            } else {
+                cosmic::theme::Button::Icon
+            })
+            .on_press(EntryMessage::SetIconColor(color))
+            .into()
+        };
+        let colors = matches!(self.icon, TotpIcon::Initials { .. }).then(|| {
+            cosmic::widget::flex_row(
+                std::iter::once(swatch(None))
+                    .chain(super::icons::INITIALS_COLORS.map(|c| swatch(Some(c))))
+                    .collect(),
+            )
+        });
+        let icon = settings::section().title("Icon").add(settings::item(
+            "Image",
+            row()
+                .push(button::standard("Paste").on_press(EntryMessage::PasteIcon))
+                .push(button::standard("Choose File").on_press(EntryMessage::GetIconFile))
+                .spacing(5),
+        ));
+        let icon = match &self.icon_crop {
+            Some(crop) => icon.add(view_crop(crop)),
+            None => icon,
+        };
+        let icon = icon
+            .add(settings::item_row(vec![
+                text_input("Image URL", &self.icon_url)
+                    .on_input(EntryMessage::IconUrlInput)
+                    .on_submit(|_| EntryMessage::FetchIcon)
+                    .into(),
+                button::standard(if self.fetching_icon {
+                    "Downloading"
+                } else {
+                    "Download"
+                })
+                .on_press_maybe(
+                    (!self.fetching_icon && !self.icon_url.trim().is_empty())
+                        .then_some(EntryMessage::FetchIcon),
+                )
+                .into(),
+            ]))
+            .add(settings::item(
+                "Theme icon",
+                text_input("None", icon_name).on_input(EntryMessage::SetIconName),
+            ))
+            .add(theme_icons)
+            .add(settings::item(
+                "Emoji",
+                text_input("None", icon_emoji).on_input(EntryMessage::SetIconEmoji),
+            ))
+            .add(emojis)
+            .add(settings::item(
+                "Initials",
+                button::standard("Use Initials").on_press_maybe(
+                    (!matches!(self.icon, TotpIcon::Initials { .. }))
+                        .then(|| EntryMessage::SetIconName(String::new())),
+                ),
+            ));
+        let icon = match colors {
+            Some(colors) => icon.add(settings::item("Background", colors)),
+            None => icon,
+        };
+
+        let mut recovery = settings::section().title("Recovery Codes");
+        for (idx, code) in self.recovery_codes.iter().enumerate() {
+            let label = if code.used {
+                text::monotext(&code.code).class(cosmic::theme::Text::Default)
+            } else {
+                text::monotext(&code.code).class(cosmic::theme::Text::Accent)
+            };
+            recovery = recovery.add(settings::item_row(vec![
+                label.into(),
+                cosmic::widget::horizontal_space().into(),
+                button::standard(if code.used { "Used" } else { "Use" })
+                    .on_press_maybe((!code.used).then_some(EntryMessage::RecoveryUse(idx)))
+                    .into(),
+                button::icon(cosmic::widget::icon::from_name("edit-delete-symbolic"))
+                    .on_press(EntryMessage::RecoveryRemove(idx))
+                    .into(),
+            ]));
+        }
+        let recovery = recovery.add(settings::item_row(vec![
+            text_input("Paste recovery codes", &self.recovery_input)
+                .on_input(EntryMessage::RecoveryInput)
+                .on_submit(|_| EntryMessage::RecoveryAdd)
+                .into(),
+            button::standard("Add")
+                .on_press_maybe(
+                    (!self.recovery_input.trim().is_empty()).then_some(EntryMessage::RecoveryAdd),
+                )
+                .into(),
+        ]));
+
+        let col = settings::view_column(Vec::new())
+            // .spacing(5)
+            .push(if new {
+                text::title1("New Entry")
+            } else {
+                text::title1("Edit Entry")
+            })
+            .push(basic)
+            .push(icon)
+            .push(advanced)
+            .push(recovery)
+            .push_maybe(export)
+            .push_maybe(details)
+            .push_maybe(qr)
+            .push(self.view_preview(&problems, display));
+
+        container(col).into()
+    }
+
+    /// Only the settings codes are generated from, for trying a secret out without saving it.
+    pub fn view_scratch(&self, display: DisplayOptions) -> cosmic::Element<EntryMessage> {
+        use cosmic::widget::{settings, text};
+
+        let problems = self.problems();
+        settings::view_column(Vec::new())
+            .push(text::title1("Quick Generate"))
+            .push(text::caption(
+                "Try a secret out before adding it, nothing here is saved unless you add it as an \
+                 entry.",
+            ))
+            .push(self.add_secret_settings(settings::section(), &problems))
+            .push(self.add_param_settings(settings::section().title("Parameters"), &problems))
+            .push(self.view_preview(&problems, display))
+            .into()
+    }
+
+    /// Adds the secret and how it's read to a section.
+    fn add_secret_settings<'a>(
+        &'a self,
+        section: cosmic::widget::settings::Section<'a, EntryMessage>,
+        problems: &[(Field, String)],
+    ) -> cosmic::widget::settings::Section<'a, EntryMessage> {
+        use cosmic::widget::{button, dropdown, settings, text_input};
+
+        let secret_input = text_input("XXXXXXXX", &self.secret).on_input(EntryMessage::Secret);
+        let secret_input = if self.show_secret {
+            secret_input
+        } else {
+            secret_input.password()
+        };
+        let secret = checked(
+            problems,
+            Field::Secret,
+            settings::item(
+                "Secret",
+                row()
+                    .push(secret_input)
+                    .push(
+                        button::icon(cosmic::widget::icon::from_name(if self.show_secret {
+                            "view-conceal-symbolic"
+                        } else {
+                            "view-reveal-symbolic"
+                        }))
+                        .on_press(EntryMessage::ShowSecret(!self.show_secret)),
+                    )
+                    .spacing(4)
+                    .align_y(Alignment::Center),
+            ),
+        );
+        let encoding = settings::item(
+            "Encoding",
+            dropdown(
+                &SecretEncoding::LABELS,
+                SecretEncoding::ALL.iter().position(|e| *e == self.encoding),
+                |e| EntryMessage::Encoding(SecretEncoding::ALL[e]),
+            ),
+        );
+        let section = section.add(secret);
+        // mOTP secrets are always hex
+        if let EntryKind::Motp { pin } = &self.kind {
+            section.add(checked(
+                problems,
+                Field::Pin,
+                settings::item("PIN", text_input("0000", pin).on_input(EntryMessage::Pin)),
+            ))
+        } else if self.encoding == SecretEncoding::Base32 && self.secret.len() == 10 {
+            // Only offered for the secret length Microsoft hands out
+            section.add(encoding).add(settings::item(
+                "Microsoft-compatible padding",
+                cosmic::widget::toggler(self.microsoft_padding)
+                    .on_toggle(EntryMessage::MicrosoftPadding),
+            ))
+        } else {
+            section.add(encoding)
+        }
+    }
+
+    /// Adds the time offset and its calibration to a section, for the entries that use the time.
+    fn add_offset_settings<'a>(
+        &'a self,
+        section: cosmic::widget::settings::Section<'a, EntryMessage>,
+    ) -> cosmic::widget::settings::Section<'a, EntryMessage> {
+        use cosmic::widget::{button, settings, text_input};
+
+        if let EntryKind::Hotp { .. } = self.kind {
+            return section;
+        }
+        section
+            .add(settings::item(
+                "Time offset in seconds",
+                cosmic::widget::spin_button(
+                    self.time_offset.to_string(),
+                    self.time_offset,
+                    1,
+                    -CALIBRATE_SECONDS,
+                    CALIBRATE_SECONDS,
+                    EntryMessage::TimeOffset,
+                ),
+            ))
+            .add(settings::item_row(vec![
+                text_input("Code the server accepts", &self.calibrate_input)
+                    .on_input(EntryMessage::CalibrateInput)
+                    .on_submit(|_| EntryMessage::Calibrate)
+                    .into(),
+                button::standard("Calibrate")
+                    .on_press_maybe(
+                        (!self.calibrate_input.trim().is_empty())
+                            .then_some(EntryMessage::Calibrate),
+                    )
+                    .into(),
+            ]))
+    }
+
+    /// Adds the parameters codes are generated with to a section.
+    fn add_param_settings<'a>(
+        &'a self,
+        section: cosmic::widget::settings::Section<'a, EntryMessage>,
+        problems: &[(Field, String)],
+    ) -> cosmic::widget::settings::Section<'a, EntryMessage> {
+        use cosmic::widget::{dropdown, settings};
+
+        let kind = settings::item(
+            "Type",
+            dropdown(
+                &["TOTP", "HOTP", "mOTP"],
+                match self.kind {
+                    EntryKind::Totp => Some(0),
+                    EntryKind::Hotp { .. } => Some(1),
+                    EntryKind::Motp { .. } => Some(2),
+                },
+                |s| {
+                    EntryMessage::Kind(match s {
+                        0 => EntryKind::Totp,
+                        1 => EntryKind::Hotp { counter: 0 },
+                        2 => EntryKind::Motp { pin: String::new() },
+                        _ => unreachable!(),
+                    })
+                },
+            ),
         );
-        let basic = settings::section().add(home_row).add(issuer).add(secret);
         let algorithm = settings::item::item(
             "Algorithm",
             dropdown(
-                &["SHA1", "SHA256", "SHA512"],
+                &["SHA1", "SHA256", "SHA512", "Steam"],
                 match self.totp.algorithm {
                     totp_rs::Algorithm::SHA1 => Some(0),
                     totp_rs::Algorithm::SHA256 => Some(1),
                     totp_rs::Algorithm::SHA512 => Some(2),
+                    totp_rs::Algorithm::Steam => Some(3),
                 },
                 |s| {
                     EntryMessage::Algorithm(match s {
                         0 => totp_rs::Algorithm::SHA1,
                         1 => totp_rs::Algorithm::SHA256,
                         2 => totp_rs::Algorithm::SHA512,
+                        3 => totp_rs::Algorithm::Steam,
                         _ => unreachable!(),
                     })
                 },
@@ -205,8 +1512,13 @@ impl Entry {
                 self.totp.digits.to_string(),
                 self.totp.digits,
                 1,
-                0,
-                16,
+                1,
+                // mOTP codes are a hex digest rather than a truncated number
+                if matches!(self.kind, EntryKind::Motp { .. }) {
+                    32
+                } else {
+                    9
+                },
                 EntryMessage::Digits,
             ),
         );
@@ -221,41 +1533,127 @@ impl Entry {
                 EntryMessage::Skew,
             ),
         );
-        let step = settings::item(
-            "Step",
-            cosmic::widget::spin_button(
-                self.totp.step.to_string(),
-                self.totp.step,
-                1,
-                0,
-                3600,
-                EntryMessage::Step,
+        let step = match self.kind {
+            EntryKind::Totp | EntryKind::Motp { .. } => settings::item(
+                "Step",
+                cosmic::widget::spin_button(
+                    self.totp.step.to_string(),
+                    self.totp.step,
+                    1,
+                    1,
+                    3600,
+                    EntryMessage::Step,
+                ),
             ),
-        );
-        let advanced = settings::section()
-            .title("Advanced")
-            .add(algorithm)
-            .add(digits)
-            .add(skew)
-            .add(step);
+            EntryKind::Hotp { counter } => settings::item(
+                "Counter",
+                cosmic::widget::spin_button(
+                    counter.to_string(),
+                    counter,
+                    1,
+                    0,
+                    u64::MAX,
+                    EntryMessage::Counter,
+                ),
+            ),
+        };
+        let section = section.add(kind);
+        // mOTP is always MD5
+        let section = if let EntryKind::Motp { .. } = self.kind {
+            section
+        } else {
+            section.add(algorithm)
+        };
+        section
+            .add(checked(problems, Field::Digits, digits))
+            .add(checked(problems, Field::Skew, skew))
+            .add(checked(problems, Field::Step, step))
+            .apply(|section| self.add_offset_settings(section))
+    }
 
-        let col = settings::view_column(Vec::new())
-            // .spacing(5)
-            .push(if new {
-                text::title1("New Entry")
-            } else {
-                text::title1("Edit Entry")
+    /// The code the settings currently generate, to check against the provider before saving.
+    fn view_preview(
+        &self,
+        problems: &[(Field, String)],
+        display: DisplayOptions,
+    ) -> cosmic::Element<EntryMessage> {
+        use cosmic::widget::{horizontal_space, settings};
+
+        let preview = settings::section().title("Preview");
+        if self.secret.trim().is_empty() {
+            return preview
+                .add(text::caption("Enter the secret to preview its codes"))
+                .into();
+        }
+        if !problems.is_empty() {
+            return preview
+                .add(text::caption(
+                    "Fix the settings marked above to preview codes",
+                ))
+                .into();
+        }
+        let code = self.generate(super::clock::now().as_secs());
+        let code = text::title3(if display.screen_share {
+            display.mask(&code)
+        } else {
+            display.format(&code)
+        })
+        .font(cosmic::font::mono());
+        // HOTP codes don't expire, so there's nothing to count down
+        let ttk = (!matches!(self.kind, EntryKind::Hotp { .. })).then(|| {
+            let ttk: cosmic::Element<'static, ()> = canvas(Ttk {
+                started: self.last_output,
+                step: self.totp.step,
+                display,
             })
-            .push(basic)
-            .push(advanced);
+            .width(30.0)
+            .height(30.0)
+            .into();
+            ttk.map(|()| unreachable!())
+        });
+        preview
+            .add(
+                row()
+                    .push(code)
+                    .push(horizontal_space())
+                    .push_maybe(ttk)
+                    .align_y(Alignment::Center),
+            )
+            .into()
+    }
 
-        container(col).into()
+    /// Whether the code is hidden behind a placeholder.
+    pub fn is_masked(&self, display: DisplayOptions) -> bool {
+        (display.hide_codes || self.sensitive)
+            && !self
+                .revealed_until
+                .is_some_and(|t| t > std::time::Instant::now())
     }
 
-    pub fn view<const SHOW_CODES: bool>(&self) -> cosmic::Element<EntryMessage> {
+    /// The height of the entry in the main list, matching the layout of [`Self::view`].
+    pub fn row_height(&self, display: DisplayOptions) -> f32 {
+        // HOTP codes have neither a next code nor a countdown
+        let hotp = matches!(self.kind, EntryKind::Hotp { .. });
+        let mut height = NAME_LINE + CODE_LINE + 2.0 * ROW_PADDING;
+        if display.show_next && !hotp {
+            height += NEXT_LINE;
+        }
+        if display.progress_bar && !hotp {
+            height += BAR_SPACING + BAR_HEIGHT;
+        }
+        height
+    }
+
+    pub fn view<const SHOW_CODES: bool>(
+        &self,
+        display: DisplayOptions,
+    ) -> cosmic::Element<EntryMessage> {
+        let masked = self.is_masked(display);
+        // Revealing a code while sharing the screen only lets it be copied
+        let hidden = masked || display.screen_share;
         let name = row()
             .push_maybe(self.totp.issuer.as_ref().map(|s| {
-                container(text::text(s))
+                container(name_text(display.name(s)))
                     .padding([0.0, 5.0])
                     .style(|t| container::Style {
                         icon_color: None,
@@ -276,128 +1674,318 @@ impl Entry {
                         },
                     })
             }))
-            .push(text::text(&self.totp.account_name))
+            .push(name_text(display.name(&self.totp.account_name)))
             .spacing(4);
         let code = if SHOW_CODES {
             Some(
-                cosmic::widget::text(&self.output)
-                    .class(cosmic::theme::Text::Accent)
-                    .font(cosmic::font::mono().apply(|mut s| {
-                        s.weight = Weight::Bold;
-                        s
-                    }))
-                    .size(30),
+                cosmic::widget::text(if hidden {
+                    display.mask(&self.output)
+                } else {
+                    display.format(&self.output)
+                })
+                .class(cosmic::theme::Text::Accent)
+                .font(cosmic::font::mono().apply(|mut s| {
+                    s.weight = Weight::Bold;
+                    s
+                }))
+                .size(CODE_SIZE)
+                .line_height(LineHeight::Absolute(CODE_LINE.into())),
             )
         } else {
             None
         };
-        let content = column().push(name).push_maybe(code);
-        let ttk = if SHOW_CODES {
+        // HOTP codes only change on request, so there's no next code to show ahead of time
+        let next_code = (SHOW_CODES
+            && display.show_next
+            && !matches!(self.kind, EntryKind::Hotp { .. }))
+        .then(|| {
+            text::caption(format!(
+                "Next: {}",
+                if hidden {
+                    display.mask(&self.next_output)
+                } else {
+                    display.format(&self.next_output)
+                }
+            ))
+            .line_height(LineHeight::Absolute(NEXT_LINE.into()))
+        });
+        let content = column().push(name).push_maybe(code).push_maybe(next_code);
+        let (ttk, bar): (Option<cosmic::Element<EntryMessage>>, _) = if !SHOW_CODES {
+            (None, None)
+        } else if let EntryKind::Hotp { .. } = self.kind {
+            (
+                Some(
+                    button::icon(cosmic::widget::icon::from_name("go-next-symbolic"))
+                        .on_press(EntryMessage::NextCode)
+                        .into(),
+                ),
+                None,
+            )
+        } else {
             let ttk: cosmic::Element<'static, ()> = canvas(Ttk {
-                percentage: 1.0 - self.percentage,
-                thickness: 4.0,
+                started: self.last_output,
+                step: self.totp.step,
+                display,
+            })
+            .apply(|ttk| {
+                if display.progress_bar {
+                    ttk.width(Length::Fill).height(BAR_HEIGHT)
+                } else {
+                    ttk.width(30.0).height(30.0)
+                }
             })
-            .width(30.0)
-            .height(30.0)
             .into();
-            let ttk = stack([
-                ttk.map(|()| unreachable!()),
-                container(text::monotext(
-                    (self
-                        .totp
-                        .step
-                        .checked_sub(
-                            self.current_output
-                                .duration_since(self.last_output)
-                                .as_secs(),
-                        )
-                        .unwrap_or_default())
-                    .to_string(),
-                ))
-                .center(Length::Fill)
-                .into(),
-            ]);
-            Some(ttk)
-        } else {
-            None
+            let ttk = ttk.map(|()| unreachable!());
+            if display.progress_bar {
+                (None, Some(ttk))
+            } else {
+                (Some(ttk), None)
+            }
         };
 
+        let favorite = SHOW_CODES.then(|| {
+            button::icon(cosmic::widget::icon::from_name(if self.favorite {
+                "starred-symbolic"
+            } else {
+                "non-starred-symbolic"
+            }))
+            .on_press(EntryMessage::ToggleFavorite)
+        });
+
+        // HOTP codes don't expire, so there's no need to get ahead of them
+        // Masked codes are only copied once they've been revealed
+        let copy_next = (SHOW_CODES && !masked && !matches!(self.kind, EntryKind::Hotp { .. }))
+            .then(|| {
+                cosmic::widget::tooltip(
+                    button::icon(cosmic::widget::icon::from_name(
+                        "media-skip-forward-symbolic",
+                    ))
+                    .on_press(EntryMessage::CopyNextOutput),
+                    text::text("Copy next code"),
+                    cosmic::widget::tooltip::Position::Bottom,
+                )
+            });
+
+        // Logos and initials give the issuer away as much as the name does
+        let icon = if display.screen_share {
+            TotpIcon::placeholder(20.0)
+        } else {
+            self.view_icon(20.0)
+        };
         let content = row()
-            .push(self.icon.view(20.0).map(|m| match m {}))
+            .push(icon.map(|m| match m {}))
             .push(content)
+            .push_maybe(favorite)
+            .push_maybe(copy_next)
             .push_maybe(ttk)
             .spacing(5)
             .align_y(Alignment::Center);
+        let content = column().push(content).push_maybe(bar).spacing(BAR_SPACING);
 
         if SHOW_CODES {
+            // Masked codes are revealed by the first click, and copied by the next
             button::custom(content)
                 .width(Length::Shrink)
                 .class(cosmic::theme::Button::ListItem)
-                .padding(5)
-                .on_press(EntryMessage::CopyOutput)
+                .padding(ROW_PADDING)
+                .on_press(if masked {
+                    EntryMessage::Reveal(Duration::from_secs(display.reveal_seconds))
+                } else {
+                    EntryMessage::CopyOutput
+                })
                 .into()
         } else {
             content.into()
         }
     }
 
-    pub fn subscription(&self, window_id: cosmic::iced::window::Id) -> Subscription<EntryMessage> {
-        let curr_t = std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .unwrap();
-        // let time_to = self.totp.next_step(curr_t.as_secs()) - curr_t.as_secs();
-        let next_time = Duration::new(
-            self.totp.step - curr_t.as_secs() % self.totp.step,
-            u32::MAX - curr_t.subsec_nanos(),
-        );
-        let time_since = Duration::from_secs(self.totp.step)
-            .checked_sub(next_time)
-            .unwrap_or_default();
-        let periodic = tokio_stream::once((
-            tokio::time::Instant::now().checked_sub(time_since).unwrap(),
-            curr_t.as_secs(),
-        ))
-        .chain(
-            tokio_stream::wrappers::IntervalStream::new(interval_at(
-                Instant::now() + next_time,
-                Duration::from_secs(self.totp.step),
-            ))
-            .map(|i| {
-                (
-                    i,
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                )
-            }),
+    /// Steps the code, the countdown keeps itself moving in between.
+    pub fn subscription(&self) -> Subscription<EntryMessage> {
+        if let EntryKind::Hotp { counter } = self.kind {
+            // Only needs the initial code, later ones are generated on request
+            return Subscription::run_with_id(
+                ("hotp", counter),
+                tokio_stream::once(EntryMessage::Stepped(Instant::now().into(), 0)),
+            );
+        }
+        let step = self.totp.step.max(1);
+        let offset = self.time_offset;
+        // The boundary is worked out from the wall clock again on every tick rather than trusting
+        // a fixed interval, so changes to the clock are followed
+        let periodic = cosmic::iced::futures::stream::unfold(true, move |first| async move {
+            if !first {
+                let into = into_step(super::clock::now(), offset, step);
+                tokio::time::sleep(Duration::from_secs(step).saturating_sub(into)).await;
+            }
+            let now = super::clock::now();
+            let into = into_step(now, offset, step);
+            let started = Instant::now()
+                .checked_sub(into)
+                .unwrap_or_else(Instant::now);
+            Some((EntryMessage::Stepped(started.into(), now.as_secs()), false))
+        });
+        // Changing the offset moves the step boundaries
+        Subscription::run_with_id(
+            (self.totp.step, super::clock::offset(), self.time_offset),
+            periodic,
         )
-        .map(|(i, t)| EntryMessage::Stepped(i.into(), t));
-        Subscription::batch([
-            Subscription::run_with_id(self.totp.step, periodic),
-            cosmic::iced::window::frames()
-                .with(window_id)
-                .map(|(wi, (i, t))| {
-                    if i == wi {
-                        EntryMessage::Animate(t)
-                    } else {
-                        EntryMessage::Noop
+    }
+}
+
+/// How long the current step has been going at `now`, with steps beginning and ending at shifted
+/// times for entries with an offset of their own.
+fn into_step(now: Duration, offset: i64, step: u64) -> Duration {
+    let now = super::clock::shift(now, offset);
+    Duration::new(now.as_secs() % step, now.subsec_nanos())
+}
+
+/// How far either way a time offset can go, and is looked for when calibrating.
+const CALIBRATE_SECONDS: i64 = 3600;
+
+/// The line heights of the texts in a row of the main list, which are fixed and never wrap so
+/// the height of a row is known without laying it out.
+const NAME_LINE: f32 = 20.0;
+const CODE_SIZE: f32 = 30.0;
+const CODE_LINE: f32 = 40.0;
+const NEXT_LINE: f32 = 16.0;
+/// The padding around a row of the main list.
+const ROW_PADDING: f32 = 5.0;
+/// The height of the progress bar and its gap to the rest of the row.
+const BAR_HEIGHT: f32 = 4.0;
+const BAR_SPACING: f32 = 5.0;
+
+/// A name in a row of the main list, kept to one line of a known height.
+fn name_text<'a>(name: String) -> cosmic::Element<'a, EntryMessage> {
+    text::text(name)
+        .line_height(LineHeight::Absolute(NAME_LINE.into()))
+        .wrapping(Wrapping::None)
+        .into()
+}
+
+pub fn random_id() -> String {
+    let mut bytes = [0; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// A new secret key of `bytes` random bytes.
+pub fn random_secret(bytes: usize) -> Vec<u8> {
+    let mut secret = vec![0; bytes];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// A settings item with the problems found in its field listed under it, outlined if there are
+/// any.
+fn checked<'a>(
+    problems: &[(Field, String)],
+    field: Field,
+    item: impl Into<cosmic::Element<'a, EntryMessage>>,
+) -> cosmic::Element<'a, EntryMessage> {
+    let mut problems = problems.iter().filter(|(f, _)| *f == field).peekable();
+    if problems.peek().is_none() {
+        return item.into();
+    }
+    problems
+        .fold(column().push(item).spacing(4), |column, (_, problem)| {
+            let mut problem = problem.clone();
+            if let Some(first) = problem.get_mut(..1) {
+                first.make_ascii_uppercase();
+            }
+            column.push(
+                text::caption(problem).class(cosmic::theme::Text::Custom(|t| {
+                    cosmic::iced::widget::text::Style {
+                        color: Some(t.cosmic().destructive_color().into()),
                     }
-                }),
-        ])
+                })),
+            )
+        })
+        .apply(container)
+        .padding([4, 8])
+        .style(|t: &cosmic::Theme| container::Style {
+            border: cosmic::iced::Border {
+                color: t.cosmic().destructive_color().into(),
+                width: 1.0,
+                radius: t.cosmic().corner_radii.radius_s.into(),
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// How close a code is to expiring.
+#[derive(Debug, Clone, Copy)]
+enum Urgency {
+    Normal,
+    Warning,
+    Critical,
+}
+impl Urgency {
+    fn color(self, theme: &cosmic::Theme) -> cosmic::iced::Color {
+        let palette = theme.cosmic();
+        match self {
+            Self::Normal => palette.accent_color(),
+            Self::Warning => palette.warning_color(),
+            Self::Critical => palette.destructive_color(),
+        }
+        .into()
     }
 }
 
+/// The countdown to the next code, as a ring with the seconds left or as a bar.
+///
+/// It keeps itself moving by requesting redraws and reading the time they happen at, so the
+/// app only hears about the entry once per step.
 struct Ttk {
-    percentage: f32,
-    thickness: f32,
+    /// When the current code was generated.
+    started: std::time::Instant,
+    step: u64,
+    display: DisplayOptions,
+}
+impl Ttk {
+    const THICKNESS: f32 = 4.0;
+
+    fn remaining(&self, now: std::time::Instant) -> Duration {
+        Duration::from_secs(self.step).saturating_sub(now.saturating_duration_since(self.started))
+    }
 }
 impl canvas::Program<(), cosmic::Theme> for Ttk {
-    type State = ();
+    /// When the latest redraw was requested for.
+    type State = Option<std::time::Instant>;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: &canvas::Event,
+        _bounds: cosmic::iced::Rectangle,
+        _cursor: cosmic::iced_core::mouse::Cursor,
+    ) -> Option<canvas::Action<()>> {
+        let cosmic::iced::Event::Window(cosmic::iced::window::Event::RedrawRequested(now)) = event
+        else {
+            return None;
+        };
+        *state = Some(*now);
+        if !self.display.reduce_motion {
+            return Some(canvas::Action::request_redraw());
+        }
+        // Only redraw when the seconds shown change
+        let wait = match self.remaining(*now).subsec_nanos() {
+            0 => Duration::from_secs(1),
+            nanos => Duration::from_nanos(nanos.into()),
+        };
+        Some(canvas::Action::request_redraw_at(*now + wait))
+    }
 
+    #[allow(clippy::cast_precision_loss)]
     fn draw(
         &self,
-        _state: &Self::State,
+        state: &Self::State,
         renderer: &cosmic::Renderer,
         theme: &cosmic::Theme,
         bounds: cosmic::iced::Rectangle,
@@ -405,29 +1993,60 @@ impl canvas::Program<(), cosmic::Theme> for Ttk {
     ) -> Vec<canvas::Geometry<cosmic::Renderer>> {
         let mut frame = canvas::Frame::new(renderer, bounds.size());
 
-        let line = canvas::Path::new(|b| {
-            b.ellipse(canvas::path::arc::Elliptical {
-                center: frame.center(),
-                radii: cosmic::iced::Vector::from(frame.size() - [self.thickness + 1.0; 2].into())
-                    * 0.5,
-                // radii: cosmic::iced::Vector::new(5.0, 5.0),
-                rotation: cosmic::iced::Radians(-std::f32::consts::PI / 2.0),
-                start_angle: cosmic::iced::Radians(0.0),
-                end_angle: cosmic::iced::Radians(-self.percentage * 2.0 * std::f32::consts::PI),
-            });
-        });
+        let remaining = self.remaining(state.unwrap_or_else(std::time::Instant::now));
+        // Rounded up, so the last second shows as 1 rather than 0
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        let left = remaining.as_secs_f32() / self.step.max(1) as f32;
+        let color = self.display.urgency(seconds).color(theme);
+
+        let line = if self.display.progress_bar {
+            let y = frame.height() / 2.0;
+            let start = Self::THICKNESS / 2.0;
+            canvas::Path::line(
+                cosmic::iced::Point::new(start, y),
+                cosmic::iced::Point::new(start + (frame.width() - Self::THICKNESS) * left, y),
+            )
+        } else {
+            canvas::Path::new(|b| {
+                b.ellipse(canvas::path::arc::Elliptical {
+                    center: frame.center(),
+                    radii: cosmic::iced::Vector::from(
+                        frame.size() - [Self::THICKNESS + 1.0; 2].into(),
+                    ) * 0.5,
+                    rotation: cosmic::iced::Radians(-std::f32::consts::PI / 2.0),
+                    start_angle: cosmic::iced::Radians(0.0),
+                    end_angle: cosmic::iced::Radians(-left * 2.0 * std::f32::consts::PI),
+                });
+            })
+        };
 
         frame.stroke(
             &line,
             canvas::Stroke {
-                style: canvas::Style::Solid(theme.cosmic().accent_color().into()),
-                width: self.thickness,
+                style: canvas::Style::Solid(color),
+                width: Self::THICKNESS,
                 line_cap: canvas::LineCap::Round,
                 line_join: canvas::LineJoin::Round,
                 line_dash: canvas::LineDash::default(),
             },
         );
 
+        if !self.display.progress_bar {
+            frame.fill_text(canvas::Text {
+                content: seconds.to_string(),
+                position: frame.center(),
+                // Only tinted once the code is about to expire
+                color: match self.display.urgency(seconds) {
+                    Urgency::Normal => theme.cosmic().on_bg_color().into(),
+                    _ => color,
+                },
+                font: cosmic::font::mono(),
+                align_x: cosmic::iced::widget::text::Alignment::Center,
+                align_y: cosmic::iced::alignment::Vertical::Center,
+                ..canvas::Text::default()
+            });
+        }
+
         vec![frame.into_geometry()]
     }
 }
@@ -439,11 +2058,36 @@ pub enum TotpIcon {
         #[serde(skip)]
         handle: std::sync::OnceLock<widget::image::Handle>,
     },
+    /// The issuer's logo, replaced when the issuer changes.
+    Brand {
+        path: PathBuf,
+        #[serde(skip)]
+        handle: std::sync::OnceLock<widget::image::Handle>,
+    },
+    Emoji {
+        emoji: String,
+    },
+    /// An icon from the icon theme, so symbolic icons follow the theme's colors.
+    Named {
+        name: String,
+    },
     Initials {
         initials: String,
     },
 }
 impl TotpIcon {
+    /// The path of an image that's drawn from a thumbnail, and the thumbnail once it's loaded.
+    fn raster(&self) -> Option<(&PathBuf, &std::sync::OnceLock<widget::image::Handle>)> {
+        match self {
+            Self::Image { path, handle } | Self::Brand { path, handle }
+                if path.extension().is_none_or(|e| e != "svg") =>
+            {
+                Some((path, handle))
+            }
+            _ => None,
+        }
+    }
+
     pub fn default_for_name(name: &str) -> Self {
         let fch = name
             .split_whitespace()
@@ -464,15 +2108,67 @@ impl TotpIcon {
         }
     }
 
-    pub fn view(&self, radius: f32) -> cosmic::Element<std::convert::Infallible> {
+    /// A neutral icon that gives nothing about the entry away, for sharing the screen.
+    pub fn placeholder<'a>(radius: f32) -> cosmic::Element<'a, std::convert::Infallible> {
+        widget::container(
+            cosmic::widget::icon::from_name("dialog-password-symbolic")
+                .symbolic(true)
+                .icon()
+                .width(Length::Fixed(radius))
+                .height(Length::Fixed(radius)),
+        )
+        .center(Length::Fixed(radius * 2.0))
+        .into()
+    }
+
+    /// Draws the icon, with `background` behind initials instead of the container color.
+    pub fn view(
+        &self,
+        radius: f32,
+        background: Option<cosmic::iced::Color>,
+    ) -> cosmic::Element<std::convert::Infallible> {
+        let background = background.filter(|_| matches!(self, Self::Initials { .. }));
         widget::container(match self {
-            Self::Image { path, handle } => cosmic::Element::from(
-                widget::image(handle.get_or_init(|| widget::image::Handle::from_path(path)))
-                    .width(Length::Fixed(radius * 2.0))
-                    .height(Length::Fixed(radius * 2.0))
-                    .border_radius([radius; 4])
-                    .content_fit(cosmic::iced::ContentFit::Cover),
-            ),
+            Self::Image { path, .. } | Self::Brand { path, .. }
+                if path.extension().is_some_and(|e| e == "svg") =>
+            {
+                cosmic::Element::from(
+                    widget::svg(widget::svg::Handle::from_path(path))
+                        .width(Length::Fixed(radius * 2.0))
+                        .height(Length::Fixed(radius * 2.0))
+                        .content_fit(cosmic::iced::ContentFit::Contain),
+                )
+            }
+            Self::Image { handle, .. } | Self::Brand { handle, .. } => match handle.get() {
+                Some(handle) => cosmic::Element::from(
+                    widget::image(handle)
+                        .width(Length::Fixed(radius * 2.0))
+                        .height(Length::Fixed(radius * 2.0))
+                        .border_radius([radius; 4])
+                        .content_fit(cosmic::iced::ContentFit::Cover),
+                ),
+                // Left empty while the thumbnail is created
+                None => cosmic::widget::Space::new(
+                    Length::Fixed(radius * 2.0),
+                    Length::Fixed(radius * 2.0),
+                )
+                .into(),
+            },
+            Self::Named { name } => widget::container(
+                cosmic::widget::icon::from_name(name.as_str())
+                    .symbolic(name.ends_with("-symbolic"))
+                    .icon()
+                    .width(Length::Fixed(radius))
+                    .height(Length::Fixed(radius)),
+            )
+            .center(Length::Fixed(radius * 2.0))
+            .into(),
+            Self::Emoji { emoji } => cosmic::widget::text::title1(emoji)
+                .width(radius * 2.0)
+                .height(radius * 2.0)
+                .center()
+                .size(radius * 1.2)
+                .into(),
             Self::Initials { initials } => cosmic::widget::text::title1(initials)
                 .width(radius * 2.0)
                 .height(radius * 2.0)
@@ -484,12 +2180,73 @@ impl TotpIcon {
         .height(Length::Fixed(radius * 2.0))
         .clip(true)
         .style(move |t: &cosmic::Theme| {
-            cosmic::style::Container::secondary(t.cosmic()).border(cosmic::iced::Border {
-                color: t.cosmic().secondary.divider.into(),
-                width: 1.,
-                radius: cosmic::iced::Radius::new(radius),
-            })
+            let style =
+                cosmic::style::Container::secondary(t.cosmic()).border(cosmic::iced::Border {
+                    color: t.cosmic().secondary.divider.into(),
+                    width: 1.,
+                    radius: cosmic::iced::Radius::new(radius),
+                });
+            match background {
+                Some(color) => container::Style {
+                    background: Some(cosmic::iced::Background::Color(color)),
+                    text_color: Some(contrasting_text(color)),
+                    ..style
+                },
+                None => style,
+            }
         })
         .into()
     }
 }
+
+fn view_crop(crop: &super::icons::Crop) -> cosmic::Element<EntryMessage> {
+    use cosmic::widget::{settings, slider};
+
+    let sliders = column()
+        .push(settings::item(
+            "Zoom",
+            slider(1.0..=4.0, crop.zoom, EntryMessage::CropZoom).step(0.05),
+        ))
+        .push(settings::item(
+            "Horizontal",
+            slider(0.0..=1.0, crop.x, EntryMessage::CropX).step(0.01),
+        ))
+        .push(settings::item(
+            "Vertical",
+            slider(0.0..=1.0, crop.y, EntryMessage::CropY).step(0.01),
+        ))
+        .push(
+            row()
+                .push(cosmic::widget::horizontal_space())
+                .push(button::standard("Cancel").on_press(EntryMessage::CropCancel))
+                .push(button::suggested("Use Image").on_press(EntryMessage::CropApply))
+                .spacing(5),
+        )
+        .spacing(5)
+        .width(Length::Fill);
+
+    row()
+        .push(
+            widget::image(crop.preview.clone())
+                .width(Length::Fixed(96.0))
+                .height(Length::Fixed(96.0))
+                .border_radius([48.0; 4]),
+        )
+        .push(sliders)
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .into()
+}
+
+/// Black or white, whichever is more readable on `background`.
+fn contrasting_text(background: cosmic::iced::Color) -> cosmic::iced::Color {
+    let luma = 0.299f32.mul_add(
+        background.r,
+        0.587f32.mul_add(background.g, 0.114 * background.b),
+    );
+    if luma > 0.6 {
+        cosmic::iced::Color::BLACK
+    } else {
+        cosmic::iced::Color::WHITE
+    }
+}
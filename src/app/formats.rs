@@ -0,0 +1,114 @@
+//! Import and export formats of other authenticators.
+
+use std::{path::PathBuf, sync::Arc};
+
+use tracing::info;
+
+use super::entry::Entry;
+
+pub mod aegis;
+pub mod backup;
+pub mod bitwarden;
+pub mod ente;
+pub mod freeotp;
+pub mod google;
+pub mod uri_list;
+
+#[derive(Debug, Clone)]
+pub enum Imported {
+    Entries(Vec<Entry>),
+    /// The export is encrypted, and has to be parsed again with a password.
+    Encrypted(Arc<[u8]>),
+}
+
+/// Lets the user pick a file exported from another authenticator and parses its entries.
+///
+/// Returns no entries if the user cancelled the file picker.
+pub async fn import_file() -> Result<Imported, String> {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_title("Import Entries")
+        .pick_file()
+        .await
+    else {
+        info!("User cancelled import");
+        return Ok(Imported::Entries(Vec::new()));
+    };
+
+    let data = tokio::fs::read(file.path())
+        .await
+        .map_err(|e| format!("Couldn't read import file: {e}"))?;
+    tokio::task::spawn_blocking(move || parse(&data, None))
+        .await
+        .map_err(|e| format!("Couldn't join import thread: {e}"))?
+}
+
+pub async fn decrypt_import(data: Arc<[u8]>, password: String) -> Result<Imported, String> {
+    tokio::task::spawn_blocking(move || parse(&data, Some(&password)))
+        .await
+        .map_err(|e| format!("Couldn't join import thread: {e}"))?
+}
+
+/// Asks the user where to save an export, then writes the data produced by `export` there.
+///
+/// Returns [`None`] if the user cancelled the file picker.
+pub async fn export_file<F>(file_name: &str, export: F) -> Result<Option<PathBuf>, String>
+where
+    F: FnOnce() -> Result<Vec<u8>, String> + Send + 'static,
+{
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_title("Export Entries")
+        .set_file_name(file_name)
+        .save_file()
+        .await
+    else {
+        info!("User cancelled export");
+        return Ok(None);
+    };
+
+    let data = tokio::task::spawn_blocking(export)
+        .await
+        .map_err(|e| format!("Couldn't join export thread: {e}"))??;
+    tokio::fs::write(file.path(), data)
+        .await
+        .map_err(|e| format!("Couldn't write export file: {e}"))?;
+
+    info!("Exported entries to {:?}", file.path());
+    Ok(Some(file.path().to_path_buf()))
+}
+
+/// Detects the format of an export and parses its entries.
+pub fn parse(data: &[u8], password: Option<&str>) -> Result<Imported, String> {
+    if backup::is_backup(data) {
+        info!("Importing backup");
+        return match password {
+            Some(password) => backup::decrypt(data, password).map(Imported::Entries),
+            None => Ok(Imported::Encrypted(data.into())),
+        };
+    }
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(data) {
+        if aegis::is_vault(&json) {
+            info!("Importing Aegis vault");
+            return match password {
+                _ if !aegis::is_encrypted(&json) => aegis::parse(json).map(Imported::Entries),
+                Some(password) => aegis::decrypt(json, password).map(Imported::Entries),
+                None => Ok(Imported::Encrypted(data.into())),
+            };
+        }
+        if freeotp::is_backup(&json) {
+            info!("Importing FreeOTP+ backup");
+            return freeotp::parse(json).map(Imported::Entries);
+        }
+        if bitwarden::is_export(&json) {
+            info!("Importing Bitwarden export");
+            return bitwarden::parse(json).map(Imported::Entries);
+        }
+    }
+    if let Ok(text) = std::str::from_utf8(data)
+        && ente::is_export(text)
+    {
+        info!("Importing Ente Auth export");
+        return Ok(Imported::Entries(ente::parse(text)));
+    }
+
+    Err("Unrecognised import format".into())
+}
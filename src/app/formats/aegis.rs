@@ -0,0 +1,441 @@
+//! Aegis Authenticator vault exports.
+
+use aes_gcm::{
+    Aes256Gcm,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use tracing::{info, warn};
+
+use crate::app::{
+    entry::{Entry, EntryKind, TotpIcon},
+    icons,
+};
+
+/// The scrypt parameters used by Aegis itself.
+const SCRYPT_N: u32 = 1 << 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+#[derive(serde::Deserialize)]
+struct Vault {
+    header: Header,
+    db: serde_json::Value,
+}
+
+#[derive(serde::Deserialize)]
+struct Header {
+    slots: Option<Vec<Slot>>,
+    params: Option<KeyParams>,
+}
+
+/// An encrypted copy of the master key.
+#[derive(serde::Deserialize)]
+struct Slot {
+    #[serde(rename = "type")]
+    kind: u8,
+    key: String,
+    key_params: KeyParams,
+    n: Option<u32>,
+    r: Option<u32>,
+    p: Option<u32>,
+    salt: Option<String>,
+}
+impl Slot {
+    const PASSWORD: u8 = 1;
+
+    fn unlock(&self, password: &str) -> Result<Vec<u8>, String> {
+        let (Some(n), Some(r), Some(p), Some(salt)) = (self.n, self.r, self.p, &self.salt) else {
+            return Err("Password slot is missing its parameters".into());
+        };
+        let salt = hex::decode(salt).map_err(|e| format!("Invalid salt: {e}"))?;
+        let key = derive_key(password, &salt, n, r, p)?;
+
+        let master_key = hex::decode(&self.key).map_err(|e| format!("Invalid slot key: {e}"))?;
+        self.key_params.decrypt(&key, &master_key)
+    }
+}
+
+fn derive_key(password: &str, salt: &[u8], n: u32, r: u32, p: u32) -> Result<[u8; 32], String> {
+    #[allow(clippy::cast_possible_truncation)]
+    let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, 32)
+        .map_err(|e| format!("Invalid key derivation parameters: {e}"))?;
+    let mut key = [0; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| format!("Couldn't derive key: {e}"))?;
+
+    Ok(key)
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct KeyParams {
+    nonce: String,
+    tag: String,
+}
+impl KeyParams {
+    /// Encrypts with a fresh nonce, returning the ciphertext with the tag split off.
+    fn encrypt(key: &[u8], plaintext: &[u8]) -> Result<(Vec<u8>, Self), String> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| format!("Invalid key: {e}"))?;
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let mut ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Encryption failed")?;
+        let tag = ciphertext.split_off(ciphertext.len() - 16);
+
+        Ok((
+            ciphertext,
+            Self {
+                nonce: hex::encode(nonce),
+                tag: hex::encode(tag),
+            },
+        ))
+    }
+
+    fn decrypt(&self, key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| format!("Invalid key: {e}"))?;
+        let nonce = hex::decode(&self.nonce).map_err(|e| format!("Invalid nonce: {e}"))?;
+        if nonce.len() != 12 {
+            return Err("Invalid nonce length".into());
+        }
+        let mut payload = ciphertext.to_vec();
+        payload.extend(hex::decode(&self.tag).map_err(|e| format!("Invalid tag: {e}"))?);
+
+        cipher
+            .decrypt(aes_gcm::Nonce::from_slice(&nonce), payload.as_slice())
+            .map_err(|_| "Decryption failed".into())
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Db {
+    #[serde(default)]
+    entries: Vec<AegisEntry>,
+    #[serde(default)]
+    groups: Vec<Group>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Group {
+    uuid: String,
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct AegisEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    #[serde(default)]
+    issuer: String,
+    icon: Option<String>,
+    icon_mime: Option<String>,
+    info: Info,
+    /// Group uuids, used since database version 3.
+    #[serde(default)]
+    groups: Vec<String>,
+    /// The group name, used before database version 3.
+    group: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct Info {
+    secret: String,
+    algo: String,
+    digits: usize,
+    period: Option<u64>,
+    counter: Option<u64>,
+    pin: Option<String>,
+}
+
+pub fn is_vault(json: &serde_json::Value) -> bool {
+    json.get("header").is_some() && json.get("db").is_some()
+}
+
+pub fn is_encrypted(json: &serde_json::Value) -> bool {
+    json.get("db").is_some_and(serde_json::Value::is_string)
+}
+
+pub fn parse(json: serde_json::Value) -> Result<Vec<Entry>, String> {
+    let vault: Vault =
+        serde_json::from_value(json).map_err(|e| format!("Invalid Aegis vault: {e}"))?;
+    let db: Db =
+        serde_json::from_value(vault.db).map_err(|e| format!("Invalid Aegis vault: {e}"))?;
+
+    Ok(convert_all(db))
+}
+
+/// Unlocks the master key with a password slot, and decrypts the database with it.
+pub fn decrypt(json: serde_json::Value, password: &str) -> Result<Vec<Entry>, String> {
+    let vault: Vault =
+        serde_json::from_value(json).map_err(|e| format!("Invalid Aegis vault: {e}"))?;
+    let (Some(slots), Some(params)) = (vault.header.slots, vault.header.params) else {
+        return Err("Aegis vault is missing its encryption header".into());
+    };
+    let db = vault
+        .db
+        .as_str()
+        .ok_or("Aegis vault database isn't encrypted")?;
+    let db = BASE64
+        .decode(db)
+        .map_err(|e| format!("Invalid Aegis vault database: {e}"))?;
+
+    info!("Unlocking Aegis vault");
+    let master_key = slots
+        .iter()
+        .filter(|s| s.kind == Slot::PASSWORD)
+        .find_map(|s| {
+            s.unlock(password)
+                .inspect_err(|e| info!("Couldn't unlock slot: {e}"))
+                .ok()
+        })
+        .ok_or("Incorrect password")?;
+    let db = params.decrypt(&master_key, &db)?;
+    let db: Db =
+        serde_json::from_slice(&db).map_err(|e| format!("Invalid Aegis vault database: {e}"))?;
+
+    Ok(convert_all(db))
+}
+
+fn convert_all(db: Db) -> Vec<Entry> {
+    db.entries
+        .into_iter()
+        .filter_map(|entry| {
+            let name = entry.name.clone();
+            convert(entry, &db.groups)
+                .inspect_err(|e| warn!("Skipping Aegis entry {name}: {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+fn convert(aegis: AegisEntry, groups: &[Group]) -> Result<Entry, String> {
+    if !matches!(aegis.kind.as_str(), "totp" | "hotp" | "motp" | "steam") {
+        return Err(format!("Unsupported entry type {}", aegis.kind));
+    }
+
+    let mut entry = Entry::new();
+    entry.totp.account_name = aegis.name;
+    entry.totp.issuer = (!aegis.issuer.is_empty()).then_some(aegis.issuer);
+    entry.totp.algorithm = match aegis.info.algo.as_str() {
+        _ if aegis.kind == "steam" => totp_rs::Algorithm::Steam,
+        // mOTP entries are always MD5, which is handled by the entry kind
+        _ if aegis.kind == "motp" => totp_rs::Algorithm::SHA1,
+        "SHA1" => totp_rs::Algorithm::SHA1,
+        "SHA256" => totp_rs::Algorithm::SHA256,
+        "SHA512" => totp_rs::Algorithm::SHA512,
+        algo => return Err(format!("Unsupported algorithm {algo}")),
+    };
+    entry.totp.digits = aegis.info.digits;
+    entry.totp.step = aegis.info.period.unwrap_or(30);
+    entry.secret = aegis.info.secret;
+    entry.recalc_secret()?;
+    match aegis.kind.as_str() {
+        "hotp" => {
+            entry.kind = EntryKind::Hotp {
+                counter: aegis.info.counter.unwrap_or_default(),
+            };
+        }
+        "motp" => {
+            // Aegis stores the hex mOTP secret as base32 like any other
+            entry.secret = hex::encode(&entry.totp.secret);
+            entry.kind = EntryKind::Motp {
+                pin: aegis.info.pin.unwrap_or_default(),
+            };
+        }
+        _ => {}
+    }
+    entry.group = aegis
+        .groups
+        .first()
+        .and_then(|uuid| groups.iter().find(|g| &g.uuid == uuid))
+        .map(|g| g.name.clone())
+        .or(aegis.group);
+    entry.recalc_icon();
+
+    if let Some(icon) = aegis.icon {
+        match store_icon(&icon, aegis.icon_mime.as_deref()) {
+            Ok(path) => {
+                entry.icon = TotpIcon::Image {
+                    path,
+                    handle: std::sync::OnceLock::new(),
+                };
+            }
+            Err(e) => warn!("Couldn't import icon for {}: {e}", entry.totp.account_name),
+        }
+    }
+
+    Ok(entry)
+}
+
+fn store_icon(icon: &str, mime: Option<&str>) -> Result<std::path::PathBuf, String> {
+    let data = BASE64
+        .decode(icon)
+        .map_err(|e| format!("Invalid icon data: {e}"))?;
+    let extension = match mime {
+        Some("image/svg+xml") => "svg",
+        Some("image/jpeg") => "jpg",
+        _ => "png",
+    };
+    icons::store(&data, extension)
+}
+
+/// Writes an encrypted vault that Aegis can import, protected by a single password slot.
+pub fn export(entries: &[Entry], password: &str) -> Result<Vec<u8>, String> {
+    let mut groups: Vec<Group> = Vec::new();
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let group = entry.group.as_ref().map(|name| {
+                if let Some(group) = groups.iter().find(|g| &g.name == name) {
+                    group.uuid.clone()
+                } else {
+                    let uuid = random_uuid();
+                    groups.push(Group {
+                        uuid: uuid.clone(),
+                        name: name.clone(),
+                    });
+                    uuid
+                }
+            });
+            let (icon, icon_mime) = export_icon(&entry.icon).unzip();
+            let (kind, counter, pin) = match &entry.kind {
+                EntryKind::Hotp { counter } => ("hotp", Some(*counter), None),
+                EntryKind::Motp { pin } => ("motp", None, Some(pin)),
+                EntryKind::Totp if entry.totp.algorithm == totp_rs::Algorithm::Steam => {
+                    ("steam", None, None)
+                }
+                EntryKind::Totp => ("totp", None, None),
+            };
+
+            serde_json::json!({
+                "type": kind,
+                "uuid": random_uuid(),
+                "name": entry.totp.account_name,
+                "issuer": entry.totp.issuer.as_deref().unwrap_or_default(),
+                "note": "",
+                "favorite": false,
+                "icon": icon,
+                "icon_mime": icon_mime,
+                "info": {
+                    "secret": entry.totp.get_secret_base32(),
+                    "algo": match entry.totp.algorithm {
+                        _ if pin.is_some() => "MD5",
+                        totp_rs::Algorithm::SHA1 | totp_rs::Algorithm::Steam => "SHA1",
+                        totp_rs::Algorithm::SHA256 => "SHA256",
+                        totp_rs::Algorithm::SHA512 => "SHA512",
+                    },
+                    "digits": entry.totp.digits,
+                    "period": entry.totp.step,
+                    "counter": counter,
+                    "pin": pin,
+                },
+                "groups": Vec::from_iter(group),
+            })
+        })
+        .collect::<Vec<_>>();
+    let db = serde_json::json!({
+        "version": 3,
+        "entries": entries,
+        "groups": groups,
+    });
+    let db = serde_json::to_vec(&db).map_err(|e| format!("Couldn't serialise vault: {e}"))?;
+
+    info!("Encrypting Aegis vault");
+    let master_key = Aes256Gcm::generate_key(OsRng);
+    let (db, params) = KeyParams::encrypt(&master_key, &db)?;
+
+    let mut salt = [0; 32];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(password, &salt, SCRYPT_N, SCRYPT_R, SCRYPT_P)?;
+    let (slot_key, key_params) = KeyParams::encrypt(&key, &master_key)?;
+
+    let vault = serde_json::json!({
+        "version": 1,
+        "header": {
+            "slots": [{
+                "type": Slot::PASSWORD,
+                "uuid": random_uuid(),
+                "key": hex::encode(slot_key),
+                "key_params": key_params,
+                "n": SCRYPT_N,
+                "r": SCRYPT_R,
+                "p": SCRYPT_P,
+                "salt": hex::encode(salt),
+                "repaired": true,
+                "is_backup": false,
+            }],
+            "params": params,
+        },
+        "db": BASE64.encode(db),
+    });
+    serde_json::to_vec_pretty(&vault).map_err(|e| format!("Couldn't serialise vault: {e}"))
+}
+
+fn export_icon(icon: &TotpIcon) -> Option<(String, &'static str)> {
+    let (TotpIcon::Image { path, .. } | TotpIcon::Brand { path, .. }) = icon else {
+        return None;
+    };
+    let data = std::fs::read(path)
+        .inspect_err(|e| warn!("Couldn't export icon {path:?}: {e}"))
+        .ok()?;
+    let mime = match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => "image/svg+xml",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        _ => "image/png",
+    };
+
+    Some((BASE64.encode(data), mime))
+}
+
+fn random_uuid() -> String {
+    let mut bytes = [0; 16];
+    OsRng.fill_bytes(&mut bytes);
+    // Version 4, variant 1
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, export, is_encrypted, is_vault};
+    use crate::app::entry::{Entry, EntryKind};
+
+    #[test]
+    fn round_trip() {
+        let mut totp = Entry::from_uri(
+            "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example&digits=8",
+        )
+        .unwrap();
+        totp.group = Some("Work".into());
+        let hotp = Entry::from_uri("otpauth://hotp/bob?secret=GEZDGNBVGY3TQOJQ&counter=7").unwrap();
+
+        let data = export(&[totp.clone(), hotp.clone()], "hunter2").unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+        assert!(is_vault(&json) && is_encrypted(&json));
+        assert_eq!(
+            decrypt(json.clone(), "hunter3").unwrap_err(),
+            "Incorrect password"
+        );
+        let entries = decrypt(json, "hunter2").unwrap();
+        assert_eq!(entries.len(), 2);
+
+        for (original, decoded) in [&totp, &hotp].into_iter().zip(&entries) {
+            assert_eq!(decoded.totp.secret, original.totp.secret);
+            assert_eq!(decoded.totp.account_name, original.totp.account_name);
+            assert_eq!(decoded.totp.issuer, original.totp.issuer);
+            assert_eq!(decoded.totp.digits, original.totp.digits);
+            assert_eq!(decoded.group, original.group);
+        }
+        assert_eq!(entries[1].kind, EntryKind::Hotp { counter: 7 });
+    }
+}
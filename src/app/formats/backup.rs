@@ -0,0 +1,128 @@
+//! The app's own backups, an age passphrase-encrypted copy of the vault.
+
+use std::io::{Read, Write};
+
+use age::secrecy::SecretString;
+use tracing::info;
+
+use crate::app::{
+    entry::Entry,
+    secrets::{self, migrations},
+};
+
+/// What's encrypted in a backup, versioned like the vault so older backups are upgraded.
+#[derive(serde::Serialize)]
+struct Backup<'a> {
+    version: u32,
+    entries: &'a [Entry],
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Contents {
+    Versioned {
+        version: u32,
+        entries: Vec<serde_json::Value>,
+    },
+    /// Backups from before the format had a version are a bare list of entries.
+    Unversioned(Vec<serde_json::Value>),
+}
+
+pub fn is_backup(data: &[u8]) -> bool {
+    data.starts_with(b"age-encryption.org/v1")
+}
+
+pub fn export(entries: &[Entry], password: &str) -> Result<Vec<u8>, String> {
+    let backup = Backup {
+        version: migrations::VERSION,
+        entries,
+    };
+    let data =
+        serde_json::to_vec(&backup).map_err(|e| format!("Couldn't serialise backup: {e}"))?;
+    encrypt(&data, password)
+}
+
+fn encrypt(data: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    info!("Encrypting backup");
+    let encryptor = age::Encryptor::with_user_passphrase(SecretString::from(password.to_owned()));
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut encrypted)
+        .map_err(|e| format!("Couldn't encrypt backup: {e}"))?;
+    writer
+        .write_all(data)
+        .and_then(|()| writer.finish())
+        .map_err(|e| format!("Couldn't encrypt backup: {e}"))?;
+
+    Ok(encrypted)
+}
+
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<Entry>, String> {
+    let decryptor = age::Decryptor::new(data).map_err(|e| format!("Invalid backup: {e}"))?;
+    let identity = age::scrypt::Identity::new(SecretString::from(password.to_owned()));
+
+    info!("Decrypting backup");
+    let mut reader = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| match e {
+            age::DecryptError::DecryptionFailed | age::DecryptError::NoMatchingKeys => {
+                "Incorrect password".to_string()
+            }
+            e => format!("Couldn't decrypt backup: {e}"),
+        })?;
+    let mut decrypted = Vec::new();
+    reader
+        .read_to_end(&mut decrypted)
+        .map_err(|e| format!("Couldn't decrypt backup: {e}"))?;
+
+    let (version, entries) = match serde_json::from_slice(&decrypted)
+        .map_err(|e| format!("Invalid backup contents: {e}"))?
+    {
+        Contents::Versioned { version, entries } => (version, entries),
+        Contents::Unversioned(entries) => (0, entries),
+    };
+    if version > migrations::VERSION {
+        return Err("The backup was made by a newer version of the app".into());
+    }
+    entries
+        .into_iter()
+        .map(|entry| secrets::parse_entry(version, entry))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt, export, is_backup};
+    use crate::app::entry::Entry;
+
+    #[test]
+    fn round_trip() {
+        let mut entry =
+            Entry::from_uri("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example")
+                .unwrap();
+        entry.group = Some("Work".into());
+
+        let data = export(std::slice::from_ref(&entry), "hunter2").unwrap();
+        assert!(is_backup(&data));
+        let entries = decrypt(&data, "hunter2").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].totp.secret, entry.totp.secret);
+        assert_eq!(entries[0].totp.account_name, entry.totp.account_name);
+        assert_eq!(entries[0].totp.issuer, entry.totp.issuer);
+        assert_eq!(entries[0].group, entry.group);
+
+        assert_eq!(decrypt(&data, "hunter3").unwrap_err(), "Incorrect password");
+    }
+
+    #[test]
+    fn unversioned_backups_are_upgraded() {
+        let entry = Entry::from_uri("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        let mut json = serde_json::to_value(&entry).unwrap();
+        json["secret"] = "ABCDEFGHIJ".into();
+        json.as_object_mut().unwrap().remove("microsoft_padding");
+
+        let data = encrypt(&serde_json::to_vec(&[json]).unwrap(), "hunter2").unwrap();
+        let entries = decrypt(&data, "hunter2").unwrap();
+        assert!(entries[0].microsoft_padding);
+    }
+}
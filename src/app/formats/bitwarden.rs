@@ -0,0 +1,96 @@
+//! Bitwarden unencrypted JSON exports.
+
+use tracing::warn;
+
+use crate::app::entry::Entry;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<Folder>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(serde::Deserialize)]
+struct Folder {
+    id: String,
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    name: String,
+    folder_id: Option<String>,
+    login: Option<Login>,
+}
+
+#[derive(serde::Deserialize)]
+struct Login {
+    username: Option<String>,
+    totp: Option<String>,
+}
+
+pub fn is_export(json: &serde_json::Value) -> bool {
+    json.get("items").is_some_and(serde_json::Value::is_array)
+}
+
+pub fn parse(json: serde_json::Value) -> Result<Vec<Entry>, String> {
+    let export: Export =
+        serde_json::from_value(json).map_err(|e| format!("Invalid Bitwarden export: {e}"))?;
+    if export.encrypted {
+        return Err("Encrypted Bitwarden exports are not supported".into());
+    }
+
+    Ok(export
+        .items
+        .into_iter()
+        .filter_map(|item| {
+            let name = item.name.clone();
+            convert(item, &export.folders)
+                .inspect_err(|e| warn!("Skipping Bitwarden item {name}: {e}"))
+                .ok()
+                .flatten()
+        })
+        .collect())
+}
+
+/// Converts an item, returning [`None`] for items without a TOTP seed.
+fn convert(item: Item, folders: &[Folder]) -> Result<Option<Entry>, String> {
+    let Some(login) = item.login else {
+        return Ok(None);
+    };
+    let Some(totp) = login.totp.filter(|s| !s.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let totp = totp.trim();
+
+    let mut entry = if totp.starts_with("otpauth://") {
+        Entry::from_uri(totp)?
+    } else {
+        let mut entry = Entry::new();
+        if let Some(secret) = totp.strip_prefix("steam://") {
+            entry.totp.algorithm = totp_rs::Algorithm::Steam;
+            entry.totp.digits = 5;
+            entry.secret = secret.into();
+        } else {
+            // Bitwarden accepts seeds with spaces and in lowercase
+            entry.secret = totp.replace(' ', "").to_ascii_uppercase();
+        }
+        entry.recalc_secret()?;
+        entry.totp.issuer = Some(item.name);
+        entry.totp.account_name = login.username.unwrap_or_default();
+        entry
+    };
+    entry.group = item
+        .folder_id
+        .and_then(|id| folders.iter().find(|f| f.id == id))
+        .map(|f| f.name.clone());
+    entry.recalc_icon();
+
+    Ok(Some(entry))
+}
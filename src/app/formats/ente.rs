@@ -0,0 +1,99 @@
+//! Ente Auth plain-text exports, which are newline separated otpauth URIs.
+//!
+//! Ente adds a few things that don't parse as-is: lowercase algorithms, padded secrets, empty
+//! parameters and a `codeDisplay` JSON blob that also marks trashed codes.
+
+use tracing::{info, warn};
+
+use crate::app::entry::Entry;
+
+pub fn is_export(text: &str) -> bool {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .peekable();
+    lines.peek().is_some() && lines.all(|l| l.starts_with("otpauth://"))
+}
+
+pub fn parse(text: &str) -> Vec<Entry> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .filter_map(|line| {
+            let Some(uri) = normalise(line) else {
+                info!("Skipping trashed Ente code");
+                return None;
+            };
+            Entry::from_uri(&uri)
+                .inspect_err(|e| warn!("Skipping Ente code: {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// Cleans up an Ente URI, returning [`None`] if the code was trashed.
+fn normalise(line: &str) -> Option<String> {
+    let (base, query) = line.split_once('?').unwrap_or((line, ""));
+
+    let mut params = Vec::new();
+    for param in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = url::form_urlencoded::parse(param.as_bytes())
+            .next()
+            .unwrap_or_default();
+        match key.as_ref() {
+            "codeDisplay" => {
+                if value.contains("\"trashed\":true") {
+                    return None;
+                }
+            }
+            "algorithm" => params.push(format!("algorithm={}", value.to_ascii_uppercase())),
+            "secret" => params.push(format!(
+                "secret={}",
+                value
+                    .trim_end_matches('=')
+                    .replace(' ', "")
+                    .to_ascii_uppercase()
+            )),
+            "digits" | "period" | "counter" if value.is_empty() => {}
+            _ => params.push(param.to_string()),
+        }
+    }
+
+    Some(format!("{base}?{}", params.join("&")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_export, parse};
+    use crate::app::{entry::Entry, formats::uri_list};
+
+    #[test]
+    fn reads_uri_list_exports() {
+        let entries = [
+            "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example",
+            "otpauth://hotp/bob?secret=GEZDGNBVGY3TQOJQ&counter=7",
+        ]
+        .map(|uri| Entry::from_uri(uri).unwrap());
+
+        let text = String::from_utf8(uri_list::export(&entries)).unwrap();
+        assert!(is_export(&text));
+        let parsed = parse(&text);
+        assert_eq!(parsed.len(), entries.len());
+        for (original, parsed) in entries.iter().zip(&parsed) {
+            assert_eq!(parsed.uri(), original.uri());
+        }
+    }
+
+    #[test]
+    fn cleans_up_ente_quirks() {
+        let text = "otpauth://totp/alice?secret=jbswy3dpehpk3pxp%3D%3D&algorithm=sha256&digits=\n\
+                    otpauth://totp/bob?secret=JBSWY3DPEHPK3PXP\
+                    &codeDisplay=%7B%22trashed%22%3Atrue%7D\n";
+        let entries = parse(text);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].totp.account_name, "alice");
+        assert_eq!(entries[0].totp.algorithm, totp_rs::Algorithm::SHA256);
+        assert_eq!(entries[0].totp.digits, 6);
+    }
+}
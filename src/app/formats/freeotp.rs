@@ -0,0 +1,108 @@
+//! FreeOTP+ JSON backups.
+
+use tracing::warn;
+
+use crate::app::entry::{Entry, EntryKind};
+
+#[derive(serde::Deserialize)]
+struct Backup {
+    tokens: Vec<Token>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+    #[serde(rename = "type")]
+    kind: String,
+    label: String,
+    issuer_ext: Option<String>,
+    issuer_int: Option<String>,
+    secret: Secret,
+    #[serde(default = "default_algo")]
+    algo: String,
+    #[serde(default = "default_digits")]
+    digits: usize,
+    #[serde(default = "default_period")]
+    period: u64,
+    #[serde(default)]
+    counter: u64,
+}
+
+/// Secrets are usually signed Java bytes, but some versions write them as base32.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Secret {
+    Bytes(Vec<i8>),
+    Encoded(String),
+}
+
+fn default_algo() -> String {
+    "SHA1".into()
+}
+
+const fn default_digits() -> usize {
+    6
+}
+
+const fn default_period() -> u64 {
+    30
+}
+
+pub fn is_backup(json: &serde_json::Value) -> bool {
+    json.get("tokens").is_some_and(serde_json::Value::is_array)
+}
+
+pub fn parse(json: serde_json::Value) -> Result<Vec<Entry>, String> {
+    let backup: Backup =
+        serde_json::from_value(json).map_err(|e| format!("Invalid FreeOTP+ backup: {e}"))?;
+
+    Ok(backup
+        .tokens
+        .into_iter()
+        .filter_map(|token| {
+            let label = token.label.clone();
+            convert(token)
+                .inspect_err(|e| warn!("Skipping FreeOTP+ token {label}: {e}"))
+                .ok()
+        })
+        .collect())
+}
+
+fn convert(token: Token) -> Result<Entry, String> {
+    let mut entry = Entry::new();
+    match token.kind.to_ascii_uppercase().as_str() {
+        "TOTP" => {}
+        "HOTP" => {
+            entry.kind = EntryKind::Hotp {
+                counter: token.counter,
+            };
+        }
+        kind => return Err(format!("Unsupported token type {kind}")),
+    }
+    entry.totp.account_name = token.label;
+    entry.totp.issuer = token
+        .issuer_ext
+        .or(token.issuer_int)
+        .filter(|s| !s.is_empty());
+    entry.totp.algorithm = match token.algo.to_ascii_uppercase().as_str() {
+        "SHA1" => totp_rs::Algorithm::SHA1,
+        "SHA256" => totp_rs::Algorithm::SHA256,
+        "SHA512" => totp_rs::Algorithm::SHA512,
+        algo => return Err(format!("Unsupported algorithm {algo}")),
+    };
+    entry.totp.digits = token.digits;
+    entry.totp.step = token.period;
+    match token.secret {
+        Secret::Bytes(bytes) => {
+            entry.totp.secret = bytes.into_iter().map(i8::cast_unsigned).collect();
+            entry.secret = entry.totp.get_secret_base32();
+        }
+        Secret::Encoded(secret) => {
+            entry.secret = secret;
+            entry.recalc_secret()?;
+        }
+    }
+    entry.recalc_icon();
+
+    Ok(entry)
+}
@@ -0,0 +1,347 @@
+//! Google Authenticator's `otpauth-migration://` transfer QR codes.
+//!
+//! The `data` parameter is a base64 encoded protobuf `MigrationPayload`, encoded and decoded by
+//! hand here as only a handful of fields are needed.
+
+use base64::{
+    Engine,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+use tracing::warn;
+
+use crate::app::entry::{Entry, EntryKind};
+
+pub const SCHEME: &str = "otpauth-migration";
+/// Entries per transfer code, matching what Google Authenticator produces itself.
+const BATCH_SIZE: usize = 10;
+
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// A single transfer QR code, which may be one of several in a batch.
+#[derive(Debug)]
+pub struct Batch {
+    pub id: i32,
+    pub index: i32,
+    pub size: i32,
+    pub entries: Vec<Entry>,
+}
+
+pub fn is_migration_uri(uri: &str) -> bool {
+    uri.split_once("://")
+        .is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case(SCHEME))
+}
+
+pub fn decode(uri: &str) -> Result<Batch, String> {
+    let url = url::Url::parse(uri).map_err(|e| format!("Invalid migration URI: {e}"))?;
+    let data = url
+        .query_pairs()
+        .find_map(|(k, v)| (k == "data").then_some(v))
+        .ok_or("Migration URI has no data")?
+        // Unescaped '+' characters are decoded as spaces in query strings
+        .replace(' ', "+");
+    let payload = BASE64
+        .decode(data)
+        .map_err(|e| format!("Invalid migration data: {e}"))?;
+
+    let mut batch = Batch {
+        id: 0,
+        index: 0,
+        size: 1,
+        entries: Vec::new(),
+    };
+    let mut reader = Reader { data: &payload };
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(params)) => match decode_params(params)? {
+                Some(entry) => batch.entries.push(entry),
+                None => warn!("Skipping unsupported migration entry"),
+            },
+            (3, Value::Varint(v)) => batch.size = varint_i32(v),
+            (4, Value::Varint(v)) => batch.index = varint_i32(v),
+            (5, Value::Varint(v)) => batch.id = varint_i32(v),
+            _ => {}
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Whether Google Authenticator can represent an entry, as it only supports 30 second steps,
+/// 6 or 8 digits and no Steam codes.
+pub fn is_exportable(entry: &Entry) -> bool {
+    let step_ok = match entry.kind {
+        EntryKind::Totp => entry.totp.step == 30,
+        EntryKind::Hotp { .. } => true,
+        EntryKind::Motp { .. } => false,
+    };
+    step_ok
+        && matches!(entry.totp.digits, 6 | 8)
+        && entry.totp.algorithm != totp_rs::Algorithm::Steam
+        && !entry.totp.secret.is_empty()
+}
+
+/// Packs entries into as many transfer codes as needed.
+pub fn encode(entries: &[&Entry]) -> Vec<String> {
+    #[allow(clippy::cast_possible_truncation)]
+    let id = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+        & 0x7fff_ffff;
+    let batches = entries.chunks(BATCH_SIZE);
+    let size = batches.len() as u64;
+
+    batches
+        .enumerate()
+        .map(|(index, batch)| {
+            let mut payload = Writer::default();
+            for entry in batch {
+                payload.bytes(1, &encode_params(entry));
+            }
+            payload.varint(2, 1);
+            payload.varint(3, size);
+            payload.varint(4, index as u64);
+            payload.varint(5, id);
+
+            let data = BASE64.encode(payload.data);
+            format!(
+                "{SCHEME}://offline?data={}",
+                url::form_urlencoded::byte_serialize(data.as_bytes()).collect::<String>()
+            )
+        })
+        .collect()
+}
+
+fn encode_params(entry: &Entry) -> Vec<u8> {
+    let totp = &entry.totp;
+    let mut params = Writer::default();
+    params.bytes(1, &totp.secret);
+    params.bytes(2, totp.account_name.as_bytes());
+    if let Some(issuer) = &totp.issuer {
+        params.bytes(3, issuer.as_bytes());
+    }
+    params.varint(
+        4,
+        match totp.algorithm {
+            totp_rs::Algorithm::SHA1 | totp_rs::Algorithm::Steam => 1,
+            totp_rs::Algorithm::SHA256 => 2,
+            totp_rs::Algorithm::SHA512 => 3,
+        },
+    );
+    params.varint(5, if totp.digits == 8 { 2 } else { 1 });
+    match entry.kind {
+        EntryKind::Totp | EntryKind::Motp { .. } => params.varint(6, 2),
+        EntryKind::Hotp { counter } => {
+            params.varint(6, 1);
+            params.varint(7, counter);
+        }
+    }
+
+    params.data
+}
+
+/// Decodes an `OtpParameters` message, returning [`None`] for unsupported OTP types.
+fn decode_params(data: &[u8]) -> Result<Option<Entry>, String> {
+    let mut totp = Entry::new().totp;
+    let mut supported = true;
+    let mut hotp = false;
+    let mut counter = 0;
+
+    let mut reader = Reader { data };
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(secret)) => totp.secret = secret.to_vec(),
+            (2, Value::Bytes(name)) => totp.account_name = String::from_utf8_lossy(name).into(),
+            (3, Value::Bytes(issuer)) => {
+                let issuer = String::from_utf8_lossy(issuer).into_owned();
+                totp.issuer = (!issuer.is_empty()).then_some(issuer);
+            }
+            (4, Value::Varint(algorithm)) => match algorithm {
+                0 | 1 => totp.algorithm = totp_rs::Algorithm::SHA1,
+                2 => totp.algorithm = totp_rs::Algorithm::SHA256,
+                3 => totp.algorithm = totp_rs::Algorithm::SHA512,
+                _ => supported = false,
+            },
+            (5, Value::Varint(digits)) => totp.digits = if digits == 2 { 8 } else { 6 },
+            (6, Value::Varint(kind)) => match kind {
+                1 => hotp = true,
+                0 | 2 => hotp = false,
+                _ => supported = false,
+            },
+            (7, Value::Varint(c)) => counter = c,
+            _ => {}
+        }
+    }
+
+    // Names are usually stored as "Issuer:Account"
+    if let Some(issuer) = &totp.issuer
+        && let Some(account) = totp
+            .account_name
+            .strip_prefix(issuer.as_str())
+            .and_then(|s| s.strip_prefix(':'))
+    {
+        totp.account_name = account.trim_start().to_string();
+    }
+
+    Ok(supported.then(|| {
+        let mut entry = Entry::from_totp(totp);
+        if hotp {
+            entry.kind = EntryKind::Hotp { counter };
+        }
+        entry
+    }))
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const fn varint_i32(v: u64) -> i32 {
+    v as i32
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.data.len() {
+            return Err("Truncated migration data".into());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Invalid varint in migration data".into())
+    }
+
+    fn field(&mut self) -> Result<Option<(u64, Value<'a>)>, String> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_| "Invalid field length in migration data")?;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            t => return Err(format!("Unsupported wire type {t} in migration data")),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    data: Vec<u8>,
+}
+impl Writer {
+    #[allow(clippy::cast_possible_truncation)]
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.data.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        self.data.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u64, value: u64) {
+        self.raw_varint(field << 3);
+        self.raw_varint(value);
+    }
+
+    fn bytes(&mut self, field: u64, value: &[u8]) {
+        self.raw_varint((field << 3) | 2);
+        self.raw_varint(value.len() as u64);
+        self.data.extend_from_slice(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, is_exportable, is_migration_uri};
+    use crate::app::entry::{Entry, EntryKind};
+
+    fn entry(uri: &str) -> Entry {
+        Entry::from_uri(uri).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let totp = entry(
+            "otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example\
+             &algorithm=SHA256&digits=8",
+        );
+        let hotp = entry("otpauth://hotp/bob?secret=GEZDGNBVGY3TQOJQ&counter=7");
+
+        let uris = encode(&[&totp, &hotp]);
+        assert_eq!(uris.len(), 1);
+        assert!(is_migration_uri(&uris[0]));
+        let batch = decode(&uris[0]).unwrap();
+        assert_eq!((batch.index, batch.size), (0, 1));
+        assert_eq!(batch.entries.len(), 2);
+
+        for (original, decoded) in [&totp, &hotp].into_iter().zip(&batch.entries) {
+            assert_eq!(decoded.totp.secret, original.totp.secret);
+            assert_eq!(decoded.totp.account_name, original.totp.account_name);
+            assert_eq!(decoded.totp.issuer, original.totp.issuer);
+            assert_eq!(decoded.totp.algorithm, original.totp.algorithm);
+            assert_eq!(decoded.totp.digits, original.totp.digits);
+            assert_eq!(decoded.kind, original.kind);
+        }
+        assert_eq!(batch.entries[1].kind, EntryKind::Hotp { counter: 7 });
+    }
+
+    #[test]
+    fn large_exports_are_split() {
+        let entries: Vec<_> = (0..11)
+            .map(|i| entry(&format!("otpauth://totp/user{i}?secret=JBSWY3DPEHPK3PXP")))
+            .collect();
+        let uris = encode(&entries.iter().collect::<Vec<_>>());
+        assert_eq!(uris.len(), 2);
+
+        let batches: Vec<_> = uris.iter().map(|uri| decode(uri).unwrap()).collect();
+        assert_eq!(batches[0].id, batches[1].id);
+        assert_eq!((batches[1].index, batches[1].size), (1, 2));
+        assert_eq!(batches[0].entries.len() + batches[1].entries.len(), 11);
+    }
+
+    #[test]
+    fn only_supported_entries_are_exportable() {
+        assert!(is_exportable(&entry(
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP"
+        )));
+        assert!(!is_exportable(&entry(
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=60"
+        )));
+        assert!(!is_exportable(&entry(
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=7"
+        )));
+    }
+}
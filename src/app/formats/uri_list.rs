@@ -0,0 +1,11 @@
+//! Plain-text lists of otpauth URIs, one per line.
+
+use crate::app::entry::Entry;
+
+pub fn export(entries: &[Entry]) -> Vec<u8> {
+    entries
+        .iter()
+        .map(|entry| entry.uri() + "\n")
+        .collect::<String>()
+        .into_bytes()
+}
@@ -0,0 +1,180 @@
+//! Aegis icon packs, a zip of icons with a `pack.json` listing the issuers each icon is for.
+//!
+//! Packs are extracted into the app's data directory, and are matched against issuers before the
+//! bundled brand table.
+
+use std::{
+    io::Read,
+    path::{Component, Path, PathBuf},
+    sync::{LazyLock, RwLock},
+};
+
+use tracing::{info, warn};
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct Pack {
+    uuid: String,
+    name: String,
+    #[serde(default)]
+    icons: Vec<PackIcon>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct PackIcon {
+    filename: String,
+    #[serde(default)]
+    issuer: Vec<String>,
+}
+
+/// An installed pack, with the issuers it covers already normalised.
+struct Installed {
+    dir: PathBuf,
+    icons: Vec<(PathBuf, Vec<String>)>,
+}
+
+static INSTALLED: LazyLock<RwLock<Vec<Installed>>> = LazyLock::new(|| RwLock::new(load()));
+
+fn packs_dir() -> Result<PathBuf, String> {
+    Ok(super::icons::data_dir()?.join("packs"))
+}
+
+fn load_pack(dir: &Path) -> Result<Installed, String> {
+    let json = std::fs::read(dir.join("pack.json"))
+        .map_err(|e| format!("Couldn't read icon pack {dir:?}: {e}"))?;
+    let pack = serde_json::from_slice::<Pack>(&json)
+        .map_err(|e| format!("Couldn't parse icon pack {dir:?}: {e}"))?;
+
+    Ok(Installed {
+        dir: dir.to_path_buf(),
+        icons: pack
+            .icons
+            .into_iter()
+            .filter_map(|icon| {
+                let Some(name) = enclosed_name(&icon.filename) else {
+                    warn!("Skipping icon with unsafe name {}", icon.filename);
+                    return None;
+                };
+                Some((
+                    dir.join(name),
+                    icon.issuer
+                        .iter()
+                        .map(|i| super::brands::normalize(i))
+                        .collect(),
+                ))
+            })
+            .collect(),
+    })
+}
+
+/// A file name from `pack.json` as a path inside the pack, `None` if it would lead out of it.
+fn enclosed_name(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        .then(|| path.to_path_buf())
+}
+
+fn load() -> Vec<Installed> {
+    let Ok(entries) = packs_dir().and_then(|d| std::fs::read_dir(d).map_err(|e| e.to_string()))
+    else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|e| load_pack(&e.path()).inspect_err(|e| warn!("{e}")).ok())
+        .collect()
+}
+
+/// How many icon packs are installed.
+pub fn count() -> usize {
+    INSTALLED.read().map_or(0, |p| p.len())
+}
+
+/// The icon for an issuer from the installed packs.
+pub fn find(issuer: &str) -> Option<PathBuf> {
+    let issuer = super::brands::normalize(issuer);
+    if issuer.is_empty() {
+        return None;
+    }
+
+    INSTALLED.read().ok()?.iter().find_map(|pack| {
+        pack.icons
+            .iter()
+            .find(|(_, issuers)| issuers.contains(&issuer))
+            .map(|(path, _)| path.clone())
+    })
+}
+
+/// Asks for an icon pack and installs it, returning its name.
+pub async fn import() -> Result<Option<String>, String> {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_title("Import Icon Pack")
+        .add_filter("Aegis icon pack", &["zip"])
+        .pick_file()
+        .await
+    else {
+        return Ok(None);
+    };
+    let data = file.read().await;
+
+    tokio::task::spawn_blocking(move || install(&data))
+        .await
+        .map_err(|e| format!("Couldn't import icon pack: {e}"))?
+        .map(Some)
+}
+
+fn install(data: &[u8]) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+        .map_err(|e| format!("Invalid icon pack: {e}"))?;
+
+    let mut json = Vec::new();
+    archive
+        .by_name("pack.json")
+        .map_err(|e| format!("Icon pack has no pack.json: {e}"))?
+        .read_to_end(&mut json)
+        .map_err(|e| format!("Couldn't read pack.json: {e}"))?;
+    let pack = serde_json::from_slice::<Pack>(&json)
+        .map_err(|e| format!("Couldn't parse pack.json: {e}"))?;
+    if pack.uuid.is_empty() || !pack.uuid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err("Icon pack has an invalid UUID".into());
+    }
+
+    // Replaces any older version of the same pack
+    let dir = packs_dir()?.join(&pack.uuid);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("Couldn't remove old icon pack: {e}"))?;
+    }
+    for icon in &pack.icons {
+        let Ok(mut file) = archive.by_name(&icon.filename) else {
+            warn!("Icon pack is missing {}", icon.filename);
+            continue;
+        };
+        // Names like "../x" would escape the pack directory
+        let Some(name) = file.enclosed_name() else {
+            warn!("Skipping icon with unsafe name {}", icon.filename);
+            continue;
+        };
+        let path = dir.join(name);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .map_err(|e| format!("Couldn't read {}: {e}", icon.filename))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Couldn't create icon pack directory: {e}"))?;
+        }
+        std::fs::write(&path, contents).map_err(|e| format!("Couldn't store icon: {e}"))?;
+    }
+    std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(dir.join("pack.json"), &json))
+        .map_err(|e| format!("Couldn't store pack.json: {e}"))?;
+    info!("Installed icon pack {} to {dir:?}", pack.name);
+
+    let installed = load_pack(&dir)?;
+    if let Ok(mut packs) = INSTALLED.write() {
+        packs.retain(|p| p.dir != dir);
+        packs.push(installed);
+    }
+
+    Ok(pack.name)
+}
@@ -0,0 +1,295 @@
+//! Storage for entry icons that don't come from a user-picked file.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use tracing::info;
+
+/// Symbolic icons offered in the editor, the name can also be typed in for any other icon.
+pub const THEME_ICONS: &[&str] = &[
+    "mail-unread-symbolic",
+    "web-browser-symbolic",
+    "network-server-symbolic",
+    "network-workgroup-symbolic",
+    "folder-remote-symbolic",
+    "system-users-symbolic",
+    "user-home-symbolic",
+    "applications-games-symbolic",
+    "applications-development-symbolic",
+    "applications-office-symbolic",
+    "security-high-symbolic",
+    "starred-symbolic",
+];
+
+/// Emoji offered in the editor, any other can be typed in.
+pub const EMOJIS: &[&str] = &[
+    "🔑", "🔒", "🛡️", "📧", "💬", "🌐", "☁️", "💻", "🖥️", "📱", "🎮", "🎵", "🎬", "📷", "🛒", "💳",
+    "🏦", "💰", "📈", "🏠", "🏢", "🎓", "✈️", "🚗", "❤️", "⭐", "🔧", "🧪", "📦", "🐙",
+];
+
+/// Backgrounds offered for initials icons.
+pub const INITIALS_COLORS: [[u8; 3]; 10] = [
+    [0xe0, 0x1b, 0x24],
+    [0xff, 0x78, 0x00],
+    [0xf6, 0xd3, 0x2d],
+    [0x33, 0xd1, 0x7a],
+    [0x26, 0xa2, 0x69],
+    [0x2a, 0xa1, 0xb3],
+    [0x35, 0x84, 0xe4],
+    [0x91, 0x41, 0xac],
+    [0xc0, 0x61, 0xcb],
+    [0x86, 0x5e, 0x3c],
+];
+
+/// The starting value of [`fnv1a`].
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, as the standard hasher isn't guaranteed to be stable between releases, so anything
+/// derived from it could change with an update.
+fn fnv1a(hash: u64, bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(hash, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A background for initials picked from the name, so it stays the same across runs.
+pub fn initials_color(name: &str) -> [u8; 3] {
+    let hash = fnv1a(FNV_OFFSET, name.trim().to_lowercase().bytes());
+    INITIALS_COLORS[usize::try_from(hash % INITIALS_COLORS.len() as u64).unwrap_or_default()]
+}
+
+/// Largest icon that will be downloaded.
+const MAX_DOWNLOAD: usize = 4 * 1024 * 1024;
+
+/// Largest side of the image the crop preview is made from.
+const CROP_PREVIEW_SOURCE: u32 = 512;
+/// Edge length of the stored crop and its preview.
+const CROP_SIZE: u32 = 256;
+const CROP_PREVIEW_SIZE: u32 = 128;
+
+/// Edge length of cached thumbnails, enough for the largest icon on a 3x scaled display.
+const THUMBNAIL_SIZE: u32 = 128;
+
+pub fn data_dir() -> Result<PathBuf, String> {
+    dirs::data_dir()
+        .map(|d| d.join(crate::APP_ID).join("icons"))
+        .ok_or_else(|| "No data directory available".into())
+}
+
+/// Stores icon data in the app's data directory, named after its contents so the same icon is
+/// only stored once.
+pub fn store(data: &[u8], extension: &str) -> Result<PathBuf, String> {
+    let hash = fnv1a(FNV_OFFSET, data.iter().copied());
+
+    let dir = data_dir()?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Couldn't create icon directory: {e}"))?;
+    let path = dir.join(format!("{hash:016x}.{extension}"));
+    if !path.exists() {
+        info!("Storing icon at {path:?}");
+        std::fs::write(&path, data).map_err(|e| format!("Couldn't store icon: {e}"))?;
+    }
+
+    Ok(path)
+}
+
+/// Downloads an icon and stores it in the data directory, so it stays available offline.
+pub async fn download(url: String) -> Result<PathBuf, String> {
+    let url = url::Url::parse(url.trim()).map_err(|e| format!("Invalid icon URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Icon URL must be http or https".into());
+    }
+
+    info!("Downloading icon from {url}");
+    let response = reqwest::get(url.clone())
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| format!("Couldn't download icon: {e}"))?;
+    if response
+        .content_length()
+        .is_some_and(|l| l > MAX_DOWNLOAD as u64)
+    {
+        return Err("Icon is too large".into());
+    }
+    let is_svg = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|t| t.to_str().ok())
+        .is_some_and(|t| t.starts_with("image/svg"))
+        || url.path().ends_with(".svg");
+    let data = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Couldn't download icon: {e}"))?;
+    if data.len() > MAX_DOWNLOAD {
+        return Err("Icon is too large".into());
+    }
+
+    let extension = if is_svg {
+        "svg"
+    } else {
+        image::guess_format(&data)
+            .ok()
+            .and_then(|f| f.extensions_str().first().copied())
+            .ok_or("Downloaded file isn't a supported image")?
+    };
+    store(&data, extension)
+}
+
+fn thumbnail_dir() -> Result<PathBuf, String> {
+    dirs::cache_dir()
+        .map(|d| d.join(crate::APP_ID).join("thumbnails"))
+        .ok_or_else(|| "No cache directory available".into())
+}
+
+/// Returns a downscaled copy of the image at `path`, creating it in the cache directory if needed.
+///
+/// Decoding a large image takes a while, so it's done off the UI thread.
+pub async fn thumbnail(path: PathBuf) -> Result<PathBuf, String> {
+    tokio::task::spawn_blocking(move || create_thumbnail(&path))
+        .await
+        .map_err(|e| format!("Couldn't create thumbnail: {e}"))?
+}
+
+/// Thumbnails are keyed on the path, size and modification time of the source, so editing the
+/// source image produces a fresh thumbnail. Images already small enough are returned as is.
+fn create_thumbnail(path: &Path) -> Result<PathBuf, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| format!("Couldn't read icon: {e}"))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let hash = fnv1a(
+        FNV_OFFSET,
+        path.as_os_str().as_encoded_bytes().iter().copied(),
+    );
+    let hash = fnv1a(hash, metadata.len().to_le_bytes());
+    let hash = fnv1a(hash, modified.as_nanos().to_le_bytes());
+
+    let dir = thumbnail_dir()?;
+    let thumbnail = dir.join(format!("{hash:016x}.png"));
+    if thumbnail.exists() {
+        return Ok(thumbnail);
+    }
+
+    let image = image::ImageReader::open(path)
+        .and_then(image::ImageReader::with_guessed_format)
+        .map_err(|e| format!("Couldn't read icon: {e}"))?
+        .decode()
+        .map_err(|e| format!("Couldn't decode icon: {e}"))?;
+    if image.width() <= THUMBNAIL_SIZE && image.height() <= THUMBNAIL_SIZE {
+        return Ok(path.to_path_buf());
+    }
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Couldn't create thumbnail directory: {e}"))?;
+    // Cropped rather than fitted, as icons are drawn to cover a square
+    image
+        .resize_to_fill(
+            THUMBNAIL_SIZE,
+            THUMBNAIL_SIZE,
+            image::imageops::FilterType::Lanczos3,
+        )
+        .save_with_format(&thumbnail, image::ImageFormat::Png)
+        .map_err(|e| format!("Couldn't store thumbnail: {e}"))?;
+    info!("Stored thumbnail for {path:?} at {thumbnail:?}");
+
+    Ok(thumbnail)
+}
+
+/// A square part of an image being picked as an icon.
+#[derive(Debug, Clone)]
+pub struct Crop {
+    source: Arc<image::DynamicImage>,
+    /// A smaller copy of the source, so the preview keeps up with the sliders.
+    preview_source: Arc<image::DynamicImage>,
+    /// How far the crop is zoomed in from the largest square that fits, 1 or more.
+    pub zoom: f32,
+    /// Where the crop sits in the space left over, from 0 (left, top) to 1 (right, bottom).
+    pub x: f32,
+    pub y: f32,
+    pub preview: cosmic::widget::image::Handle,
+}
+impl Crop {
+    pub async fn load(path: PathBuf) -> Result<Self, String> {
+        tokio::task::spawn_blocking(move || {
+            let source = image::ImageReader::open(&path)
+                .and_then(image::ImageReader::with_guessed_format)
+                .map_err(|e| format!("Couldn't read icon: {e}"))?
+                .decode()
+                .map_err(|e| format!("Couldn't decode icon: {e}"))?;
+            let preview_source = if source.width().max(source.height()) > CROP_PREVIEW_SOURCE {
+                source.resize(
+                    CROP_PREVIEW_SOURCE,
+                    CROP_PREVIEW_SOURCE,
+                    image::imageops::FilterType::Triangle,
+                )
+            } else {
+                source.clone()
+            };
+
+            let mut crop = Self {
+                source: Arc::new(source),
+                preview_source: Arc::new(preview_source),
+                zoom: 1.0,
+                x: 0.5,
+                y: 0.5,
+                preview: cosmic::widget::image::Handle::from_rgba(0, 0, Vec::new()),
+            };
+            crop.update_preview();
+            Ok(crop)
+        })
+        .await
+        .map_err(|e| format!("Couldn't load icon: {e}"))?
+    }
+
+    /// The square to keep out of an image of the given size, as its corner and side length.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn region(&self, width: u32, height: u32) -> (u32, u32, u32) {
+        let side = ((width.min(height) as f32 / self.zoom.max(1.0)).round() as u32).max(1);
+        let x = ((width - side) as f32 * self.x.clamp(0.0, 1.0)).round() as u32;
+        let y = ((height - side) as f32 * self.y.clamp(0.0, 1.0)).round() as u32;
+        (x, y, side)
+    }
+
+    fn cropped(&self, image: &image::DynamicImage, size: u32) -> image::DynamicImage {
+        let (x, y, side) = self.region(image.width(), image.height());
+        let cropped = image.crop_imm(x, y, side, side);
+        if side > size {
+            cropped.resize_exact(size, size, image::imageops::FilterType::Lanczos3)
+        } else {
+            cropped
+        }
+    }
+
+    pub fn update_preview(&mut self) {
+        let preview = self
+            .cropped(&self.preview_source, CROP_PREVIEW_SIZE)
+            .into_rgba8();
+        self.preview = cosmic::widget::image::Handle::from_rgba(
+            preview.width(),
+            preview.height(),
+            preview.into_raw(),
+        );
+    }
+
+    /// Stores the cropped image in the data directory.
+    pub async fn store(self) -> Result<PathBuf, String> {
+        tokio::task::spawn_blocking(move || {
+            let mut data = std::io::Cursor::new(Vec::new());
+            self.cropped(&self.source, CROP_SIZE)
+                .write_to(&mut data, image::ImageFormat::Png)
+                .map_err(|e| format!("Couldn't encode icon: {e}"))?;
+            store(data.get_ref(), "png")
+        })
+        .await
+        .map_err(|e| format!("Couldn't store icon: {e}"))?
+    }
+}
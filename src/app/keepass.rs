@@ -0,0 +1,64 @@
+//! Read-only entries from a KeePassXC database's `otp` attributes.
+
+use std::path::PathBuf;
+
+use tracing::{info, warn};
+
+use super::entry::Entry;
+
+pub async fn pick_database() -> Option<PathBuf> {
+    rfd::AsyncFileDialog::new()
+        .set_title("KeePassXC Database")
+        .add_filter("KeePass Database", &["kdbx"])
+        .pick_file()
+        .await
+        .map(|f| f.path().to_path_buf())
+}
+
+pub async fn open(path: PathBuf, password: String) -> Result<Vec<Entry>, String> {
+    tokio::task::spawn_blocking(move || {
+        info!("Opening KeePassXC database {path:?}");
+        let mut file =
+            std::fs::File::open(&path).map_err(|e| format!("Couldn't open database: {e}"))?;
+        let db = keepass::Database::open(
+            &mut file,
+            keepass::DatabaseKey::new().with_password(&password),
+        )
+        .map_err(|e| format!("Couldn't unlock database: {e}"))?;
+
+        let mut entries = Vec::new();
+        collect(&db.root, &mut entries);
+        Ok(entries)
+    })
+    .await
+    .map_err(|e| format!("Couldn't join database thread: {e}"))?
+}
+
+fn collect(group: &keepass::db::Group, entries: &mut Vec<Entry>) {
+    for node in &group.children {
+        match node {
+            keepass::db::Node::Group(child) => collect(child, entries),
+            keepass::db::Node::Entry(kp) => {
+                let Some(otp) = kp.get("otp") else {
+                    continue;
+                };
+                let title = kp.get_title().unwrap_or_default();
+                match Entry::from_uri(otp) {
+                    Ok(mut entry) => {
+                        if entry.totp.issuer.is_none() {
+                            entry.totp.issuer = Some(title.to_string()).filter(|s| !s.is_empty());
+                        }
+                        if entry.totp.account_name.is_empty() {
+                            entry.totp.account_name =
+                                kp.get_username().unwrap_or(title).to_string();
+                        }
+                        entry.group = Some(group.name.clone());
+                        entry.recalc_icon();
+                        entries.push(entry);
+                    }
+                    Err(e) => warn!("Skipping KeePassXC entry {title}: {e}"),
+                }
+            }
+        }
+    }
+}
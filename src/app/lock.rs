@@ -0,0 +1,201 @@
+//! An optional lock over the popup, independent of the keyring.
+//!
+//! While locked the vault isn't read from the keyring at all, so nothing is decrypted or shown
+//! until the passphrase or PIN has been checked against the argon2 hash in the settings.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+
+/// The fewest digits a PIN can have.
+pub const MIN_PIN_LENGTH: usize = 4;
+
+/// Wrong attempts allowed in a row before attempts are slowed down.
+const FREE_ATTEMPTS: u32 = 3;
+/// The wait after the first attempt past [`FREE_ATTEMPTS`], doubling with every further one.
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+/// The most doublings of [`BASE_BACKOFF`], capping the wait at a bit over five minutes.
+const MAX_DOUBLINGS: u32 = 6;
+
+/// What the popup is unlocked with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum LockKind {
+    #[default]
+    Passphrase,
+    /// Digits only, entered on a keypad.
+    Pin,
+}
+impl LockKind {
+    pub const ALL: [Self; 2] = [Self::Passphrase, Self::Pin];
+    pub const LABELS: [&str; 2] = ["Passphrase", "PIN"];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Passphrase => "passphrase",
+            Self::Pin => "PIN",
+        }
+    }
+
+    /// Drops anything that can't be part of this kind of secret from typed input.
+    pub fn filter(self, mut input: String) -> String {
+        if self == Self::Pin {
+            input.retain(|c| c.is_ascii_digit());
+        }
+        input
+    }
+}
+
+/// A new passphrase or PIN and its confirmation, while the lock is being set up.
+pub struct Setup {
+    pub kind: LockKind,
+    pub secret: String,
+    pub confirm: String,
+}
+impl Setup {
+    pub const fn new(kind: LockKind) -> Self {
+        Self {
+            kind,
+            secret: String::new(),
+            confirm: String::new(),
+        }
+    }
+
+    /// Why the new secret can't be saved yet.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self.kind {
+            LockKind::Passphrase if self.secret.is_empty() => Err("Enter a passphrase"),
+            LockKind::Pin if self.secret.len() < MIN_PIN_LENGTH => {
+                Err("A PIN needs at least 4 digits")
+            }
+            LockKind::Passphrase if self.secret != self.confirm => Err("Passphrases don't match"),
+            LockKind::Pin if self.secret != self.confirm => Err("PINs don't match"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The unlock prompt shown instead of the popup's contents.
+pub struct Prompt {
+    /// The hash the input is checked against, kept in case the lock is removed from outside the
+    /// app while it's locked.
+    pub hash: String,
+    pub input: String,
+    /// Whether the input is being checked.
+    pub checking: bool,
+    /// Wrong attempts in a row.
+    pub failures: u32,
+    /// When another attempt is allowed, after too many wrong attempts.
+    pub retry_at: Option<Instant>,
+    pub error: Option<String>,
+}
+impl Prompt {
+    /// A prompt carrying on after `failures` wrong attempts, with the next one allowed from
+    /// `retry_at` in Unix seconds, as saved by an earlier run.
+    pub fn new(hash: String, failures: u32, retry_at: u64) -> Self {
+        let wait = Duration::from_secs(retry_at.saturating_sub(unix_time()));
+        Self {
+            hash,
+            input: String::new(),
+            checking: false,
+            failures,
+            retry_at: (!wait.is_zero()).then(|| Instant::now() + wait),
+            error: (!wait.is_zero()).then(|| retry_message(wait)),
+        }
+    }
+
+    /// Whether the input can be checked now.
+    pub fn can_submit(&self) -> bool {
+        !self.checking && self.retry_at.is_none_or(|at| at <= Instant::now())
+    }
+
+    /// Records a wrong passphrase or PIN, returning how long to wait before the next attempt.
+    pub fn fail(&mut self, kind: LockKind) -> Duration {
+        self.checking = false;
+        self.failures += 1;
+        let wait = backoff(self.failures);
+        if wait.is_zero() {
+            self.error = Some(format!("Wrong {}", kind.name()));
+        } else {
+            self.retry_at = Some(Instant::now() + wait);
+            self.error = Some(retry_message(wait));
+        }
+        wait
+    }
+}
+
+fn retry_message(wait: Duration) -> String {
+    format!(
+        "Too many wrong attempts, try again in {} seconds",
+        wait.as_secs()
+    )
+}
+
+/// The current time in Unix seconds, which the next allowed attempt is saved in.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// How long to wait before another attempt after `failures` wrong attempts in a row.
+pub fn backoff(failures: u32) -> Duration {
+    match failures.checked_sub(FREE_ATTEMPTS) {
+        None => Duration::ZERO,
+        Some(extra) => BASE_BACKOFF * 2u32.pow(extra.min(MAX_DOUBLINGS)),
+    }
+}
+
+/// Hashes a new passphrase or PIN, returning the hash in PHC string format.
+pub async fn hash(passphrase: String) -> Result<String, String> {
+    // Hashing is deliberately slow, so it's kept off the UI thread
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(passphrase.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| format!("Couldn't hash passphrase: {e}"))
+    })
+    .await
+    .map_err(|e| format!("Couldn't hash passphrase: {e}"))?
+}
+
+/// Checks a passphrase or PIN against a hash from [`hash`].
+pub async fn verify(passphrase: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(passphrase.as_bytes(), &hash)
+                .is_ok()
+        })
+    })
+    .await
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::backoff;
+
+    #[test]
+    fn backoff_starts_after_free_attempts() {
+        for failures in 0..=3 {
+            assert_eq!(backoff(failures), Duration::ZERO);
+        }
+        assert_eq!(backoff(4), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_cap() {
+        assert_eq!(backoff(5), Duration::from_secs(10));
+        assert_eq!(backoff(6), Duration::from_secs(20));
+        assert_eq!(backoff(9), Duration::from_secs(320));
+        assert_eq!(backoff(10), Duration::from_secs(320));
+        assert_eq!(backoff(u32::MAX), Duration::from_secs(320));
+    }
+}
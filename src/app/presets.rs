@@ -0,0 +1,46 @@
+//! Known parameters for issuers that don't use the usual 6 digit, 30 second SHA1 codes, or that
+//! are common enough to be worth picking from a list.
+
+pub struct Preset {
+    pub issuer: &'static str,
+    pub algorithm: totp_rs::Algorithm,
+    pub digits: usize,
+    pub step: u64,
+}
+impl AsRef<str> for Preset {
+    fn as_ref(&self) -> &str {
+        self.issuer
+    }
+}
+
+const fn standard(issuer: &'static str) -> Preset {
+    Preset {
+        issuer,
+        algorithm: totp_rs::Algorithm::SHA1,
+        digits: 6,
+        step: 30,
+    }
+}
+
+pub const PRESETS: [Preset; 10] = [
+    standard("Amazon"),
+    Preset {
+        issuer: "Battle.net",
+        algorithm: totp_rs::Algorithm::SHA1,
+        digits: 8,
+        step: 30,
+    },
+    standard("Discord"),
+    standard("Dropbox"),
+    standard("GitHub"),
+    standard("Google"),
+    standard("Microsoft"),
+    standard("Proton"),
+    Preset {
+        issuer: "Steam",
+        algorithm: totp_rs::Algorithm::Steam,
+        digits: 5,
+        step: 30,
+    },
+    standard("Twitter"),
+];
@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use tracing::{info, warn};
+
+/// Lets the user pick an image file and decodes every QR code in it.
+///
+/// Returns an empty list if the user cancelled the file picker.
+pub async fn scan_file() -> Result<Vec<String>, String> {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_title("QR Code Image")
+        .add_filter("Images", &["png", "jpg", "jpeg", "gif", "bmp", "webp"])
+        .pick_file()
+        .await
+    else {
+        info!("User cancelled QR image selection");
+        return Ok(Vec::new());
+    };
+
+    decode_file(file.path().to_path_buf()).await
+}
+
+/// Takes a screenshot through the desktop portal and decodes every QR code on screen.
+///
+/// The screenshot file is removed afterwards, as it will usually contain the secret.
+pub async fn scan_screen() -> Result<Vec<String>, String> {
+    info!("Requesting screenshot");
+    let screenshot = ashpd::desktop::screenshot::Screenshot::request()
+        .interactive(false)
+        .modal(false)
+        .send()
+        .await
+        .and_then(|r| r.response())
+        .map_err(|e| format!("Couldn't take screenshot: {e}"))?;
+    let path = screenshot
+        .uri()
+        .to_file_path()
+        .map_err(|()| format!("Screenshot {} is not a local file", screenshot.uri()))?;
+
+    let codes = decode_file(path.clone()).await;
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        warn!("Couldn't remove screenshot {path:?}: {e}");
+    }
+
+    codes
+}
+
+pub async fn decode_file(path: PathBuf) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || {
+        info!("Decoding QR codes from {path:?}");
+        let image = image::open(&path)
+            .map_err(|e| format!("Couldn't open image: {e}"))?
+            .to_luma8();
+        decode(&image)
+    })
+    .await
+    .map_err(|e| format!("Couldn't join QR decoding thread: {e}"))?
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn decode(image: &image::GrayImage) -> Result<Vec<String>, String> {
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+
+    let grids = prepared.detect_grids();
+    let found = grids.len();
+    // One damaged or unrelated code shouldn't hide the others in the image
+    let codes: Vec<_> = grids
+        .into_iter()
+        .map(|grid| grid.decode().map(|(_, content)| content))
+        .filter_map(Result::ok)
+        .collect();
+
+    if found == 0 {
+        return Err("No QR codes found in image".into());
+    }
+    if codes.is_empty() {
+        return Err("Couldn't decode the QR codes in the image".into());
+    }
+
+    info!("Decoded {} QR codes", codes.len());
+    Ok(codes)
+}
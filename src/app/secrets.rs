@@ -1,37 +1,84 @@
-use tracing::{info, warn};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use aes_gcm::{
+    Aes256Gcm,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use tracing::{error, info, warn};
 
 use super::entry::Entry;
 
+mod file;
+pub mod migrations;
+mod os_keyring;
+mod recovery;
+mod secret_service;
+
+use file::Files;
+use os_keyring::OsKeyring;
+pub use recovery::Recovery;
+use secret_service::SecretService;
+
 #[derive(Debug, Clone)]
 pub enum State {
     PendingUser,
+    /// The vault is encrypted with a passphrase that hasn't been entered yet.
+    Encrypted,
+    /// The keyring collection holding the vault is locked.
+    KeyringLocked,
+    /// The vault couldn't be read, and nothing is written until the user has seen what's left.
+    Corrupt(Recovery),
     Secrets(Vec<Entry>),
 }
 impl State {
     pub fn as_mut_array(&mut self) -> &mut [Entry] {
         match self {
-            Self::PendingUser => &mut [],
+            Self::PendingUser | Self::Encrypted | Self::KeyringLocked | Self::Corrupt(_) => &mut [],
             Self::Secrets(items) => &mut *items,
         }
     }
     pub fn as_array(&self) -> &[Entry] {
         match self {
-            Self::PendingUser => &[],
+            Self::PendingUser | Self::Encrypted | Self::KeyringLocked | Self::Corrupt(_) => &[],
             Self::Secrets(items) => items,
         }
     }
 
-    pub fn delete(&mut self, idx: usize) {
-        if let State::Secrets(items) = self {
-            items.remove(idx);
+    pub fn delete(&mut self, idx: usize) -> Option<Entry> {
+        match self {
+            Self::Secrets(items) if idx < items.len() => Some(items.remove(idx)),
+            _ => None,
+        }
+    }
+
+    #[expect(clippy::result_large_err)]
+    pub fn try_insert(&mut self, idx: usize, mut entry: Entry) -> Result<(), Entry> {
+        match self {
+            Self::PendingUser | Self::Encrypted | Self::KeyringLocked | Self::Corrupt(_) => {
+                Err(entry)
+            }
+            Self::Secrets(items) => {
+                make_id_unique(items, &mut entry);
+                items.insert(idx.min(items.len()), entry);
+                Ok(())
+            }
         }
     }
 
     #[expect(clippy::result_large_err)]
-    pub fn try_push(&mut self, entry: Entry) -> Result<(), Entry> {
+    pub fn try_push(&mut self, mut entry: Entry) -> Result<(), Entry> {
         match self {
-            Self::PendingUser => Err(entry),
+            Self::PendingUser | Self::Encrypted | Self::KeyringLocked | Self::Corrupt(_) => {
+                Err(entry)
+            }
             Self::Secrets(items) => {
+                make_id_unique(items, &mut entry);
                 items.push(entry);
                 Ok(())
             }
@@ -39,42 +86,828 @@ impl State {
     }
 }
 
-pub async fn get_secret_key(username: String) -> Result<State, String> {
-    let data = tokio::task::spawn_blocking(move || {
-        info!("Requesting secrets");
-        let entry = keyring::Entry::new(crate::APP_ID, &username).map_err(|e| e.to_string())?;
-        Ok(match entry.get_secret() {
-            Ok(secr) => serde_json::from_slice(&secr)
-                .map_err(|e| format!("Couldn't deserialise secret store: {e}"))?,
-            Err(keyring::Error::NoEntry) => {
-                warn!("No entry in secret store, defaulting to empty");
-                Vec::new()
+/// Where the vault is kept.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+pub enum Storage {
+    /// The kernel's keyring, through the keyring crate.
+    #[default]
+    Keyring,
+    /// The Secret Service directly, rather than through the generic keyring.
+    SecretService,
+    /// The Secret Service as provided by KWallet, in its default wallet unless another is named.
+    KWallet,
+    /// Files in the app's data directory, for systems without a Secret Service.
+    ///
+    /// Nothing else encrypts these, so the vault has to be encrypted with a passphrase or the TPM
+    /// before any entries are written.
+    File,
+}
+impl Storage {
+    pub const ALL: [Self; 4] = [
+        Self::Keyring,
+        Self::SecretService,
+        Self::KWallet,
+        Self::File,
+    ];
+    pub const LABELS: [&str; 4] = [
+        "Kernel keyring",
+        "Secret Service",
+        "KWallet",
+        "Encrypted file",
+    ];
+
+    /// Whether the storage encrypts the vault itself, so it doesn't need a key of its own.
+    pub const fn encrypts(self) -> bool {
+        !matches!(self, Self::File)
+    }
+
+    /// Whether items are kept in a Secret Service collection.
+    pub const fn has_collection(self) -> bool {
+        matches!(self, Self::SecretService | Self::KWallet)
+    }
+}
+
+/// Failed reads and writes are retried this many times, as the keyring may not be up yet when
+/// the session starts.
+const RETRIES: u32 = 5;
+/// The wait before the first retry, doubling with every further one.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How long to wait before retrying after `attempts` failed reads or writes in a row, `None` once
+/// it's time to give up.
+pub fn retry_delay(attempts: u32) -> Option<Duration> {
+    (1..=RETRIES)
+        .contains(&attempts)
+        .then(|| RETRY_BACKOFF * 2u32.pow(attempts - 1))
+}
+
+/// The wallet KWallet opens by default.
+const KWALLET_COLLECTION: &str = "kdewallet";
+
+/// Where the vault is kept, and the names it's kept under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub storage: Storage,
+    /// The keyring service or Secret Service application the items belong to, the app id when
+    /// empty.
+    pub service: String,
+    /// The label of the Secret Service collection, the default collection when empty.
+    pub collection: String,
+}
+
+/// The vault as read from storage.
+#[derive(Debug, Clone)]
+pub struct Loaded {
+    pub state: State,
+    /// The vault's key, if it's bound to the TPM.
+    pub key: Option<VaultKey>,
+}
+
+/// Why the vault can't be read when its keyring can't be reached, which is usually because it
+/// hasn't started yet, so it's worth trying again before choosing to use a file instead.
+pub const NO_KEYRING: &str = "The keyring can't be reached";
+
+/// One of the items making up a user's vault.
+#[derive(Debug, Clone, Copy)]
+pub enum Item<'a> {
+    /// The [`Index`] listing the entries.
+    Index,
+    /// An entry, by the name of its item from [`item_name`].
+    Entry(&'a str),
+}
+
+/// Somewhere a user's vault can be kept.
+///
+/// Backends only store opaque items, the index, serialisation and extra encryption work the same
+/// way for all of them.
+pub trait VaultBackend: Send + Sync {
+    /// Reads an item, `None` if it doesn't exist.
+    fn load(&self, item: Item<'_>) -> impl Future<Output = Result<Option<Vec<u8>>, String>> + Send;
+    fn save(
+        &self,
+        item: Item<'_>,
+        data: Vec<u8>,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+    /// Removes an item, which isn't an error if it's already gone.
+    fn delete(&self, item: Item<'_>) -> impl Future<Output = Result<(), String>> + Send;
+    /// Whether items are encrypted at rest, otherwise the vault needs its own key before any
+    /// entries are written.
+    fn encrypts(&self) -> bool;
+
+    /// Whether the items can't be read until the store is unlocked.
+    fn is_locked(&self) -> impl Future<Output = Result<bool, String>> + Send {
+        async { Ok(false) }
+    }
+    /// Asks the store to unlock, which may show the user a prompt.
+    fn unlock(&self) -> impl Future<Output = Result<(), String>> + Send {
+        async { Ok(()) }
+    }
+    /// The users with a vault in the same store, empty if the store can't be searched.
+    fn users(&self) -> impl Future<Output = Result<Vec<String>, String>> + Send {
+        async { Ok(Vec::new()) }
+    }
+}
+
+/// The backend chosen with [`Storage`].
+enum Backend {
+    Keyring(OsKeyring),
+    SecretService(SecretService),
+    File(Files),
+}
+impl Backend {
+    fn new(location: &Location, username: &str) -> Result<Self, String> {
+        let service = match location.service.trim() {
+            "" => crate::APP_ID,
+            service => service,
+        }
+        .to_string();
+        let collection = match location.collection.trim() {
+            "" if location.storage == Storage::KWallet => Some(KWALLET_COLLECTION.to_string()),
+            "" => None,
+            collection => Some(collection.to_string()),
+        };
+        let username = username.to_string();
+        Ok(match location.storage {
+            Storage::Keyring => Self::Keyring(OsKeyring::new(username, service)),
+            Storage::SecretService | Storage::KWallet => {
+                Self::SecretService(SecretService::new(username, service, collection))
             }
-            Err(e) => return Err(e.to_string()),
+            Storage::File => Self::File(Files::new(&username)?),
         })
-    })
-    .await
-    .map_err(|e| format!("Couldn't join secret retrieving thread: {e}"))??;
+    }
 
-    info!("Retrieved secret key");
-    Ok(State::Secrets(data))
+    /// Whether the backend can be reached at all.
+    async fn available(&self) -> bool {
+        match self {
+            Self::Keyring(backend) => backend.available().await,
+            Self::SecretService(_) => SecretService::available().await,
+            Self::File(_) => true,
+        }
+    }
 }
+impl VaultBackend for Backend {
+    async fn load(&self, item: Item<'_>) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Self::Keyring(backend) => backend.load(item).await,
+            Self::SecretService(backend) => backend.load(item).await,
+            Self::File(backend) => backend.load(item).await,
+        }
+    }
+
+    async fn save(&self, item: Item<'_>, data: Vec<u8>) -> Result<(), String> {
+        match self {
+            Self::Keyring(backend) => backend.save(item, data).await,
+            Self::SecretService(backend) => backend.save(item, data).await,
+            Self::File(backend) => backend.save(item, data).await,
+        }
+    }
+
+    async fn delete(&self, item: Item<'_>) -> Result<(), String> {
+        match self {
+            Self::Keyring(backend) => backend.delete(item).await,
+            Self::SecretService(backend) => backend.delete(item).await,
+            Self::File(backend) => backend.delete(item).await,
+        }
+    }
+
+    fn encrypts(&self) -> bool {
+        match self {
+            Self::Keyring(backend) => backend.encrypts(),
+            Self::SecretService(backend) => backend.encrypts(),
+            Self::File(backend) => backend.encrypts(),
+        }
+    }
+
+    async fn is_locked(&self) -> Result<bool, String> {
+        match self {
+            Self::Keyring(backend) => backend.is_locked().await,
+            Self::SecretService(backend) => backend.is_locked().await,
+            Self::File(backend) => backend.is_locked().await,
+        }
+    }
 
-pub async fn set_secret_key(username: String, secret: Vec<Entry>) -> Result<(), String> {
-    tokio::task::spawn_blocking(move || {
-        info!("Setting secrets");
-        let entry = keyring::Entry::new(crate::APP_ID, &username).map_err(|e| e.to_string())?;
-        let ser = serde_json::to_string(&secret)
-            .map_err(|e| format!("Failed to serialise secrets: {e}"))?;
+    async fn unlock(&self) -> Result<(), String> {
+        match self {
+            Self::Keyring(backend) => backend.unlock().await,
+            Self::SecretService(backend) => backend.unlock().await,
+            Self::File(backend) => backend.unlock().await,
+        }
+    }
 
-        match entry.set_secret(ser.as_bytes()) {
-            Ok(attrs) => attrs,
-            Err(e) => return Err(e.to_string()),
+    async fn users(&self) -> Result<Vec<String>, String> {
+        match self {
+            Self::Keyring(backend) => backend.users().await,
+            Self::SecretService(backend) => backend.users().await,
+            Self::File(backend) => backend.users().await,
         }
-        Ok(())
+    }
+}
+
+/// The name of the item an entry is stored in.
+///
+/// Entries written again with a new key or format go to new items, and the index only points at
+/// them once they've all been written, so a save that fails halfway leaves the vault as it was.
+fn item_name(id: &str, generation: u32) -> String {
+    if generation == 0 {
+        id.to_string()
+    } else {
+        format!("{id}.{generation}")
+    }
+}
+
+/// Entries restored from a backup of this same vault would otherwise share a keyring item.
+fn make_id_unique(items: &[Entry], entry: &mut Entry) {
+    if items.iter().any(|e| e.id == entry.id) {
+        entry.id = super::entry::random_id();
+    }
+}
+
+/// The item under the user's name, listing the ids of their entries.
+///
+/// Each entry is kept in its own item, so saving one doesn't rewrite the others and a corrupt
+/// item only loses a single entry.
+#[derive(Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+struct Index {
+    /// The format the entries are stored in, 0 for vaults saved before it was tracked.
+    #[serde(default)]
+    version: u32,
+    /// Bumped whenever every entry is written again, see [`item_name`]. Kept ahead of the
+    /// entries so it survives an index that's cut short.
+    #[serde(default)]
+    generation: u32,
+    entries: Vec<String>,
+    /// Set when the entries are encrypted with a passphrase on top of the keyring's own encryption.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption: Option<Encryption>,
+    /// Set when the entries are encrypted with a key sealed against this machine's TPM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tpm: Option<Sealed>,
+}
+
+/// A vault key sealed against the TPM, as the base64 encoded parts of the sealed object.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+struct Sealed {
+    public: String,
+    private: String,
+}
+
+/// Where the key of an encrypted vault comes from.
+#[derive(Clone)]
+enum KeySource {
+    Passphrase(Encryption),
+    #[cfg(feature = "tpm")]
+    Tpm(Sealed),
+}
+
+/// How the key for an encrypted vault is derived from its passphrase.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+struct Encryption {
+    /// The argon2id salt, base64 encoded.
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// [`CHECK`] encrypted with the key, so a wrong passphrase is caught before reading entries.
+    check: String,
+}
+
+const CHECK: &[u8] = b"cosmic-totp-client";
+/// The length of the AES-GCM nonce stored in front of each encrypted entry.
+const NONCE_LEN: usize = 12;
+
+/// The key of an encrypted vault, along with how it was derived.
+#[derive(Clone)]
+pub struct VaultKey {
+    source: KeySource,
+    key: [u8; 32],
+}
+impl std::fmt::Debug for VaultKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultKey").finish_non_exhaustive()
+    }
+}
+impl VaultKey {
+    /// Derives a key for a vault that's about to be encrypted, with a fresh salt.
+    pub async fn new(passphrase: String) -> Result<Self, String> {
+        tokio::task::spawn_blocking(move || {
+            let params = argon2::Params::default();
+            let mut salt = [0; 16];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive_key(&passphrase, &salt, &params)?;
+            let check = encrypt(&key, CHECK)?;
+            Ok(Self {
+                source: KeySource::Passphrase(Encryption {
+                    salt: BASE64.encode(salt),
+                    m_cost: params.m_cost(),
+                    t_cost: params.t_cost(),
+                    p_cost: params.p_cost(),
+                    check: BASE64.encode(check),
+                }),
+                key,
+            })
+        })
+        .await
+        .map_err(|e| format!("Couldn't join key derivation thread: {e}"))?
+    }
+
+    /// Derives the key of an existing vault, failing if the passphrase is wrong.
+    fn unlock(passphrase: &str, encryption: Encryption) -> Result<Self, String> {
+        let salt = BASE64
+            .decode(&encryption.salt)
+            .map_err(|e| format!("Invalid vault salt: {e}"))?;
+        let params = argon2::Params::new(
+            encryption.m_cost,
+            encryption.t_cost,
+            encryption.p_cost,
+            Some(32),
+        )
+        .map_err(|e| format!("Invalid vault key parameters: {e}"))?;
+        let key = derive_key(passphrase, &salt, &params)?;
+        let check = BASE64
+            .decode(&encryption.check)
+            .map_err(|e| format!("Invalid vault check: {e}"))?;
+        if decrypt(&key, &check).is_err() {
+            return Err("Wrong vault passphrase".into());
+        }
+
+        Ok(Self {
+            source: KeySource::Passphrase(encryption),
+            key,
+        })
+    }
+
+    /// Generates a key for a vault that's about to be bound to this machine's TPM.
+    #[cfg(feature = "tpm")]
+    pub async fn sealed() -> Result<Self, String> {
+        tokio::task::spawn_blocking(|| {
+            let mut key = [0; 32];
+            OsRng.fill_bytes(&mut key);
+            let (public, private) = super::tpm::seal(&key)?;
+            Ok(Self {
+                source: KeySource::Tpm(Sealed {
+                    public: BASE64.encode(public),
+                    private: BASE64.encode(private),
+                }),
+                key,
+            })
+        })
+        .await
+        .map_err(|e| format!("Couldn't join key sealing thread: {e}"))?
+    }
+
+    /// Recovers the key of a vault bound to this machine's TPM.
+    fn unseal(sealed: Sealed) -> Result<Self, String> {
+        #[cfg(feature = "tpm")]
+        {
+            let decode = |part: &str| {
+                BASE64
+                    .decode(part)
+                    .map_err(|e| format!("Invalid sealed key: {e}"))
+            };
+            let key = super::tpm::unseal(&decode(&sealed.public)?, &decode(&sealed.private)?)?;
+            Ok(Self {
+                source: KeySource::Tpm(sealed),
+                key,
+            })
+        }
+        #[cfg(not(feature = "tpm"))]
+        {
+            drop(sealed);
+            Err("The vault is bound to a TPM, but this build has no TPM support".into())
+        }
+    }
+
+    /// Whether the key is sealed against the TPM rather than derived from a passphrase.
+    pub const fn is_sealed(&self) -> bool {
+        match self.source {
+            KeySource::Passphrase(_) => false,
+            #[cfg(feature = "tpm")]
+            KeySource::Tpm(_) => true,
+        }
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: &argon2::Params) -> Result<[u8; 32], String> {
+    let mut key = [0; 32];
+    argon2::Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        params.clone(),
+    )
+    .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+    .map_err(|e| format!("Couldn't derive vault key: {e}"))?;
+
+    Ok(key)
+}
+
+/// Encrypts with a fresh nonce, which is put in front of the ciphertext.
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Aes256Gcm::generate_nonce(OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Encryption failed")?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_LEN {
+        return Err("Encrypted entry is too short".into());
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    Aes256Gcm::new(key.into())
+        .decrypt(aes_gcm::Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Decryption failed".into())
+}
+
+/// An entry serialised for the keyring, so saving doesn't need its own copy of the vault.
+pub struct Serialized {
+    id: String,
+    json: Vec<u8>,
+}
+
+pub fn serialize<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Result<Vec<Serialized>, String> {
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(Serialized {
+                id: entry.id.clone(),
+                json: serde_json::to_vec(entry)
+                    .map_err(|e| format!("Failed to serialise secrets: {e}"))?,
+            })
+        })
+        .collect()
+}
+
+/// Digests of the entries as they are in storage, so unchanged ones aren't written again, by
+/// where they're stored, their user and their id.
+static SAVED: LazyLock<Mutex<HashMap<(Location, String, String), u64>>> =
+    LazyLock::new(Mutex::default);
+
+fn saved() -> MutexGuard<'static, HashMap<(Location, String, String), u64>> {
+    // It's only a cache, at worst an entry is written again
+    SAVED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn saved_key(location: &Location, username: &str, id: &str) -> (Location, String, String) {
+    (location.clone(), username.to_string(), id.to_string())
+}
+
+/// Forgets what's in storage for a user's vault, as it may change while the vault isn't open.
+async fn forget_saved(location: &Location, username: &str) {
+    let _writing = WRITING.lock().await;
+    saved().retain(|(saved_location, saved_user, _), _| {
+        saved_location != location || saved_user != username
+    });
+}
+
+fn digest(json: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Only one save is written at a time, so they can't interleave their items.
+static WRITING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Reads an entry stored in `version` of the format, upgrading it to the current one.
+pub fn parse_entry(version: u32, entry: serde_json::Value) -> Result<Entry, String> {
+    serde_json::from_value(migrations::upgrade(version, entry)?)
+        .map_err(|e| format!("Couldn't deserialise: {e}"))
+}
+
+async fn read_entry(
+    backend: &impl VaultBackend,
+    location: &Location,
+    username: &str,
+    id: &str,
+    generation: u32,
+    version: u32,
+    key: Option<&VaultKey>,
+) -> Result<Entry, String> {
+    let mut secr = backend
+        .load(Item::Entry(&item_name(id, generation)))
+        .await?
+        .ok_or("Entry is missing")?;
+    if let Some(key) = key {
+        secr = decrypt(&key.key, &secr)?;
+    }
+    let entry = serde_json::from_slice(&secr).map_err(|e| format!("Couldn't deserialise: {e}"))?;
+    let entry = parse_entry(version, entry)?;
+    saved().insert(saved_key(location, username, id), digest(&secr));
+    Ok(entry)
+}
+
+async fn read_entries(
+    backend: &impl VaultBackend,
+    location: &Location,
+    username: &str,
+    index: &Index,
+    key: Option<&VaultKey>,
+) -> Vec<Entry> {
+    let mut entries = Vec::with_capacity(index.entries.len());
+    for id in &index.entries {
+        match read_entry(
+            backend,
+            location,
+            username,
+            id,
+            index.generation,
+            index.version,
+            key,
+        )
+        .await
+        {
+            Ok(entry) => entries.push(entry),
+            Err(e) => error!("Couldn't read entry {id}, leaving it stored: {e}"),
+        }
+    }
+    entries
+}
+
+/// Runs key derivation, which is deliberately slow, off the async runtime.
+async fn derive<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| format!("Couldn't join key derivation thread: {e}"))?
+}
+
+/// Reads the vault.
+pub async fn get_secret_key(username: String, location: Location) -> Result<Loaded, String> {
+    info!("Requesting secrets");
+    let backend = Backend::new(&location, &username)?;
+    // An empty file vault in its place would look like the vault was lost
+    if !backend.available().await {
+        return Err(NO_KEYRING.into());
+    }
+    forget_saved(&location, &username).await;
+    let loaded = |state, key| Loaded { state, key };
+
+    if backend.is_locked().await? {
+        info!("Keyring is locked, waiting for it to be unlocked");
+        return Ok(loaded(State::KeyringLocked, None));
+    }
+    let Some(data) = backend.load(Item::Index).await? else {
+        warn!("No entry in secret store, defaulting to empty");
+        return Ok(loaded(State::Secrets(Vec::new()), None));
+    };
+    if let Ok(index) = serde_json::from_slice::<Index>(&data) {
+        if index.version > migrations::VERSION {
+            return Err(migrations::newer(index.version));
+        }
+        if let Some(sealed) = index.tpm.clone() {
+            info!("Unsealing vault key with the TPM");
+            let key = derive(move || VaultKey::unseal(sealed)).await?;
+            let entries = read_entries(&backend, &location, &username, &index, Some(&key)).await;
+            return Ok(loaded(State::Secrets(entries), Some(key)));
+        }
+        if index.encryption.is_some() {
+            info!("Vault is encrypted, waiting for its passphrase");
+            return Ok(loaded(State::Encrypted, None));
+        }
+        let entries = read_entries(&backend, &location, &username, &index, None).await;
+        info!("Retrieved secret key");
+        return Ok(loaded(State::Secrets(entries), None));
+    }
+
+    let entries = serde_json::from_slice::<Vec<serde_json::Value>>(&data)
+        .map_err(|e| e.to_string())
+        .and_then(|entries| {
+            entries
+                .into_iter()
+                .map(|entry| parse_entry(0, entry))
+                .collect::<Result<Vec<_>, _>>()
+        });
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            let error = format!("Couldn't deserialise secret store: {e}");
+            error!("{error}");
+            let recovery = recover(&backend, &location, &username, data, error).await?;
+            return Ok(loaded(State::Corrupt(recovery), None));
+        }
+    };
+    info!(
+        "Moving {} entries to their own keyring items",
+        entries.len()
+    );
+    write_entries(&backend, &location, &username, &serialize(&entries)?, None).await?;
+    Ok(loaded(State::Secrets(entries), None))
+}
+
+/// Backs up an unreadable index, then reads whatever entries can still be found.
+async fn recover(
+    backend: &impl VaultBackend,
+    location: &Location,
+    username: &str,
+    data: Vec<u8>,
+    error: String,
+) -> Result<Recovery, String> {
+    let mut entries = recovery::salvage_legacy(&data);
+    let ids = recovery::salvage_ids(&data);
+    let generation = recovery::salvage_generation(&data);
+    // Nothing may be written over the data until there's a copy of it
+    let backup = recovery::backup(username, data)
+        .await
+        .map_err(|e| format!("{error}, and it couldn't be backed up: {e}"))?;
+    warn!("Backed up unreadable vault to {}", backup.display());
+
+    for id in &ids {
+        // The version was lost with the index, so the entries are taken to be the oldest
+        match read_entry(backend, location, username, id, generation, 0, None).await {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("Couldn't recover entry {id}: {e}"),
+        }
+    }
+    info!("Recovered {} entries", entries.len());
+    Ok(Recovery {
+        entries,
+        backup,
+        error,
     })
-    .await
-    .map_err(|e| format!("Couldn't join secret retrieving thread: {e}"))??;
+}
+
+/// Reads an encrypted vault, returning its key for writing the vault back.
+pub async fn unlock(
+    username: String,
+    location: Location,
+    passphrase: String,
+) -> Result<(State, VaultKey), String> {
+    info!("Unlocking encrypted vault");
+    forget_saved(&location, &username).await;
+    let backend = Backend::new(&location, &username)?;
+    let index = backend
+        .load(Item::Index)
+        .await?
+        .and_then(|data| serde_json::from_slice::<Index>(&data).ok())
+        .ok_or("Couldn't read the vault index")?;
+    let encryption = index
+        .encryption
+        .clone()
+        .ok_or("The vault isn't encrypted")?;
+    let key = derive(move || VaultKey::unlock(&passphrase, encryption)).await?;
+    let entries = read_entries(&backend, &location, &username, &index, Some(&key)).await;
+    Ok((State::Secrets(entries), key))
+}
+
+/// Lists the users with a vault in the store, sorted by name.
+pub async fn find_users(location: Location) -> Result<Vec<String>, String> {
+    // Any user will do, as it's the store that's searched
+    let mut users = Backend::new(&location, "")?.users().await?;
+    users.sort_unstable();
+    users.dedup();
+    Ok(users)
+}
+
+/// Asks the keyring holding the vault to unlock, after which the vault can be read again.
+pub async fn unlock_keyring(username: String, location: Location) -> Result<(), String> {
+    info!("Unlocking keyring");
+    Backend::new(&location, &username)?.unlock().await
+}
+
+/// Writes the entries that changed since they were last read or written, then the index.
+///
+/// The entries are encrypted with `key` if there is one, and all of them are written again to new
+/// items when the encryption changes, which the index only switches to once they're all written.
+async fn write_entries(
+    backend: &impl VaultBackend,
+    location: &Location,
+    username: &str,
+    entries: &[Serialized],
+    key: Option<&VaultKey>,
+) -> Result<(), String> {
+    if !backend.encrypts() && key.is_none() && !entries.is_empty() {
+        return Err("The vault file needs a passphrase before secrets can be saved".into());
+    }
+    let _writing = WRITING.lock().await;
+    let old = match backend.load(Item::Index).await? {
+        Some(data) => serde_json::from_slice::<Index>(&data).unwrap_or_default(),
+        None => Index::default(),
+    };
+    if old.version > migrations::VERSION {
+        return Err(migrations::newer(old.version));
+    }
+    let (encryption, tpm) = match key.map(|k| &k.source) {
+        None => (None, None),
+        Some(KeySource::Passphrase(encryption)) => (Some(encryption.clone()), None),
+        #[cfg(feature = "tpm")]
+        Some(KeySource::Tpm(sealed)) => (None, Some(sealed.clone())),
+    };
+    let rewrite = encryption != old.encryption || tpm != old.tpm;
+    if rewrite {
+        info!("Vault encryption changed, writing every entry again");
+    }
+    // Entries are stored in the index's format, so upgrading it means writing all of them
+    let rewrite = rewrite || (old.version != migrations::VERSION && !old.entries.is_empty());
+    let generation = if rewrite {
+        old.generation.wrapping_add(1)
+    } else {
+        old.generation
+    };
+
+    let changed: Vec<_> = {
+        let saved = saved();
+        entries
+            .iter()
+            .filter(|entry| {
+                let item = saved_key(location, username, &entry.id);
+                rewrite || saved.get(&item) != Some(&digest(&entry.json))
+            })
+            .collect()
+    };
+    // Written entries are only remembered once the index points at them
+    let mut written = Vec::with_capacity(changed.len());
+    for entry in changed {
+        let secret = match key {
+            Some(key) => encrypt(&key.key, &entry.json)?,
+            None => entry.json.clone(),
+        };
+        backend
+            .save(Item::Entry(&item_name(&entry.id, generation)), secret)
+            .await?;
+        written.push((entry.id.clone(), digest(&entry.json)));
+    }
+
+    let mut index = Index {
+        version: migrations::VERSION,
+        generation,
+        entries: entries.iter().map(|e| e.id.clone()).collect(),
+        encryption,
+        tpm,
+    };
+    // Items the index no longer points at, removed once it's been written
+    let mut stale = Vec::new();
+    for id in &old.entries {
+        if rewrite {
+            stale.push(item_name(id, old.generation));
+        }
+        if index.entries.contains(id) {
+            continue;
+        }
+        if saved().contains_key(&saved_key(location, username, id)) {
+            if !rewrite {
+                stale.push(item_name(id, old.generation));
+            }
+            continue;
+        }
+        // Never loaded, so it wasn't deleted, it just couldn't be read and is kept as it is
+        if rewrite
+            && let Some(data) = backend
+                .load(Item::Entry(&item_name(id, old.generation)))
+                .await?
+        {
+            backend
+                .save(Item::Entry(&item_name(id, generation)), data)
+                .await?;
+        }
+        index.entries.push(id.clone());
+    }
+
+    if index != old {
+        let ser =
+            serde_json::to_vec(&index).map_err(|e| format!("Failed to serialise secrets: {e}"))?;
+        backend.save(Item::Index, ser).await?;
+    }
+
+    let mut saved = saved();
+    for (id, digest) in written {
+        saved.insert(saved_key(location, username, &id), digest);
+    }
+    for id in old.entries.iter().filter(|id| !index.entries.contains(id)) {
+        saved.remove(&saved_key(location, username, id));
+    }
+    drop(saved);
+    for item in stale {
+        // The vault is already consistent, so this only leaves an unused item behind
+        if let Err(e) = backend.delete(Item::Entry(&item)).await {
+            warn!("Couldn't remove old item {item}: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Writes the vault, forgetting what's in storage afterwards if it's being closed.
+pub async fn set_secret_key(
+    username: String,
+    location: Location,
+    secret: Vec<Serialized>,
+    key: Option<VaultKey>,
+    closing: bool,
+) -> Result<(), String> {
+    info!("Setting secrets");
+    let backend = Backend::new(&location, &username)?;
+    let written = write_entries(&backend, &location, &username, &secret, key.as_ref()).await;
+    if closing {
+        forget_saved(&location, &username).await;
+    }
+    written?;
 
     info!("Set secret key");
     Ok(())
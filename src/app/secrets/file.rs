@@ -0,0 +1,110 @@
+//! Files in the app's data directory, for systems without a Secret Service.
+
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncWriteExt;
+
+use super::{Item, VaultBackend};
+
+/// A user's vault as a directory with the index and a file for each entry.
+pub struct Files {
+    dir: PathBuf,
+}
+impl Files {
+    pub fn new(username: &str) -> Result<Self, String> {
+        // Usernames can contain anything, so they're encoded to be safe as a file name
+        let dir = vaults_dir()?.join(hex::encode(username));
+        Ok(Self { dir })
+    }
+
+    fn path(&self, item: Item<'_>) -> PathBuf {
+        match item {
+            Item::Index => self.dir.join("index.json"),
+            Item::Entry(id) => self.dir.join("entries").join(hex::encode(id)),
+        }
+    }
+}
+
+fn vaults_dir() -> Result<PathBuf, String> {
+    Ok(dirs::data_dir()
+        .ok_or("No data directory available")?
+        .join(crate::APP_ID)
+        .join("vaults"))
+}
+
+/// Writes a file only the user can read, replacing it in one step so it's never half written.
+pub async fn write_private(path: &Path, data: Vec<u8>) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("Couldn't create {dir:?}: {e}"))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let write = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .await?;
+        file.write_all(&data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, path).await
+    };
+    write
+        .await
+        .map_err(|e| format!("Couldn't write {path:?}: {e}"))
+}
+
+impl VaultBackend for Files {
+    async fn load(&self, item: Item<'_>) -> Result<Option<Vec<u8>>, String> {
+        let path = self.path(item);
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Couldn't read {path:?}: {e}")),
+        }
+    }
+
+    async fn save(&self, item: Item<'_>, data: Vec<u8>) -> Result<(), String> {
+        write_private(&self.path(item), data).await
+    }
+
+    async fn delete(&self, item: Item<'_>) -> Result<(), String> {
+        let path = self.path(item);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Couldn't remove {path:?}: {e}")),
+        }
+    }
+
+    fn encrypts(&self) -> bool {
+        false
+    }
+
+    async fn users(&self) -> Result<Vec<String>, String> {
+        let dir = vaults_dir()?;
+        let mut vaults = match tokio::fs::read_dir(&dir).await {
+            Ok(vaults) => vaults,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Couldn't list {dir:?}: {e}")),
+        };
+        let mut users = Vec::new();
+        while let Some(vault) = vaults
+            .next_entry()
+            .await
+            .map_err(|e| format!("Couldn't list {dir:?}: {e}"))?
+        {
+            let user = vault
+                .file_name()
+                .to_str()
+                .and_then(|name| hex::decode(name).ok());
+            if let Some(user) = user.and_then(|user| String::from_utf8(user).ok()) {
+                users.push(user);
+            }
+        }
+        Ok(users)
+    }
+}
@@ -0,0 +1,88 @@
+//! Upgrading entries saved in older versions of the vault format.
+//!
+//! The format version is kept in the index and applies to every entry listed in it. Entries are
+//! read as plain JSON first and passed through each migration from their version onwards, so an
+//! older vault is upgraded when it's loaded and written in the current format on the next save.
+
+use serde_json::Value;
+
+/// The version of the vault format written by this build.
+pub const VERSION: u32 = 1;
+
+/// Upgrades an entry from the version before each migration to the one after it, in order.
+const MIGRATIONS: [fn(Value) -> Value; VERSION as usize] = [to_v1];
+
+/// Version 0 entries are the ones saved before the format had a version.
+///
+/// Base32 secrets of 10 characters were always padded out for Microsoft accounts before padding
+/// became an option, so entries saved without the option keep it on.
+fn to_v1(mut entry: Value) -> Value {
+    let Some(fields) = entry.as_object_mut() else {
+        return entry;
+    };
+    let base32 = fields
+        .get("encoding")
+        .is_none_or(|encoding| encoding == "Base32");
+    let short = fields
+        .get("secret")
+        .and_then(Value::as_str)
+        .is_some_and(|secret| secret.len() == 10);
+    if base32 && short && !fields.contains_key("microsoft_padding") {
+        fields.insert("microsoft_padding".into(), Value::Bool(true));
+    }
+    entry
+}
+
+/// Upgrades an entry saved in `version` of the format to the current one.
+pub fn upgrade(version: u32, entry: Value) -> Result<Value, String> {
+    let migrations = MIGRATIONS
+        .get(version as usize..)
+        .ok_or_else(|| newer(version))?;
+    Ok(migrations
+        .iter()
+        .fold(entry, |entry, migration| migration(entry)))
+}
+
+/// Why a vault saved by a newer build can't be read or written.
+pub fn newer(version: u32) -> String {
+    format!(
+        "The vault is in version {version} of the format, which is newer than this app can read"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{VERSION, upgrade};
+
+    #[test]
+    fn short_base32_secrets_keep_padding() {
+        let entry = upgrade(0, json!({ "secret": "ABCDEFGHIJ" })).unwrap();
+        assert_eq!(entry["microsoft_padding"], true);
+        let entry = upgrade(0, json!({ "secret": "ABCDEFGHIJ", "encoding": "Base32" })).unwrap();
+        assert_eq!(entry["microsoft_padding"], true);
+    }
+
+    #[test]
+    fn other_secrets_are_left_alone() {
+        for entry in [
+            json!({ "secret": "JBSWY3DPEHPK3PXP" }),
+            json!({ "secret": "ABCDEFGHIJ", "encoding": "Raw" }),
+            json!({ "secret": "ABCDEFGHIJ", "microsoft_padding": false }),
+        ] {
+            assert_eq!(upgrade(0, entry.clone()).unwrap(), entry);
+        }
+    }
+
+    #[test]
+    fn current_entries_are_unchanged() {
+        let entry = json!({ "secret": "ABCDEFGHIJ" });
+        assert_eq!(upgrade(VERSION, entry.clone()).unwrap(), entry);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        assert!(upgrade(VERSION + 1, json!({})).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! The kernel keyring, through the keyring crate's `linux-native` store.
+
+use super::{Item, VaultBackend};
+
+/// A user's vault in the keyring, with an item for the index and one for each entry.
+pub struct OsKeyring {
+    username: String,
+    service: String,
+}
+impl OsKeyring {
+    pub const fn new(username: String, service: String) -> Self {
+        Self { username, service }
+    }
+
+    fn entry(&self, item: Item<'_>) -> Result<keyring::Entry, String> {
+        let user = match item {
+            Item::Index => self.username.clone(),
+            Item::Entry(id) => format!("{}/entries/{id}", self.username),
+        };
+        keyring::Entry::new(&self.service, &user).map_err(|e| e.to_string())
+    }
+
+    /// Whether the kernel keyring can be used, as it's not available in every sandbox.
+    pub async fn available(&self) -> bool {
+        let Ok(entry) = self.entry(Item::Index) else {
+            return false;
+        };
+        blocking(move || {
+            Ok(!matches!(
+                entry.get_secret(),
+                Err(keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_))
+            ))
+        })
+        .await
+        .unwrap_or(false)
+    }
+}
+
+/// Runs a keyring call, which blocks, off the async runtime.
+async fn blocking<T: Send + 'static>(
+    call: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(call)
+        .await
+        .map_err(|e| format!("Couldn't join keyring thread: {e}"))?
+}
+
+impl VaultBackend for OsKeyring {
+    async fn load(&self, item: Item<'_>) -> Result<Option<Vec<u8>>, String> {
+        let entry = self.entry(item)?;
+        blocking(move || match entry.get_secret() {
+            Ok(secr) => Ok(Some(secr)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        })
+        .await
+    }
+
+    async fn save(&self, item: Item<'_>, data: Vec<u8>) -> Result<(), String> {
+        let entry = self.entry(item)?;
+        blocking(move || entry.set_secret(&data).map_err(|e| e.to_string())).await
+    }
+
+    async fn delete(&self, item: Item<'_>) -> Result<(), String> {
+        let entry = self.entry(item)?;
+        blocking(move || match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        })
+        .await
+    }
+
+    fn encrypts(&self) -> bool {
+        true
+    }
+
+    async fn users(&self) -> Result<Vec<String>, String> {
+        // The kernel lists the keys the user can see, with the descriptions the keyring crate
+        // gives them of `keyring-rs:<user>@<service>`
+        let keys = tokio::fs::read_to_string("/proc/keys")
+            .await
+            .map_err(|e| format!("Couldn't list the kernel's keys: {e}"))?;
+        let suffix = format!("@{}", self.service);
+        Ok(keys
+            .lines()
+            .filter_map(|line| {
+                let description = line.split_once(" keyring-rs:")?.1;
+                // User keys are followed by the length of their data
+                let (name, _) = description.rsplit_once(": ")?;
+                let user = name.strip_suffix(&suffix)?;
+                // The type comes after the id, flags, usage, timeout, permissions, uid and gid
+                let is_user_key = line.split_whitespace().nth(7) == Some("user");
+                // Only the index is stored under the bare username
+                (is_user_key && !user.contains("/entries/")).then(|| user.to_string())
+            })
+            .collect())
+    }
+}
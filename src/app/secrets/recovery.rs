@@ -0,0 +1,75 @@
+//! Recovering what's left of a vault whose index can't be read.
+//!
+//! The unreadable index is copied to the data directory first, so nothing is lost when the
+//! recovered entries are saved over it.
+
+use std::path::PathBuf;
+
+use super::super::entry::Entry;
+
+/// A vault that couldn't be read, waiting for the user to decide what to keep.
+#[derive(Debug, Clone)]
+pub struct Recovery {
+    /// The entries that could still be read.
+    pub entries: Vec<Entry>,
+    /// Where the unreadable data was copied to.
+    pub backup: PathBuf,
+    /// Why the vault couldn't be read.
+    pub error: String,
+}
+
+/// Copies unreadable vault data to the data directory, returning where it was written.
+pub async fn backup(username: &str, data: Vec<u8>) -> Result<PathBuf, String> {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dirs::data_dir()
+        .ok_or("No data directory available")?
+        .join(crate::APP_ID)
+        .join("recovery")
+        .join(format!("{}-{time}.json", hex::encode(username)));
+    super::file::write_private(&path, data).await?;
+    Ok(path)
+}
+
+/// Finds the complete entry ids at the start of a damaged index, which is usually cut short.
+pub fn salvage_ids(data: &[u8]) -> Vec<String> {
+    let data = String::from_utf8_lossy(data);
+    let Some(start) = data.find("\"entries\"") else {
+        return Vec::new();
+    };
+    let Some(list) = data[start..].split_once('[').map(|(_, list)| list) else {
+        return Vec::new();
+    };
+    let list = list.split_once(']').map_or(list, |(list, _)| list);
+    // Ids are hex, so any quoted string in the list is one, unless it was cut off
+    list.split(',')
+        .filter_map(|id| id.trim().strip_prefix('"')?.strip_suffix('"'))
+        .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Finds the generation of the items in a damaged index, 0 if it was lost as well.
+pub fn salvage_generation(data: &[u8]) -> u32 {
+    let data = String::from_utf8_lossy(data);
+    data.split_once("\"generation\":")
+        .and_then(|(_, rest)| {
+            let digits = rest.trim_start();
+            let end = digits
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(digits.len());
+            digits[..end].parse().ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads whichever entries of an old single-item vault are still intact.
+pub fn salvage_legacy(data: &[u8]) -> Vec<Entry> {
+    serde_json::from_slice::<Vec<serde_json::Value>>(data)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| super::parse_entry(0, entry).ok())
+        .collect()
+}
@@ -0,0 +1,186 @@
+//! The Secret Service, talked to directly over D-Bus with oo7.
+//!
+//! Unlike going through the keyring crate, items are found by their attributes in a collection,
+//! secrets travel over an encrypted session, and a locked collection or item is unlocked through
+//! the service's own prompt.
+
+use std::collections::HashMap;
+
+use oo7::dbus::{Collection, Service};
+
+use super::{Item, VaultBackend};
+
+/// A user's vault in a collection, with an item for the index and one for each entry.
+pub struct SecretService {
+    username: String,
+    /// The application attribute of the items.
+    service: String,
+    /// The label of the collection, the default collection if `None`.
+    collection: Option<String>,
+}
+impl SecretService {
+    pub const fn new(username: String, service: String, collection: Option<String>) -> Self {
+        Self {
+            username,
+            service,
+            collection,
+        }
+    }
+
+    fn attributes<'a>(&'a self, item: Item<'a>) -> HashMap<&'a str, &'a str> {
+        let mut attributes = HashMap::from([
+            ("application", self.service.as_str()),
+            ("username", self.username.as_str()),
+        ]);
+        match item {
+            Item::Index => {
+                attributes.insert("item", "index");
+            }
+            Item::Entry(id) => {
+                attributes.insert("item", "entry");
+                attributes.insert("id", id);
+            }
+        }
+        attributes
+    }
+
+    fn label(&self, item: Item<'_>) -> String {
+        match item {
+            Item::Index => format!("TOTP vault of {}", self.username),
+            Item::Entry(id) => format!("TOTP entry {id} of {}", self.username),
+        }
+    }
+
+    /// Opens the collection, unlocking it first if it's locked.
+    async fn collection<'a>(&self, service: &'a Service) -> Result<Collection<'a>, String> {
+        let collection = self.locked_collection(service).await?;
+        if collection.is_locked().await.unwrap_or(false) {
+            collection
+                .unlock(None)
+                .await
+                .map_err(|e| format!("Couldn't unlock the collection: {e}"))?;
+        }
+        Ok(collection)
+    }
+
+    /// Opens the collection as it is, which may be locked.
+    async fn locked_collection<'a>(&self, service: &'a Service) -> Result<Collection<'a>, String> {
+        match &self.collection {
+            None => service
+                .default_collection()
+                .await
+                .map_err(|e| format!("Couldn't open the default collection: {e}")),
+            Some(label) => service
+                .with_label(label)
+                .await
+                .map_err(|e| format!("Couldn't open collection {label}: {e}"))?
+                .ok_or_else(|| format!("There's no collection named {label}")),
+        }
+    }
+
+    /// Whether the Secret Service can be reached.
+    pub async fn available() -> bool {
+        Service::new().await.is_ok()
+    }
+}
+
+async fn connect() -> Result<Service, String> {
+    Service::new()
+        .await
+        .map_err(|e| format!("Couldn't connect to the Secret Service: {e}"))
+}
+
+impl VaultBackend for SecretService {
+    async fn load(&self, item: Item<'_>) -> Result<Option<Vec<u8>>, String> {
+        let service = connect().await?;
+        let collection = self.collection(&service).await?;
+        let found = collection
+            .search_items(&self.attributes(item))
+            .await
+            .map_err(|e| format!("Couldn't search the Secret Service: {e}"))?;
+        let Some(found) = found.first() else {
+            return Ok(None);
+        };
+        if found.is_locked().await.unwrap_or(false) {
+            found
+                .unlock(None)
+                .await
+                .map_err(|e| format!("Couldn't unlock {}: {e}", self.label(item)))?;
+        }
+        let secret = found
+            .secret()
+            .await
+            .map_err(|e| format!("Couldn't read {}: {e}", self.label(item)))?;
+        Ok(Some(secret.as_bytes().to_vec()))
+    }
+
+    async fn save(&self, item: Item<'_>, data: Vec<u8>) -> Result<(), String> {
+        let service = connect().await?;
+        let collection = self.collection(&service).await?;
+        collection
+            .create_item(
+                &self.label(item),
+                &self.attributes(item),
+                oo7::Secret::blob(data),
+                true,
+                None,
+            )
+            .await
+            .map_err(|e| format!("Couldn't write {}: {e}", self.label(item)))?;
+        Ok(())
+    }
+
+    async fn delete(&self, item: Item<'_>) -> Result<(), String> {
+        let service = connect().await?;
+        let collection = self.collection(&service).await?;
+        let found = collection
+            .search_items(&self.attributes(item))
+            .await
+            .map_err(|e| format!("Couldn't search the Secret Service: {e}"))?;
+        for found in found {
+            found
+                .delete(None)
+                .await
+                .map_err(|e| format!("Couldn't remove {}: {e}", self.label(item)))?;
+        }
+        Ok(())
+    }
+
+    fn encrypts(&self) -> bool {
+        true
+    }
+
+    async fn is_locked(&self) -> Result<bool, String> {
+        let service = connect().await?;
+        self.locked_collection(&service)
+            .await?
+            .is_locked()
+            .await
+            .map_err(|e| format!("Couldn't check whether the collection is locked: {e}"))
+    }
+
+    async fn users(&self) -> Result<Vec<String>, String> {
+        let service = connect().await?;
+        // Attributes can be searched while the collection is locked, so this doesn't prompt
+        let collection = self.locked_collection(&service).await?;
+        let attributes = HashMap::from([("application", self.service.as_str()), ("item", "index")]);
+        let found = collection
+            .search_items(&attributes)
+            .await
+            .map_err(|e| format!("Couldn't search the Secret Service: {e}"))?;
+        let mut users = Vec::new();
+        for item in found {
+            if let Ok(mut attributes) = item.attributes().await
+                && let Some(user) = attributes.remove("username")
+            {
+                users.push(user);
+            }
+        }
+        Ok(users)
+    }
+
+    async fn unlock(&self) -> Result<(), String> {
+        let service = connect().await?;
+        self.collection(&service).await.map(drop)
+    }
+}
@@ -0,0 +1,125 @@
+//! Watches for the desktop session locking, so the vault isn't left open behind the lock screen,
+//! and for the system waking from sleep, which leaves the codes' timers behind.
+//!
+//! Both logind and the `org.freedesktop.ScreenSaver` interface are watched for locks, as lock
+//! screens differ in which of them they report through.
+
+use cosmic::iced::{
+    Subscription,
+    futures::{FutureExt, SinkExt, StreamExt, channel::mpsc::Sender, stream},
+};
+use tracing::{info, warn};
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    #[zbus(signal)]
+    fn active_changed(&self, active: bool) -> zbus::Result<()>;
+}
+
+/// Yields whenever the session locks.
+pub fn subscription() -> Subscription<()> {
+    Subscription::run_with_id(
+        "session-lock",
+        cosmic::iced::stream::channel(1, |output| async move {
+            let logind = logind(output.clone()).map(|r| {
+                if let Err(e) = r {
+                    warn!("Can't watch logind for session locks: {e}");
+                }
+            });
+            let screensaver = screensaver(output).map(|r| {
+                if let Err(e) = r {
+                    warn!("Can't watch the screensaver for session locks: {e}");
+                }
+            });
+            cosmic::iced::futures::join!(logind, screensaver);
+        }),
+    )
+}
+
+async fn logind(mut output: Sender<()>) -> zbus::Result<()> {
+    let connection = zbus::Connection::system().await?;
+    let session = SessionProxy::new(&connection).await?;
+    let locks = session.receive_lock().await?.map(|_| true);
+    // Some lock screens only set the hint rather than asking logind to lock
+    let hints = session
+        .receive_locked_hint_changed()
+        .await
+        .then(|hint| async move { hint.get().await.unwrap_or(false) });
+    let mut changes = stream::select(locks.boxed(), hints.boxed());
+    info!("Watching logind for session locks");
+
+    while let Some(locked) = changes.next().await {
+        if locked && output.send(()).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn screensaver(mut output: Sender<()>) -> zbus::Result<()> {
+    let connection = zbus::Connection::session().await?;
+    let screensaver = ScreenSaverProxy::new(&connection).await?;
+    let mut changes = screensaver.receive_active_changed().await?;
+    info!("Watching the screensaver for session locks");
+
+    while let Some(change) = changes.next().await {
+        if change.args()?.active && output.send(()).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Yields whenever the system wakes from sleep.
+pub fn resumes() -> Subscription<()> {
+    Subscription::run_with_id(
+        "session-resume",
+        cosmic::iced::stream::channel(1, |output| async move {
+            if let Err(e) = wakes(output).await {
+                warn!("Can't watch logind for waking from sleep: {e}");
+            }
+        }),
+    )
+}
+
+async fn wakes(mut output: Sender<()>) -> zbus::Result<()> {
+    let connection = zbus::Connection::system().await?;
+    let manager = ManagerProxy::new(&connection).await?;
+    let mut changes = manager.receive_prepare_for_sleep().await?;
+    info!("Watching logind for waking from sleep");
+
+    while let Some(change) = changes.next().await {
+        // Sent with true before sleeping and false after waking
+        if !change.args()?.start && output.send(()).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
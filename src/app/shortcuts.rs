@@ -0,0 +1,69 @@
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use cosmic::iced::{
+    Subscription,
+    futures::{SinkExt, StreamExt, channel::mpsc::Sender},
+};
+use tracing::info;
+
+/// Opens the popup and focuses the search.
+pub const OPEN_POPUP: &str = "open-popup";
+/// Copies the quick copy entry's code without showing anything.
+pub const COPY_CODE: &str = "copy-code";
+/// The triggers suggested to the portal when none are configured.
+pub const DEFAULT_OPEN_POPUP_TRIGGER: &str = "CTRL+ALT+A";
+pub const DEFAULT_COPY_CODE_TRIGGER: &str = "CTRL+ALT+C";
+
+/// Registers the global shortcuts with the desktop portal, yielding the ids of activated shortcuts.
+///
+/// The triggers are only preferences, the user has the final say through the portal.
+pub fn subscription(open_popup: String, copy_code: String) -> Subscription<Result<String, String>> {
+    Subscription::run_with_id(
+        ("global-shortcuts", open_popup.clone(), copy_code.clone()),
+        cosmic::iced::stream::channel(1, move |mut output| async move {
+            let shortcuts = [
+                NewShortcut::new(OPEN_POPUP, "Open the TOTP client")
+                    .preferred_trigger(Some(open_popup.as_str())),
+                NewShortcut::new(COPY_CODE, "Copy the quick copy TOTP code")
+                    .preferred_trigger(Some(copy_code.as_str())),
+            ];
+            if let Err(e) = listen(&shortcuts, &mut output).await {
+                _ = output.send(Err(e)).await;
+            }
+        }),
+    )
+}
+
+async fn listen(
+    shortcuts: &[NewShortcut],
+    output: &mut Sender<Result<String, String>>,
+) -> Result<(), String> {
+    let portal = GlobalShortcuts::new()
+        .await
+        .map_err(|e| format!("Couldn't access global shortcuts portal: {e}"))?;
+    // Dropping the session unbinds the shortcuts, so it's kept alive for as long as we listen
+    let session = portal
+        .create_session()
+        .await
+        .map_err(|e| format!("Couldn't create global shortcuts session: {e}"))?;
+    portal
+        .bind_shortcuts(&session, shortcuts, None)
+        .await
+        .and_then(|r| r.response())
+        .map_err(|e| format!("Couldn't bind global shortcuts: {e}"))?;
+    let mut activated = portal
+        .receive_activated()
+        .await
+        .map_err(|e| format!("Couldn't listen for global shortcuts: {e}"))?;
+    info!("Global shortcuts bound");
+
+    while let Some(activation) = activated.next().await {
+        if output
+            .send(Ok(activation.shortcut_id().to_owned()))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    Ok(())
+}
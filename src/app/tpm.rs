@@ -0,0 +1,109 @@
+//! Sealing the vault key against the TPM, so it can only be recovered on this machine.
+//!
+//! The key is stored as a sealed data object under a primary key of the owner hierarchy. The
+//! primary key is recreated from the same template whenever it's needed, which the TPM derives
+//! from its own seed, so only the sealed object itself has to be stored.
+
+use tss_esapi::{
+    Context, TctiNameConf,
+    attributes::ObjectAttributesBuilder,
+    handles::KeyHandle,
+    interface_types::{
+        algorithm::{HashingAlgorithm, PublicAlgorithm},
+        key_bits::RsaKeyBits,
+        resource_handles::Hierarchy,
+    },
+    structures::{
+        Digest, KeyedHashScheme, Private, Public, PublicBuilder, PublicKeyedHashParameters,
+        RsaExponent, SensitiveData, SymmetricDefinitionObject,
+    },
+    traits::{Marshall, UnMarshall},
+    utils::create_restricted_decryption_rsa_public,
+};
+
+/// The kernel's resource manager, which unlike the raw `/dev/tpm0` can be shared with other
+/// programs and is usually the one users are allowed to open.
+const DEVICE: &str = "/dev/tpmrm0";
+
+fn context() -> Result<Context, String> {
+    // TCTI overrides the default device, as with the tpm2-tools
+    let tcti = TctiNameConf::from_environment_variable()
+        .unwrap_or_else(|_| TctiNameConf::Device(DEVICE.parse().unwrap_or_default()));
+    Context::new(tcti).map_err(|e| format!("Couldn't open the TPM: {e}"))
+}
+
+fn primary(context: &mut Context) -> Result<KeyHandle, String> {
+    let template = create_restricted_decryption_rsa_public(
+        SymmetricDefinitionObject::AES_128_CFB,
+        RsaKeyBits::Rsa2048,
+        RsaExponent::default(),
+    )
+    .map_err(|e| format!("Couldn't build TPM primary key template: {e}"))?;
+    context
+        .execute_with_nullauth_session(|ctx| {
+            ctx.create_primary(Hierarchy::Owner, template, None, None, None, None)
+        })
+        .map(|primary| primary.key_handle)
+        .map_err(|e| format!("Couldn't create TPM primary key: {e}"))
+}
+
+/// Seals a key, returning the sealed object's public and private parts.
+pub fn seal(key: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut context = context()?;
+    let primary = primary(&mut context)?;
+
+    let attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_user_with_auth(true)
+        .build()
+        .map_err(|e| format!("Couldn't build sealed object attributes: {e}"))?;
+    let template = PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::KeyedHash)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(attributes)
+        .with_keyed_hash_parameters(PublicKeyedHashParameters::new(KeyedHashScheme::Null))
+        .with_keyed_hash_unique_identifier(Digest::default())
+        .build()
+        .map_err(|e| format!("Couldn't build sealed object template: {e}"))?;
+    let data = SensitiveData::try_from(key.to_vec())
+        .map_err(|e| format!("Couldn't prepare key for sealing: {e}"))?;
+
+    let sealed = context
+        .execute_with_nullauth_session(|ctx| {
+            ctx.create(primary, template, None, Some(data), None, None)
+        })
+        .map_err(|e| format!("Couldn't seal key with the TPM: {e}"));
+    _ = context.flush_context(primary.into());
+    let sealed = sealed?;
+
+    let public = sealed
+        .out_public
+        .marshall()
+        .map_err(|e| format!("Couldn't store sealed key: {e}"))?;
+    Ok((public, sealed.out_private.value().to_vec()))
+}
+
+/// Recovers a key sealed by [`seal`], which only works with the same TPM.
+pub fn unseal(public: &[u8], private: &[u8]) -> Result<[u8; 32], String> {
+    let public =
+        Public::unmarshall(public).map_err(|e| format!("Invalid sealed key public part: {e}"))?;
+    let private = Private::try_from(private.to_vec())
+        .map_err(|e| format!("Invalid sealed key private part: {e}"))?;
+
+    let mut context = context()?;
+    let primary = primary(&mut context)?;
+    let unsealed = context.execute_with_nullauth_session(|ctx| {
+        let object = ctx.load(primary, private, public)?;
+        let data = ctx.unseal(object.into());
+        ctx.flush_context(object.into())?;
+        data
+    });
+    _ = context.flush_context(primary.into());
+
+    unsealed
+        .map_err(|e| format!("Couldn't unseal key with the TPM: {e}"))?
+        .value()
+        .try_into()
+        .map_err(|_| "Unsealed key has the wrong length".into())
+}